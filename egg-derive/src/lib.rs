@@ -0,0 +1,608 @@
+/*!
+Derive macros for [`egg`](https://docs.rs/egg) languages.
+
+These are an alternative to `egg`'s `define_language!` macro.
+Since they are derives on an ordinary `enum`, the `enum` can have generic
+parameters, doc comments and any other attributes, and mistakes are
+reported at the variant that caused them.
+
+Enable them with the `derive` feature of `egg`, which re-exports them.
+
+# Variants
+
+Each variant takes one of these forms:
+
+- `#[op = "pi"] Pi`: an operator with no children.
+- `#[op = "+"] Add([Id; 2])`: an operator whose children are any
+  `LanguageChildren` type, like `Id`, `[Id; N]`, `Box<[Id]>` or `Vec<Id>`.
+- `Num(i32)`: a data variant with no children.
+  The field is parsed with `FromStr` and printed with `Display`.
+- `Call(Symbol, Vec<Id>)`: a data variant with children.
+- `#[op = "ext"] Ext(Type, Id)`: a data variant with children and an
+  operator, printed and parsed as the operator and the data joined by a
+  dot, like `ext.i64`.
+
+Like with `define_language!`, variants can also have named fields in the
+same forms, like `#[op = "load"] Load { addr: Id }` or
+`#[op = "store"] Store { ty: Type, args: [Id; 2] }`.
+Variants with children can also be marked `#[ac]` to make them
+associative-commutative, see `Language::is_ac`.
+Like with `define_language!`, `#[sort(num <- num, num)]` declares the sort
+of a variant and then the sorts of its children, see `Language::sort`.
+Like with `define_language!`, `FromOp` tries the variants in order.
+
+# Example
+```
+use egg::*;
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Language, FromOp, Display)]
+enum Math<N> {
+    /// The constant pi.
+    #[op = "pi"]
+    Pi,
+    #[ac]
+    #[op = "+"]
+    Add([Id; 2]),
+    #[sort(num <- num)]
+    #[op = "-"]
+    Neg(Id),
+    #[sort(num)]
+    Num(N),
+    Call(Symbol, Vec<Id>),
+    #[op = "load"]
+    Load { addr: Id },
+}
+
+let expr: RecExpr<Math<i64>> = "(+ 1 (- (f pi (load 2))))".parse().unwrap();
+assert_eq!(expr.to_string(), "(+ 1 (- (f pi (load 2))))");
+assert_eq!(expr.as_ref()[0], Math::Num(1));
+assert!(expr.as_ref().last().unwrap().is_ac());
+assert_eq!(expr.as_ref()[0].sort(), Some("num".into()));
+```
+*/
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    parse::ParseStream, parse_macro_input, parse_quote, punctuated::Punctuated, spanned::Spanned,
+    Data, DeriveInput, Error, Expr, Fields, Generics, Ident, Lit, LitStr, Meta, Result, Token,
+    Type,
+};
+
+/// Derives `egg::Language` for an `enum`, see the [crate] docs.
+#[proc_macro_derive(Language, attributes(op, ac, sort))]
+pub fn derive_language(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input, language_impl)
+}
+
+/// Derives `egg::FromOp` for an `enum`, see the [crate] docs.
+#[proc_macro_derive(FromOp, attributes(op, ac, sort))]
+pub fn derive_from_op(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input, from_op_impl)
+}
+
+/// Derives `std::fmt::Display` for an `enum`, printing the operator of
+/// each variant, see the [crate] docs.
+#[proc_macro_derive(Display, attributes(op, ac, sort))]
+pub fn derive_display(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input, display_impl)
+}
+
+fn expand(input: &DeriveInput, f: fn(&DeriveInput, &[Variant]) -> TokenStream2) -> TokenStream {
+    match parse_variants(input) {
+        Ok(variants) => f(input, &variants).into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+#[allow(clippy::large_enum_variant)]
+enum Kind {
+    /// `#[op = "pi"] Pi`
+    Leaf(LitStr),
+    /// `#[op = "+"] Add(ids)`
+    Op(LitStr, Type),
+    /// `Num(data)`
+    Data(Type),
+    /// `Call(data, ids)`, or `#[op = "ext"] Ext(data, ids)`
+    DataOp(Option<LitStr>, Type, Type),
+}
+
+/// How a variant's fields are written.
+enum Shape {
+    Unit,
+    Tuple,
+    Named(Vec<Ident>),
+}
+
+struct Variant {
+    ident: Ident,
+    shape: Shape,
+    kind: Kind,
+    ac: bool,
+    /// The sort from `#[sort(res <- children...)]` and its children's sorts.
+    sort: Option<(Ident, Vec<Ident>)>,
+}
+
+impl Variant {
+    /// Returns `Self::Variant` applied to the given fields, as either a
+    /// pattern or an expression.
+    fn build(&self, fields: &[TokenStream2]) -> TokenStream2 {
+        let v = &self.ident;
+        match &self.shape {
+            Shape::Unit => quote!(Self::#v),
+            Shape::Tuple => quote!(Self::#v(#(#fields),*)),
+            Shape::Named(names) => quote!(Self::#v { #(#names: #fields),* }),
+        }
+    }
+
+    /// Returns a pattern matching any value of this variant.
+    fn any(&self) -> TokenStream2 {
+        let v = &self.ident;
+        match &self.shape {
+            Shape::Unit => quote!(Self::#v),
+            Shape::Tuple => quote!(Self::#v(..)),
+            Shape::Named(_) => quote!(Self::#v { .. }),
+        }
+    }
+}
+
+fn parse_variants(input: &DeriveInput) -> Result<Vec<Variant>> {
+    let data = match &input.data {
+        Data::Enum(data) => data,
+        _ => {
+            return Err(Error::new(
+                input.ident.span(),
+                "egg languages can only be derived for enums",
+            ))
+        }
+    };
+
+    let mut variants = vec![];
+    for variant in &data.variants {
+        let mut op = None;
+        let mut ac = false;
+        let mut sort = None;
+        for attr in &variant.attrs {
+            if attr.path().is_ident("ac") {
+                attr.meta.require_path_only()?;
+                ac = true;
+            } else if attr.path().is_ident("sort") {
+                if sort.is_some() {
+                    return Err(Error::new(attr.span(), "duplicate `#[sort]` attribute"));
+                }
+                sort = Some(attr.parse_args_with(parse_sort)?);
+            } else if attr.path().is_ident("op") {
+                if op.is_some() {
+                    return Err(Error::new(attr.span(), "duplicate `#[op]` attribute"));
+                }
+                op = Some(parse_op(&attr.meta)?);
+            }
+        }
+
+        let (shape, fields): (Shape, Vec<&Type>) = match &variant.fields {
+            Fields::Unit => (Shape::Unit, vec![]),
+            Fields::Unnamed(fields) => {
+                (Shape::Tuple, fields.unnamed.iter().map(|f| &f.ty).collect())
+            }
+            Fields::Named(fields) => (
+                Shape::Named(fields.named.iter().flat_map(|f| f.ident.clone()).collect()),
+                fields.named.iter().map(|f| &f.ty).collect(),
+            ),
+        };
+
+        let ident = &variant.ident;
+        let kind = match (op, fields.as_slice()) {
+            (Some(op), []) => Kind::Leaf(op),
+            (Some(op), [ids]) => Kind::Op(op, (*ids).clone()),
+            (None, [data]) => Kind::Data((*data).clone()),
+            (op, [data, ids]) => Kind::DataOp(op, (*data).clone(), (*ids).clone()),
+            (None, []) => {
+                return Err(Error::new(
+                    ident.span(),
+                    format!("variant `{}` needs an `#[op = \"...\"]` attribute", ident),
+                ))
+            }
+            (_, _) => {
+                return Err(Error::new(
+                    variant.fields.span(),
+                    "a variant can only have a data field and a children field",
+                ))
+            }
+        };
+
+        if ac && matches!(kind, Kind::Leaf(_) | Kind::Data(_)) {
+            return Err(Error::new(
+                ident.span(),
+                format!(
+                    "variant `{}` has no children, so it can't be `#[ac]`",
+                    ident
+                ),
+            ));
+        }
+
+        variants.push(Variant {
+            ident: ident.clone(),
+            shape,
+            kind,
+            ac,
+            sort,
+        });
+    }
+    Ok(variants)
+}
+
+fn parse_op(meta: &Meta) -> Result<LitStr> {
+    if let Meta::NameValue(nv) = meta {
+        if let Expr::Lit(lit) = &nv.value {
+            if let Lit::Str(s) = &lit.lit {
+                return Ok(s.clone());
+            }
+        }
+    }
+    Err(Error::new(
+        meta.span(),
+        "expected an operator string like `#[op = \"+\"]`",
+    ))
+}
+
+/// Parses the inside of `#[sort(res <- a, b)]`, where `<- a, b` is optional.
+fn parse_sort(input: ParseStream) -> Result<(Ident, Vec<Ident>)> {
+    let res = input.parse()?;
+    let mut children = vec![];
+    if input.peek(Token![<-]) {
+        input.parse::<Token![<-]>()?;
+        children = Punctuated::<Ident, Token![,]>::parse_terminated(input)?
+            .into_iter()
+            .collect();
+    }
+    Ok((res, children))
+}
+
+/// Adds `bound` for every field type if the enum has type parameters,
+/// so generic languages only need the bounds their variants use.
+fn add_bounds<'a>(
+    generics: &Generics,
+    types: impl Iterator<Item = &'a Type>,
+    bound: TokenStream2,
+) -> Generics {
+    let mut generics = generics.clone();
+    if generics.type_params().next().is_none() {
+        return generics;
+    }
+    let where_clause = generics.make_where_clause();
+    for ty in types {
+        where_clause.predicates.push(parse_quote!(#ty: #bound));
+    }
+    generics
+}
+
+/// Adds `bound` on the enum itself if it has type parameters, for the
+/// supertraits whose derives are conditional on those parameters.
+fn add_self_bound(input: &DeriveInput, generics: &Generics, bound: TokenStream2) -> Generics {
+    let mut generics = generics.clone();
+    if generics.type_params().next().is_none() {
+        return generics;
+    }
+    let name = &input.ident;
+    let (_, ty_generics, _) = input.generics.split_for_impl();
+    generics
+        .make_where_clause()
+        .predicates
+        .push(parse_quote!(#name #ty_generics: #bound));
+    generics
+}
+
+fn data_types(variants: &[Variant]) -> impl Iterator<Item = &Type> {
+    variants.iter().filter_map(|v| match &v.kind {
+        Kind::Data(data) | Kind::DataOp(_, data, _) => Some(data),
+        _ => None,
+    })
+}
+
+fn children_types(variants: &[Variant]) -> impl Iterator<Item = &Type> {
+    variants.iter().filter_map(|v| match &v.kind {
+        Kind::Op(_, ids) | Kind::DataOp(_, _, ids) => Some(ids),
+        _ => None,
+    })
+}
+
+fn language_impl(input: &DeriveInput, variants: &[Variant]) -> TokenStream2 {
+    let name = &input.ident;
+    let generics = add_bounds(
+        &input.generics,
+        data_types(variants),
+        quote!(::std::cmp::PartialEq + ::std::clone::Clone),
+    );
+    let generics = add_bounds(
+        &generics,
+        children_types(variants),
+        quote!(::egg::LanguageChildren),
+    );
+    let generics = add_self_bound(
+        input,
+        &generics,
+        quote!(
+            ::std::fmt::Debug
+                + ::std::clone::Clone
+                + ::std::cmp::Eq
+                + ::std::cmp::Ord
+                + ::std::hash::Hash
+        ),
+    );
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let mut matches = vec![];
+    let mut children = vec![];
+    let mut children_mut = vec![];
+    let mut with_children = vec![];
+    let mut is_ac = vec![];
+    let mut sorts = vec![];
+    let mut child_sorts = vec![];
+    for variant in variants {
+        let any = variant.any();
+        match &variant.kind {
+            Kind::Leaf(_) => {
+                let unit = variant.build(&[]);
+                matches.push(quote!((#unit, #unit) => true,));
+                children.push(quote!(#unit => &[],));
+                children_mut.push(quote!(#unit => &mut [],));
+                with_children.push(quote!(
+                    (#unit, children) if children.is_empty() => Some(#unit),
+                ));
+            }
+            Kind::Op(_, ids) => {
+                let (l, r) = (variant.build(&[quote!(l)]), variant.build(&[quote!(r)]));
+                let pat = variant.build(&[quote!(ids)]);
+                let new = variant.build(&[quote!(
+                    <#ids as ::egg::LanguageChildren>::from_vec(children)
+                )]);
+                matches.push(quote!(
+                    (#l, #r) => {
+                        ::egg::LanguageChildren::len(l) == ::egg::LanguageChildren::len(r)
+                    }
+                ));
+                children.push(quote!(#pat => ::egg::LanguageChildren::as_slice(ids),));
+                children_mut.push(quote!(#pat => ::egg::LanguageChildren::as_mut_slice(ids),));
+                with_children.push(quote!(
+                    (#any, children)
+                        if <#ids as ::egg::LanguageChildren>::can_be_length(children.len()) =>
+                    {
+                        Some(#new)
+                    }
+                ));
+            }
+            Kind::Data(_) => {
+                let (l, r) = (variant.build(&[quote!(l)]), variant.build(&[quote!(r)]));
+                let pat = variant.build(&[quote!(data)]);
+                let new = variant.build(&[quote!(::std::clone::Clone::clone(data))]);
+                matches.push(quote!((#l, #r) => l == r,));
+                children.push(quote!(#any => &[],));
+                children_mut.push(quote!(#any => &mut [],));
+                with_children.push(quote!(
+                    (#pat, children) if children.is_empty() => Some(#new),
+                ));
+            }
+            Kind::DataOp(_, _, ids) => {
+                let l = variant.build(&[quote!(d1), quote!(l)]);
+                let r = variant.build(&[quote!(d2), quote!(r)]);
+                let pat = variant.build(&[quote!(_), quote!(ids)]);
+                let data = variant.build(&[quote!(data), quote!(_)]);
+                let new = variant.build(&[
+                    quote!(::std::clone::Clone::clone(data)),
+                    quote!(<#ids as ::egg::LanguageChildren>::from_vec(children)),
+                ]);
+                matches.push(quote!(
+                    (#l, #r) => {
+                        d1 == d2
+                            && ::egg::LanguageChildren::len(l) == ::egg::LanguageChildren::len(r)
+                    }
+                ));
+                children.push(quote!(#pat => ::egg::LanguageChildren::as_slice(ids),));
+                children_mut.push(quote!(#pat => ::egg::LanguageChildren::as_mut_slice(ids),));
+                with_children.push(quote!(
+                    (#data, children)
+                        if <#ids as ::egg::LanguageChildren>::can_be_length(children.len()) =>
+                    {
+                        Some(#new)
+                    }
+                ));
+            }
+        }
+        if variant.ac {
+            is_ac.push(quote!(#any => true,));
+        }
+        if let Some((res, children)) = &variant.sort {
+            let res = res.to_string();
+            let children = children.iter().map(|c| c.to_string());
+            sorts.push(quote!(#any => Some(#res),));
+            child_sorts.push(quote!(#any => &[#(#children),*],));
+        }
+    }
+
+    quote! {
+        impl #impl_generics ::egg::Language for #name #ty_generics #where_clause {
+            #[inline(always)]
+            fn matches(&self, other: &Self) -> bool {
+                match (self, other) {
+                    #(#matches)*
+                    _ => false,
+                }
+            }
+
+            fn children(&self) -> &[::egg::Id] {
+                match self {
+                    #(#children)*
+                }
+            }
+
+            fn children_mut(&mut self) -> &mut [::egg::Id] {
+                match self {
+                    #(#children_mut)*
+                }
+            }
+
+            fn with_children(
+                &self,
+                children: ::std::vec::Vec<::egg::Id>,
+            ) -> ::std::option::Option<Self> {
+                match (self, children) {
+                    #(#with_children)*
+                    _ => None,
+                }
+            }
+
+            #[allow(unreachable_patterns)]
+            fn is_ac(&self) -> bool {
+                match self {
+                    #(#is_ac)*
+                    _ => false,
+                }
+            }
+
+            #[allow(unreachable_patterns)]
+            fn sort(&self) -> ::std::option::Option<::egg::Symbol> {
+                let sort: ::std::option::Option<&str> = match self {
+                    #(#sorts)*
+                    _ => None,
+                };
+                sort.map(::egg::Symbol::from)
+            }
+
+            #[allow(unreachable_patterns)]
+            fn child_sort(&self, i: usize) -> ::std::option::Option<::egg::Symbol> {
+                let sorts: &[&str] = match self {
+                    #(#child_sorts)*
+                    _ => &[],
+                };
+                sorts.get(i).map(|&sort| ::egg::Symbol::from(sort))
+            }
+        }
+    }
+}
+
+fn from_op_impl(input: &DeriveInput, variants: &[Variant]) -> TokenStream2 {
+    let name = &input.ident;
+    let generics = add_bounds(
+        &input.generics,
+        data_types(variants),
+        quote!(::std::str::FromStr),
+    );
+    let generics = add_bounds(
+        &generics,
+        children_types(variants),
+        quote!(::egg::LanguageChildren),
+    );
+    let generics = add_self_bound(input, &generics, quote!(::egg::Language));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let arms = variants.iter().map(|variant| match &variant.kind {
+        Kind::Leaf(op) => {
+            let new = variant.build(&[]);
+            quote! {
+                if op == #op && children.is_empty() {
+                    return Ok(#new);
+                }
+            }
+        }
+        Kind::Op(op, ids) => {
+            let new = variant.build(&[quote!(
+                <#ids as ::egg::LanguageChildren>::from_vec(children)
+            )]);
+            quote! {
+                if op == #op && <#ids as ::egg::LanguageChildren>::can_be_length(children.len()) {
+                    return Ok(#new);
+                }
+            }
+        }
+        Kind::Data(data) => {
+            let new = variant.build(&[quote!(data)]);
+            quote! {
+                if children.is_empty() {
+                    if let Ok(data) = op.parse::<#data>() {
+                        return Ok(#new);
+                    }
+                }
+            }
+        }
+        Kind::DataOp(None, data, ids) => {
+            let new = variant.build(&[quote!(data), quote!(children)]);
+            quote! {
+                if <#ids as ::egg::LanguageChildren>::can_be_length(children.len()) {
+                    if let Ok(data) = op.parse::<#data>() {
+                        let children = <#ids as ::egg::LanguageChildren>::from_vec(children);
+                        return Ok(#new);
+                    }
+                }
+            }
+        }
+        Kind::DataOp(Some(op_str), data, ids) => {
+            let new = variant.build(&[quote!(data), quote!(children)]);
+            quote! {
+                if <#ids as ::egg::LanguageChildren>::can_be_length(children.len()) {
+                    let data = op.strip_prefix(#op_str).and_then(|rest| rest.strip_prefix('.'));
+                    if let Some(Ok(data)) = data.map(|data| data.parse::<#data>()) {
+                        let children = <#ids as ::egg::LanguageChildren>::from_vec(children);
+                        return Ok(#new);
+                    }
+                }
+            }
+        }
+    });
+
+    quote! {
+        impl #impl_generics ::egg::FromOp for #name #ty_generics #where_clause {
+            type Error = ::egg::FromOpError;
+
+            fn from_op(
+                op: &str,
+                children: ::std::vec::Vec<::egg::Id>,
+            ) -> ::std::result::Result<Self, Self::Error> {
+                #(#arms)*
+                Err(::egg::FromOpError::new(op, children))
+            }
+        }
+    }
+}
+
+fn display_impl(input: &DeriveInput, variants: &[Variant]) -> TokenStream2 {
+    let name = &input.ident;
+    let generics = add_bounds(
+        &input.generics,
+        data_types(variants),
+        quote!(::std::fmt::Display),
+    );
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let arms = variants.iter().map(|variant| {
+        let any = variant.any();
+        match &variant.kind {
+            Kind::Leaf(op) | Kind::Op(op, _) => quote!(#any => f.write_str(#op),),
+            Kind::Data(_) => {
+                let pat = variant.build(&[quote!(data)]);
+                quote!(#pat => ::std::fmt::Display::fmt(data, f),)
+            }
+            Kind::DataOp(None, ..) => {
+                let pat = variant.build(&[quote!(data), quote!(_)]);
+                quote!(#pat => ::std::fmt::Display::fmt(data, f),)
+            }
+            Kind::DataOp(Some(op), ..) => {
+                let pat = variant.build(&[quote!(data), quote!(_)]);
+                quote!(#pat => ::std::write!(f, "{}.{}", #op, data),)
+            }
+        }
+    });
+
+    quote! {
+        impl #impl_generics ::std::fmt::Display for #name #ty_generics #where_clause {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                match self {
+                    #(#arms)*
+                }
+            }
+        }
+    }
+}
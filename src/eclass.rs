@@ -44,6 +44,12 @@ impl<L, D> EClass<L, D> {
 }
 
 impl<L: Language, D> EClass<L, D> {
+    /// Returns the sort of this eclass: the first [`Language::sort`] of its
+    /// enodes, or `None` if none of them has a sort.
+    pub fn sort(&self) -> Option<Symbol> {
+        self.nodes.iter().find_map(|n| n.sort())
+    }
+
     /// Iterates over the childless enodes in this eclass.
     pub fn leaves(&self) -> impl Iterator<Item = &L> {
         self.nodes.iter().filter(|&n| n.is_leaf())
@@ -1,7 +1,13 @@
+use crate::table::{AnyTable, Table};
 use crate::*;
 use std::{
     borrow::BorrowMut,
     fmt::{self, Debug, Display},
+    hash::Hash,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
 };
 
 #[cfg(feature = "serde-1")]
@@ -77,6 +83,13 @@ pub struct EGraph<L: Language, N: Analysis<L>> {
     #[cfg_attr(feature = "serde-1", serde(skip))]
     #[cfg_attr(feature = "serde-1", serde(default = "default_classes_by_op"))]
     pub(crate) classes_by_op: HashMap<std::mem::Discriminant<L>, HashSet<Id>>,
+    /// User-defined indexes over the analysis data, see [`EGraph::add_data_index`].
+    #[cfg_attr(feature = "serde-1", serde(skip))]
+    #[cfg_attr(feature = "serde-1", serde(default = "default_data_indexes"))]
+    data_indexes: HashMap<Symbol, Box<dyn DataIndex<N::Data>>>,
+    /// User-defined tables from eclasses to values, see [`EGraph::add_table`].
+    #[cfg_attr(feature = "serde-1", serde(skip))]
+    tables: HashMap<Symbol, Box<dyn AnyTable>>,
     /// Whether or not reading operation are allowed on this e-graph.
     /// Mutating operations will set this to `false`, and
     /// [`EGraph::rebuild`] will set it to true.
@@ -84,6 +97,10 @@ pub struct EGraph<L: Language, N: Analysis<L>> {
     /// Only manually set it if you know what you're doing.
     #[cfg_attr(feature = "serde-1", serde(skip))]
     pub clean: bool,
+    /// A flag that, once set, asks long-running searches and applications
+    /// to stop early. See [`EGraph::is_cancelled`].
+    #[cfg_attr(feature = "serde-1", serde(skip))]
+    pub(crate) cancellation: Option<Arc<AtomicBool>>,
 }
 
 #[cfg(feature = "serde-1")]
@@ -91,6 +108,58 @@ fn default_classes_by_op<K>() -> HashMap<K, HashSet<Id>> {
     HashMap::default()
 }
 
+#[cfg(feature = "serde-1")]
+fn default_data_indexes<D>() -> HashMap<Symbol, Box<dyn DataIndex<D>>> {
+    HashMap::default()
+}
+
+/// A type-erased secondary index, see [`EGraph::add_data_index`].
+trait DataIndex<D>: Send + Sync {
+    fn clear(&mut self);
+    fn insert(&mut self, id: Id, data: &D);
+    fn as_any(&self) -> &dyn std::any::Any;
+    fn clone_box(&self) -> Box<dyn DataIndex<D>>;
+}
+
+impl<D> Clone for Box<dyn DataIndex<D>> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+type KeyFn<D, K> = Arc<dyn Fn(&D) -> Option<K> + Send + Sync>;
+
+struct KeyedIndex<D, K> {
+    key: KeyFn<D, K>,
+    classes: HashMap<K, Vec<Id>>,
+}
+
+impl<D: 'static, K> DataIndex<D> for KeyedIndex<D, K>
+where
+    K: Hash + Eq + Clone + Send + Sync + 'static,
+{
+    fn clear(&mut self) {
+        self.classes.clear();
+    }
+
+    fn insert(&mut self, id: Id, data: &D) {
+        if let Some(k) = (self.key)(data) {
+            self.classes.entry(k).or_default().push(id);
+        }
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn DataIndex<D>> {
+        Box::new(KeyedIndex {
+            key: self.key.clone(),
+            classes: self.classes.clone(),
+        })
+    }
+}
+
 impl<L: Language, N: Analysis<L> + Default> Default for EGraph<L, N> {
     fn default() -> Self {
         Self::new(N::default())
@@ -120,6 +189,9 @@ impl<L: Language, N: Analysis<L>> EGraph<L, N> {
             memo: Default::default(),
             analysis_pending: Default::default(),
             classes_by_op: Default::default(),
+            data_indexes: Default::default(),
+            tables: Default::default(),
+            cancellation: None,
         }
     }
 
@@ -128,6 +200,288 @@ impl<L: Language, N: Analysis<L>> EGraph<L, N> {
         self.classes.values()
     }
 
+    /// Returns an iterator over the eclasses that contain an enode which
+    /// [`matches`](Language::matches()) `op`; the children of `op` are ignored.
+    ///
+    /// This only looks at the eclasses that contain an enode of the same enum
+    /// variant as `op`, see [`EGraph::classes_with_discriminant`].
+    /// The index behind it is refreshed by [`EGraph::rebuild`], so the
+    /// egraph must be clean.
+    ///
+    /// # Example
+    /// ```
+    /// use egg::{*, SymbolLang as S};
+    /// let mut egraph = EGraph::<S, ()>::default();
+    /// let x = egraph.add(S::leaf("x"));
+    /// let y = egraph.add(S::leaf("y"));
+    /// let f = egraph.add(S::new("f", vec![x]));
+    /// egraph.add(S::new("f", vec![x, y]));
+    /// egraph.rebuild();
+    ///
+    /// let ids: Vec<Id> = egraph
+    ///     .classes_with_op(&S::new("f", vec![0.into()]))
+    ///     .map(|c| c.id)
+    ///     .collect();
+    /// assert_eq!(ids, vec![egraph.find(f)]);
+    /// ```
+    pub fn classes_with_op<'a>(
+        &'a self,
+        op: &'a L,
+    ) -> impl Iterator<Item = &'a EClass<L, N::Data>> + 'a {
+        #[allow(enum_intrinsics_non_enums)]
+        self.classes_with_discriminant(std::mem::discriminant(op))
+            .filter(move |class| class.iter().any(|n| op.matches(n)))
+    }
+
+    /// Returns an iterator over the enodes that [`matches`](Language::matches())
+    /// `op`, each paired with the id of its eclass.
+    ///
+    /// Like [`EGraph::classes_with_op`], the egraph must be clean.
+    pub fn nodes_with_op<'a>(&'a self, op: &'a L) -> impl Iterator<Item = (Id, &'a L)> + 'a {
+        self.classes_with_op(op).flat_map(move |class| {
+            class
+                .iter()
+                .filter(move |n| op.matches(n))
+                .map(move |n| (class.id, n))
+        })
+    }
+
+    /// Returns an iterator over the eclasses that contain an enode of the
+    /// enum variant given by `discriminant`.
+    ///
+    /// This is a lookup in the same index that [`Pattern`]s use to find
+    /// candidate eclasses, so it does not scan the whole egraph.
+    /// For languages like [`SymbolLang`] where every enode has the same
+    /// variant, use [`EGraph::classes_with_op`] instead.
+    ///
+    /// # Panics
+    /// Panics if the egraph is not clean.
+    pub fn classes_with_discriminant(
+        &self,
+        discriminant: std::mem::Discriminant<L>,
+    ) -> impl Iterator<Item = &EClass<L, N::Data>> {
+        assert!(self.clean, "Tried to query a dirty e-graph!");
+        self.classes_by_op
+            .get(&discriminant)
+            .into_iter()
+            .flatten()
+            .map(move |&id| &self.classes[&id])
+    }
+
+    /// Adds a secondary index named `name` over the analysis data.
+    ///
+    /// `key` is called on the data of every eclass, and the eclasses are
+    /// grouped by the keys it returns; eclasses for which it returns `None`
+    /// are left out.
+    /// Use [`EGraph::classes_with_data`] to look them up.
+    ///
+    /// The index is computed right away and then again at the end of every
+    /// [`EGraph::rebuild`], so it stays up to date through unions and
+    /// analysis changes.
+    /// Adding an index with a name that is already used replaces it.
+    /// Indexes are not serialized.
+    ///
+    /// # Example
+    /// ```
+    /// use egg::{*, SymbolLang as S};
+    ///
+    /// #[derive(Default)]
+    /// struct Len;
+    /// impl Analysis<S> for Len {
+    ///     type Data = usize;
+    ///     fn make(egraph: &EGraph<S, Self>, enode: &S) -> usize {
+    ///         enode.op.as_str().len()
+    ///     }
+    ///     fn merge(&mut self, a: &mut usize, b: usize) -> DidMerge {
+    ///         merge_min(a, b)
+    ///     }
+    /// }
+    ///
+    /// let mut egraph = EGraph::<S, Len>::default();
+    /// egraph.add_data_index("len", |len: &usize| Some(*len));
+    /// let abc = egraph.add(S::leaf("abc"));
+    /// let de = egraph.add(S::leaf("de"));
+    /// let f = egraph.add(S::leaf("f"));
+    /// egraph.rebuild();
+    /// assert_eq!(egraph.classes_with_data("len", &3usize).count(), 1);
+    /// assert_eq!(egraph.classes_with_data("len", &1usize).count(), 1);
+    ///
+    /// // the merged eclass now has length 1
+    /// egraph.union(abc, f);
+    /// egraph.union(de, f);
+    /// egraph.rebuild();
+    /// assert_eq!(egraph.classes_with_data("len", &3usize).count(), 0);
+    /// let ids: Vec<Id> = egraph.classes_with_data("len", &1usize).map(|c| c.id).collect();
+    /// assert_eq!(ids, vec![egraph.find(f)]);
+    /// ```
+    pub fn add_data_index<K>(
+        &mut self,
+        name: impl Into<Symbol>,
+        key: impl Fn(&N::Data) -> Option<K> + Send + Sync + 'static,
+    ) where
+        K: Hash + Eq + Clone + Send + Sync + 'static,
+        N::Data: 'static,
+    {
+        let mut index = KeyedIndex {
+            key: Arc::new(key),
+            classes: HashMap::default(),
+        };
+        for class in self.classes.values() {
+            index.insert(class.id, &class.data);
+        }
+        self.data_indexes.insert(name.into(), Box::new(index));
+    }
+
+    /// Removes the secondary index named `name`, returning whether it existed.
+    pub fn remove_data_index(&mut self, name: impl Into<Symbol>) -> bool {
+        self.data_indexes.remove(&name.into()).is_some()
+    }
+
+    /// Returns an iterator over the eclasses whose data has the given `key`
+    /// in the secondary index named `name`, see [`EGraph::add_data_index`].
+    ///
+    /// # Panics
+    /// Panics if the egraph is not clean, if there is no index named `name`,
+    /// or if that index has keys of a different type than `K`.
+    pub fn classes_with_data<K>(
+        &self,
+        name: impl Into<Symbol>,
+        key: &K,
+    ) -> impl Iterator<Item = &EClass<L, N::Data>>
+    where
+        K: Hash + Eq + 'static,
+        N::Data: 'static,
+    {
+        assert!(self.clean, "Tried to query a dirty e-graph!");
+        let name = name.into();
+        let index = self
+            .data_indexes
+            .get(&name)
+            .unwrap_or_else(|| panic!("No data index named {}", name));
+        let index = index
+            .as_any()
+            .downcast_ref::<KeyedIndex<N::Data, K>>()
+            .unwrap_or_else(|| panic!("Data index {} has a different key type", name));
+        index
+            .classes
+            .get(key)
+            .into_iter()
+            .flatten()
+            .map(move |id| &self.classes[id])
+    }
+
+    /// Adds a table named `name` that maps tuples of eclasses to values of
+    /// type `T`, replacing any table with that name.
+    ///
+    /// Tables are functions from terms to values, like the cost of an
+    /// eclass or the type of a variable in a scope, keyed by canonical
+    /// [`Id`]s.
+    /// Setting an entry that already has a value stores the result of
+    /// `merge` on the old and new values, so `merge` should be a lattice
+    /// join, like `min` or set union.
+    /// When [`rebuild`](EGraph::rebuild()) unions eclasses, the keys of
+    /// each table are canonicalized, merging the values of keys that became
+    /// equal.
+    ///
+    /// Rules read entries with [`TableLookup`] and write them with
+    /// [`TableSet`].
+    ///
+    /// # Example
+    /// ```
+    /// use egg::*;
+    /// let mut egraph = EGraph::<SymbolLang, ()>::default();
+    /// egraph.add_table("cost", |a: &i64, b: &i64| *a.min(b));
+    /// let x = egraph.add(SymbolLang::leaf("x"));
+    /// let y = egraph.add(SymbolLang::leaf("y"));
+    /// assert!(egraph.table_set("cost", &[x], 5i64));
+    /// assert!(egraph.table_set("cost", &[y], 3i64));
+    /// assert!(!egraph.table_set("cost", &[x], 7i64));
+    /// assert_eq!(egraph.table_get::<i64>("cost", &[x]), Some(&5));
+    ///
+    /// egraph.union(x, y);
+    /// egraph.rebuild();
+    /// assert_eq!(egraph.table_get::<i64>("cost", &[x]), Some(&3));
+    /// assert_eq!(egraph.table_entries::<i64>("cost").count(), 1);
+    /// ```
+    pub fn add_table<T>(
+        &mut self,
+        name: impl Into<Symbol>,
+        merge: impl Fn(&T, &T) -> T + Send + Sync + 'static,
+    ) where
+        T: Clone + PartialEq + Send + Sync + 'static,
+    {
+        self.tables.insert(name.into(), Box::new(Table::new(merge)));
+    }
+
+    /// Removes the table named `name`, returning whether it existed.
+    pub fn remove_table(&mut self, name: impl Into<Symbol>) -> bool {
+        self.tables.remove(&name.into()).is_some()
+    }
+
+    fn table<T: 'static>(&self, name: Symbol) -> &Table<T> {
+        let table = self
+            .tables
+            .get(&name)
+            .unwrap_or_else(|| panic!("No table named {}", name));
+        table
+            .as_any()
+            .downcast_ref()
+            .unwrap_or_else(|| panic!("Table {} has a different value type", name))
+    }
+
+    /// Returns the value of the table named `name` at `key`, if there is one.
+    ///
+    /// The key is canonicalized first.
+    ///
+    /// # Panics
+    /// Panics if there is no table named `name`, or if it has values of a
+    /// different type than `T`.
+    pub fn table_get<T: 'static>(&self, name: impl Into<Symbol>, key: &[Id]) -> Option<&T> {
+        let key: Box<[Id]> = key.iter().map(|&id| self.find(id)).collect();
+        self.table(name.into()).entries.get(&key)
+    }
+
+    /// Merges `value` into the table named `name` at `key`, returning
+    /// whether the entry changed.
+    ///
+    /// The key is canonicalized first.
+    ///
+    /// # Panics
+    /// Panics if there is no table named `name`, or if it has values of a
+    /// different type than `T`.
+    pub fn table_set<T>(&mut self, name: impl Into<Symbol>, key: &[Id], value: T) -> bool
+    where
+        T: Clone + PartialEq + Send + Sync + 'static,
+    {
+        let name = name.into();
+        let key: Box<[Id]> = key.iter().map(|&id| self.find(id)).collect();
+        let table = self
+            .tables
+            .get_mut(&name)
+            .unwrap_or_else(|| panic!("No table named {}", name));
+        table
+            .as_any_mut()
+            .downcast_mut::<Table<T>>()
+            .unwrap_or_else(|| panic!("Table {} has a different value type", name))
+            .set(key, value)
+    }
+
+    /// Returns an iterator over the keys and values of the table named
+    /// `name`.
+    ///
+    /// # Panics
+    /// Panics if there is no table named `name`, or if it has values of a
+    /// different type than `T`.
+    pub fn table_entries<T: 'static>(
+        &self,
+        name: impl Into<Symbol>,
+    ) -> impl Iterator<Item = (&[Id], &T)> {
+        self.table(name.into())
+            .entries
+            .iter()
+            .map(|(key, value)| (&key[..], value))
+    }
+
     /// Returns an mutating iterator over the eclasses in the egraph.
     pub fn classes_mut(&mut self) -> impl ExactSizeIterator<Item = &mut EClass<L, N::Data>> {
         self.classes.values_mut()
@@ -176,6 +530,46 @@ impl<L: Language, N: Analysis<L>> EGraph<L, N> {
         self.classes.len()
     }
 
+    /// Returns `true` if the [`Runner`] driving this egraph has been
+    /// cancelled (see [`Runner::with_cancellation`]).
+    ///
+    /// `egg`'s searchers and appliers check this between eclasses and stop
+    /// early once it is set; custom [`Searcher`]s and [`Applier`]s that do a
+    /// lot of work can do the same.
+    pub fn is_cancelled(&self) -> bool {
+        matches!(&self.cancellation, Some(flag) if flag.load(Ordering::Relaxed))
+    }
+
+    /// Returns an estimate of the heap memory, in bytes, used by this `EGraph`.
+    ///
+    /// This accounts for the hashcons (memo), the eclasses with their
+    /// enodes, parents and analysis data, the union-find, and the
+    /// explanations if they are enabled.
+    /// Heap memory owned by the enodes or the analysis data themselves
+    /// (e.g. the children of a [`SymbolLang`]) is not included,
+    /// so this is a lower bound.
+    ///
+    /// This iterates over every eclass, so it is not free.
+    pub fn estimated_heap_size(&self) -> usize {
+        let classes: usize = self
+            .classes
+            .values()
+            .map(|c| vec_heap_size(&c.nodes) + vec_heap_size(&c.parents))
+            .sum();
+        let classes_by_op: usize = self.classes_by_op.values().map(hashset_heap_size).sum();
+        let explain = self.explain.as_ref().map_or(0, |e| e.estimated_heap_size());
+
+        hashmap_heap_size(&self.memo)
+            + hashmap_heap_size(&self.classes)
+            + classes
+            + hashmap_heap_size(&self.classes_by_op)
+            + classes_by_op
+            + self.unionfind.estimated_heap_size()
+            + vec_heap_size(&self.pending)
+            + self.analysis_pending.capacity() * std::mem::size_of::<(L, Id)>()
+            + explain
+    }
+
     /// Enable explanations for this `EGraph`.
     /// This allows the egraph to explain why two expressions are
     /// equivalent with the [`explain_equivalence`](EGraph::explain_equivalence) function.
@@ -399,6 +793,9 @@ impl<L: Language, N: Analysis<L>> EGraph<L, N> {
     /// The [`Explanation`] can be used in it's default tree form or in a less compact
     /// flattened form. Each of these also has a s-expression string representation,
     /// given by [`get_flat_string`](Explanation::get_flat_string) and [`get_string`](Explanation::get_string).
+    ///
+    /// Reordering the children of an AC enode (see [`Language::is_ac`])
+    /// shows up as a step with the rule name `ac-reorder`.
     pub fn explain_equivalence(
         &mut self,
         left_expr: &RecExpr<L>,
@@ -594,12 +991,23 @@ impl<L: Language, N: Analysis<L>> EGraph<L, N> {
         for node in nodes {
             match node {
                 ENodeOrVar::Var(var) => {
-                    let id = self.find(subst[*var]);
+                    // sequence variables are spliced into their parent instead
+                    let (id, is_new) = if var.is_sequence() {
+                        (Id::from(0), false)
+                    } else if let Some(id) = subst.get(*var) {
+                        (self.find(*id), false)
+                    } else {
+                        // a variable only bound to a value becomes a new leaf
+                        let new_node = crate::pattern::value_node(self, *var, subst);
+                        let size_before = self.unionfind.size();
+                        let id = self.add_internal(new_node);
+                        (id, self.unionfind.size() > size_before)
+                    };
                     new_ids.push(id);
-                    new_node_q.push(false);
+                    new_node_q.push(is_new);
                 }
                 ENodeOrVar::ENode(node) => {
-                    let new_node = node.clone().map_children(|i| new_ids[usize::from(i)]);
+                    let new_node = crate::pattern::instantiate_node(node, nodes, &new_ids, subst);
                     let size_before = self.unionfind.size();
                     let next_id = self.add_internal(new_node);
                     if self.unionfind.size() > size_before {
@@ -660,6 +1068,7 @@ impl<L: Language, N: Analysis<L>> EGraph<L, N> {
     {
         let enode = enode.borrow_mut();
         enode.update_children(|id| self.find(id));
+        sort_ac_children(enode);
         self.memo.get(enode).copied()
     }
 
@@ -867,6 +1276,15 @@ impl<L: Language, N: Analysis<L>> EGraph<L, N> {
             }
             return false;
         }
+        #[cfg(debug_assertions)]
+        if let (Some(sort1), Some(sort2)) = (self.classes[&id1].sort(), self.classes[&id2].sort()) {
+            assert_eq!(
+                sort1, sort2,
+                "Tried to union eclass {} of sort {} with eclass {} of sort {}",
+                id1, sort1, id2, sort2
+            );
+        }
+
         // make sure class2 has fewer parents
         let class1_parents = self.classes[&id1].parents.len();
         let class2_parents = self.classes[&id2].parents.len();
@@ -955,6 +1373,15 @@ impl<L: Language + Display, N: Analysis<L>> EGraph<L, N> {
     }
 }
 
+/// Sorts the children of an AC enode (see [`Language::is_ac`]), so enodes
+/// that only differ by the order of their children are hashconsed together.
+#[inline(always)]
+fn sort_ac_children<L: Language>(enode: &mut L) {
+    if enode.is_ac() {
+        enode.children_mut().sort_unstable();
+    }
+}
+
 // All the rebuilding stuff
 impl<L: Language, N: Analysis<L>> EGraph<L, N> {
     #[inline(never)]
@@ -967,10 +1394,10 @@ impl<L: Language, N: Analysis<L>> EGraph<L, N> {
 
         for class in self.classes.values_mut() {
             let old_len = class.len();
-            class
-                .nodes
-                .iter_mut()
-                .for_each(|n| n.update_children(|id| uf.find_mut(id)));
+            class.nodes.iter_mut().for_each(|n| {
+                n.update_children(|id| uf.find_mut(id));
+                sort_ac_children(n);
+            });
             class.nodes.sort_unstable();
             class.nodes.dedup();
 
@@ -1005,6 +1432,18 @@ impl<L: Language, N: Analysis<L>> EGraph<L, N> {
         }
 
         self.classes_by_op = classes_by_op;
+
+        for index in self.data_indexes.values_mut() {
+            index.clear();
+            for class in self.classes.values() {
+                index.insert(class.id, &class.data);
+            }
+        }
+
+        let unionfind = &self.unionfind;
+        for table in self.tables.values_mut() {
+            table.rebuild(&|id| unionfind.find(id));
+        }
         trimmed
     }
 
@@ -1049,6 +1488,7 @@ impl<L: Language, N: Analysis<L>> EGraph<L, N> {
         while !self.pending.is_empty() || !self.analysis_pending.is_empty() {
             while let Some((mut node, class)) = self.pending.pop() {
                 node.update_children(|id| self.find_mut(id));
+                sort_ac_children(&mut node);
                 if let Some(memo_class) = self.memo.insert(node, class) {
                     let did_something = self.perform_union(
                         memo_class,
@@ -1,7 +1,8 @@
 use crate::Symbol;
 use crate::{
-    util::pretty_print, Analysis, EClass, EGraph, ENodeOrVar, FromOp, HashMap, HashSet, Id,
-    Language, Pattern, PatternAst, RecExpr, Rewrite, Subst, UnionFind, Var,
+    util::{hashmap_heap_size, pretty_print, vec_heap_size},
+    Analysis, EClass, EGraph, ENodeOrVar, FromOp, HashMap, HashSet, Id, IndexMap, Language,
+    Pattern, PatternAst, RecExpr, Rewrite, Subst, UnionFind, Var,
 };
 use std::cmp::Ordering;
 use std::collections::{BinaryHeap, VecDeque};
@@ -12,6 +13,9 @@ use symbolic_expressions::Sexp;
 
 const CONGRUENCE_LIMIT: usize = 10;
 const GREEDY_NUM_ITERS: usize = 10;
+/// The rule name of the explanation steps that reorder the children of an
+/// AC enode (see [`Language::is_ac`]).
+const AC_REORDER: &str = "ac-reorder";
 
 /// A justification for a union, either via a rule or congruence.
 /// A direct union with a justification is also stored as a rule.
@@ -354,6 +358,30 @@ impl<L: Language> Explanation<L> {
         }
     }
 
+    /// Count how many rewrite steps of the flattened explanation
+    /// use each rule, in order of first use.
+    ///
+    /// Each step is counted once, whether it is a forward or backward use
+    /// of the rule.
+    /// Keys are rule names or the reasons given to
+    /// [`union_instantiations`](super::EGraph::union_instantiations).
+    pub fn rule_counts(&mut self) -> IndexMap<Symbol, usize> {
+        fn count<L: Language>(term: &FlatTerm<L>, counts: &mut IndexMap<Symbol, usize>) {
+            for rule in term.forward_rule.iter().chain(&term.backward_rule) {
+                *counts.entry(*rule).or_default() += 1;
+            }
+            for child in &term.children {
+                count(child, counts);
+            }
+        }
+
+        let mut counts = IndexMap::default();
+        for term in self.make_flat_explanation() {
+            count(term, &mut counts);
+        }
+        counts
+    }
+
     /// Check the validity of the explanation with respect to the given rules.
     /// This only is able to check rule applications when the rules are implement `get_pattern_ast`.
     pub fn check_proof<'a, R, N: Analysis<L>>(&mut self, rules: R)
@@ -801,20 +829,34 @@ impl<L: Language> FlatTerm<L> {
     fn from_pattern(
         pattern: &[ENodeOrVar<L>],
         location: usize,
-        bindings: &HashMap<Var, &FlatTerm<L>>,
+        bindings: &HashMap<Var, &[FlatTerm<L>]>,
     ) -> FlatTerm<L> {
         match &pattern[location] {
-            ENodeOrVar::Var(var) => (*bindings.get(var).unwrap()).clone(),
+            ENodeOrVar::Var(var) => bindings[var][0].clone(),
             ENodeOrVar::ENode(node) => {
                 let children = node.fold(vec![], |mut acc, child| {
-                    acc.push(FlatTerm::from_pattern(
-                        pattern,
-                        usize::from(child),
-                        bindings,
-                    ));
+                    match &pattern[usize::from(child)] {
+                        // sequence variables are spliced into their parent
+                        ENodeOrVar::Var(var) if var.is_sequence() => {
+                            acc.extend(bindings[var].iter().cloned())
+                        }
+                        _ => acc.push(FlatTerm::from_pattern(
+                            pattern,
+                            usize::from(child),
+                            bindings,
+                        )),
+                    }
                     acc
                 });
-                FlatTerm::new(node.clone(), children)
+                let node = if children.len() == node.len() {
+                    node.clone()
+                } else {
+                    node.with_children(vec![Id::from(0); children.len()])
+                        .unwrap_or_else(|| {
+                            panic!("{:?} can't have {} children", node, children.len())
+                        })
+                };
+                FlatTerm::new(node, children)
             }
         }
     }
@@ -823,29 +865,63 @@ impl<L: Language> FlatTerm<L> {
         &'a self,
         pattern: &[ENodeOrVar<L>],
         location: usize,
-        bindings: &mut HashMap<Var, &'a FlatTerm<L>>,
+        bindings: &mut HashMap<Var, &'a [FlatTerm<L>]>,
     ) {
         match &pattern[location] {
             ENodeOrVar::Var(var) => {
-                if let Some(existing) = bindings.get(var) {
-                    if existing != &self {
-                        panic!(
-                            "Invalid proof: binding for variable {:?} does not match between {:?} \n and \n {:?}",
-                            var, existing, self);
-                    }
-                } else {
-                    bindings.insert(*var, self);
-                }
+                FlatTerm::bind(*var, std::slice::from_ref(self), bindings);
             }
             ENodeOrVar::ENode(node) => {
-                // The node must match the rewrite or the proof is invalid.
-                assert!(node.matches(&self.node));
-                let mut counter = 0;
-                node.for_each(|child| {
-                    self.children[counter].make_bindings(pattern, usize::from(child), bindings);
-                    counter += 1;
+                let seq = node.children().iter().position(|&child| {
+                    matches!(&pattern[usize::from(child)], ENodeOrVar::Var(v) if v.is_sequence())
                 });
+                let seq = match seq {
+                    Some(seq) => seq,
+                    None => {
+                        // The node must match the rewrite or the proof is invalid.
+                        assert!(node.matches(&self.node));
+                        for (term, &child) in self.children.iter().zip(node.children()) {
+                            term.make_bindings(pattern, usize::from(child), bindings);
+                        }
+                        return;
+                    }
+                };
+
+                // The sequence variable takes the children that the others don't.
+                let fixed = node.len() - 1;
+                assert!(self.children.len() >= fixed);
+                let resized = node.with_children(vec![Id::from(0); self.children.len()]);
+                assert!(matches!(resized, Some(n) if n.matches(&self.node)));
+                let seq_len = self.children.len() - fixed;
+                for (i, &child) in node.children().iter().enumerate() {
+                    if i == seq {
+                        let var = match &pattern[usize::from(child)] {
+                            ENodeOrVar::Var(var) => *var,
+                            ENodeOrVar::ENode(_) => unreachable!(),
+                        };
+                        FlatTerm::bind(var, &self.children[seq..seq + seq_len], bindings);
+                    } else {
+                        let j = if i < seq { i } else { i - 1 + seq_len };
+                        self.children[j].make_bindings(pattern, usize::from(child), bindings);
+                    }
+                }
+            }
+        }
+    }
+
+    fn bind<'a>(
+        var: Var,
+        terms: &'a [FlatTerm<L>],
+        bindings: &mut HashMap<Var, &'a [FlatTerm<L>]>,
+    ) {
+        if let Some(existing) = bindings.get(&var) {
+            if *existing != terms {
+                panic!(
+                    "Invalid proof: binding for variable {:?} does not match between {:?} \n and \n {:?}",
+                    var, existing, terms);
             }
+        } else {
+            bindings.insert(var, terms);
         }
     }
 }
@@ -1373,16 +1449,37 @@ impl<L: Language> Explain<L> {
                 std::mem::swap(&mut connection.next, &mut connection.current);
             }
 
+            let (current, next) = (connection.current, connection.next);
+            let is_congruence = matches!(connection.justification, Justification::Congruence);
             proof.push(self.explain_adjacent(
                 connection,
                 cache,
                 node_explanation_cache,
                 use_unoptimized,
             ));
+            if is_congruence && self.is_ac_reordered(current, next) {
+                // the congruence ends with the children of `next` in the
+                // order of `current`'s, so add a step that reorders them
+                let mut reordered =
+                    (*self.node_to_explanation(next, node_explanation_cache)).clone();
+                reordered.forward_rule = Some(Symbol::from(AC_REORDER));
+                proof.push(Rc::new(reordered));
+            }
         }
         proof
     }
 
+    fn is_ac_reordered(&self, current: Id, next: Id) -> bool {
+        let current_node = &self.explainfind[usize::from(current)].node;
+        let next_node = &self.explainfind[usize::from(next)].node;
+        current_node.is_ac()
+            && self
+                .congruent_children(current_node, next_node)
+                .iter()
+                .zip(next_node.children())
+                .any(|((_, paired), child)| paired != child)
+    }
+
     fn explain_adjacent(
         &self,
         connection: Connection,
@@ -1418,14 +1515,10 @@ impl<L: Language> Explain<L> {
                 assert!(current_node.matches(next_node));
                 let mut subproofs = vec![];
 
-                for (left_child, right_child) in current_node
-                    .children()
-                    .iter()
-                    .zip(next_node.children().iter())
-                {
+                for (left_child, right_child) in self.congruent_children(current_node, next_node) {
                     subproofs.push(self.explain_enodes(
-                        *left_child,
-                        *right_child,
+                        left_child,
+                        right_child,
                         cache,
                         node_explanation_cache,
                         use_unoptimized,
@@ -1554,16 +1647,9 @@ impl<L: Language> Explain<L> {
         let current_node = self.explainfind[usize::from(current)].node.clone();
         let next_node = self.explainfind[usize::from(next)].node.clone();
         let mut cost: usize = 0;
-        for (left_child, right_child) in current_node
-            .children()
-            .iter()
-            .zip(next_node.children().iter())
-        {
-            cost = cost.saturating_add(self.distance_between(
-                *left_child,
-                *right_child,
-                distance_memo,
-            ));
+        for (left_child, right_child) in self.congruent_children(&current_node, &next_node) {
+            cost =
+                cost.saturating_add(self.distance_between(left_child, right_child, distance_memo));
         }
         cost
     }
@@ -1693,6 +1779,18 @@ impl<L: Language> Explain<L> {
         self.explainfind.len()
     }
 
+    pub(crate) fn estimated_heap_size(&self) -> usize {
+        let neighbors: usize = self
+            .explainfind
+            .iter()
+            .map(|n| vec_heap_size(&n.neighbors))
+            .sum();
+        vec_heap_size(&self.explainfind)
+            + neighbors
+            + hashmap_heap_size(&self.uncanon_memo)
+            + hashmap_heap_size(&self.shortest_explanation_memo)
+    }
+
     fn shortest_path_modulo_congruence(
         &mut self,
         start: Id,
@@ -1839,15 +1937,9 @@ impl<L: Language> Explain<L> {
                     std::mem::swap(&mut next, &mut current);
                 }
                 if let Justification::Congruence = connection.justification {
-                    let current_node = self.explainfind[usize::from(current)].node.clone();
-                    let next_node = self.explainfind[usize::from(next)].node.clone();
-                    for (left_child, right_child) in current_node
-                        .children()
-                        .iter()
-                        .zip(next_node.children().iter())
-                    {
-                        todo_congruence.push_back((*left_child, *right_child));
-                    }
+                    let current_node = &self.explainfind[usize::from(current)].node;
+                    let next_node = &self.explainfind[usize::from(next)].node;
+                    todo_congruence.extend(self.congruent_children(current_node, next_node));
                 }
             }
         }
@@ -1895,6 +1987,41 @@ impl<L: Language> Explain<L> {
         self.explainfind[usize::from(enode)].parent_connection.next
     }
 
+    fn root(&self, mut enode: Id) -> Id {
+        loop {
+            let parent = self.parent(enode);
+            if parent == enode {
+                return enode;
+            }
+            enode = parent;
+        }
+    }
+
+    /// Pairs up the children of two congruent enodes, in the order of
+    /// `current`'s children.
+    /// The children of AC enodes (see [`Language::is_ac`]) can be in a
+    /// different order, so they are paired by eclass instead of by position.
+    fn congruent_children(&self, current: &L, next: &L) -> Vec<(Id, Id)> {
+        let mut pairs: Vec<(Id, Id)> = current
+            .children()
+            .iter()
+            .copied()
+            .zip(next.children().iter().copied())
+            .collect();
+        if current.is_ac() && pairs.iter().any(|&(l, r)| self.root(l) != self.root(r)) {
+            let mut rights = next.children().to_vec();
+            for pair in &mut pairs {
+                let root = self.root(pair.0);
+                let j = rights
+                    .iter()
+                    .position(|&r| self.root(r) == root)
+                    .expect("AC enodes were unioned by congruence with different children");
+                pair.1 = rights.swap_remove(j);
+            }
+        }
+        pairs
+    }
+
     fn calculate_common_ancestor<N: Analysis<L>>(
         &self,
         classes: &HashMap<Id, EClass<L, N::Data>>,
@@ -0,0 +1,403 @@
+use std::fmt::{self, Debug, Display, Formatter};
+
+use log::*;
+
+use crate::*;
+
+/// Evaluates terms of a [`Language`] to concrete values, so that
+/// [`RuleFuzzer`] can test rewrites for soundness.
+pub trait Interpreter<L: Language> {
+    /// The values terms evaluate to.
+    type Value: Clone + Debug + PartialEq;
+
+    /// Evaluates `enode` given the values of its children, in order.
+    ///
+    /// Return `None` if the result is undefined, like division by zero.
+    fn eval(&self, enode: &L, children: &[Self::Value]) -> Option<Self::Value>;
+
+    /// Generates a random term to substitute for a pattern variable.
+    fn arbitrary(&self, rng: &mut FuzzRng) -> RecExpr<L>;
+
+    /// Evaluates a whole term.
+    ///
+    /// The default implementation calls [`eval`](Interpreter::eval()) on
+    /// each node in order, and is undefined if any node is.
+    fn eval_expr(&self, expr: &RecExpr<L>) -> Option<Self::Value> {
+        let mut values: Vec<Option<Self::Value>> = Vec::with_capacity(expr.as_ref().len());
+        for node in expr.as_ref() {
+            let children: Option<Vec<Self::Value>> = node
+                .children()
+                .iter()
+                .map(|&c| values[usize::from(c)].clone())
+                .collect();
+            values.push(children.and_then(|cs| self.eval(node, &cs)));
+        }
+        values.pop().flatten()
+    }
+}
+
+/// A small, deterministic pseudo-random number generator for
+/// [`Interpreter::arbitrary`].
+#[derive(Debug, Clone)]
+pub struct FuzzRng(u64);
+
+impl FuzzRng {
+    /// Creates a generator from a seed.
+    pub fn new(seed: u64) -> Self {
+        // xorshift gets stuck at 0, so avoid that state even for the one
+        // seed that the XOR maps to it
+        match seed ^ 0x9E37_79B9_7F4A_7C15 {
+            0 => Self(0x9E37_79B9_7F4A_7C15),
+            state => Self(state),
+        }
+    }
+
+    /// Returns the next random `u64`.
+    pub fn next_u64(&mut self) -> u64 {
+        // xorshift64*
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Returns a random number in `0..n`. Panics if `n` is 0.
+    pub fn below(&mut self, n: usize) -> usize {
+        assert!(n > 0, "FuzzRng::below(0)");
+        (self.next_u64() % n as u64) as usize
+    }
+
+    /// Returns a random element of `items`. Panics if `items` is empty.
+    pub fn choose<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.below(items.len())]
+    }
+}
+
+/// A substitution under which a rewrite's two sides evaluate differently.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct Counterexample<L, V> {
+    /// The name of the rewrite.
+    pub rule: Symbol,
+    /// The term substituted for each pattern variable.
+    pub subst: Vec<(Var, RecExpr<L>)>,
+    /// The instantiated left-hand side.
+    pub lhs: RecExpr<L>,
+    /// The instantiated right-hand side.
+    pub rhs: RecExpr<L>,
+    /// The value of [`lhs`](Counterexample::lhs).
+    pub lhs_value: V,
+    /// The value of [`rhs`](Counterexample::rhs), or `None` if it is undefined.
+    pub rhs_value: Option<V>,
+}
+
+impl<L: Language + Display, V: Debug> Display for Counterexample<L, V> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Rule {} is unsound when", self.rule)?;
+        for (var, term) in &self.subst {
+            writeln!(f, "  {} = {}", var, term)?;
+        }
+        writeln!(f, "  lhs {} = {:?}", self.lhs, self.lhs_value)?;
+        match &self.rhs_value {
+            Some(value) => write!(f, "  rhs {} = {:?}", self.rhs, value),
+            None => write!(f, "  rhs {} is undefined", self.rhs),
+        }
+    }
+}
+
+/** Tests [`Rewrite`]s for soundness by evaluating both sides on random
+substitutions.
+
+For each trial, the fuzzer asks the [`Interpreter`] for a random term for
+each variable of the left-hand side, instantiates both sides of the rule,
+and evaluates them.
+A trial is skipped if the left-hand side is undefined,
+or if the rewrite doesn't apply to the instantiated left-hand side in a
+fresh [`EGraph`].
+The latter is how conditional rewrites are handled: a
+[`ConditionalApplier`] checks its [`Condition`] first, and only
+substitutions that pass it are evaluated.
+
+Only rewrites whose searcher and applier are both [`Pattern`]s
+(possibly under [`ConditionalApplier`]s) can be fuzzed.
+
+# Example
+```
+use egg::*;
+
+define_language! {
+    enum Arith {
+        Num(i64),
+        "+" = Add([Id; 2]),
+        "-" = Sub([Id; 2]),
+    }
+}
+
+struct Eval;
+impl Interpreter<Arith> for Eval {
+    type Value = i64;
+    fn eval(&self, enode: &Arith, args: &[i64]) -> Option<i64> {
+        match enode {
+            Arith::Num(n) => Some(*n),
+            Arith::Add(_) => args[0].checked_add(args[1]),
+            Arith::Sub(_) => args[0].checked_sub(args[1]),
+        }
+    }
+    fn arbitrary(&self, rng: &mut FuzzRng) -> RecExpr<Arith> {
+        let mut expr = RecExpr::default();
+        expr.add(Arith::Num(rng.below(10) as i64));
+        expr
+    }
+}
+
+let rules: &[Rewrite<Arith, ()>] = &[
+    rewrite!("commute-add"; "(+ ?a ?b)" => "(+ ?b ?a)"),
+    rewrite!("commute-sub"; "(- ?a ?b)" => "(- ?b ?a)"),
+];
+
+let bad = RuleFuzzer::new(Eval).check_all(rules);
+assert_eq!(bad.len(), 1);
+let cex = &bad[0];
+assert_eq!(cex.rule, Symbol::from("commute-sub"));
+// the sides evaluate to different values on the same random terms
+assert_eq!(cex.rhs_value, Some(-cex.lhs_value));
+assert_ne!(cex.lhs_value, 0);
+let (a, b) = (&cex.subst[0].1, &cex.subst[1].1);
+assert_eq!(cex.lhs.to_string(), format!("(- {} {})", a, b));
+assert_eq!(cex.rhs.to_string(), format!("(- {} {})", b, a));
+```
+*/
+#[derive(Debug, Clone)]
+pub struct RuleFuzzer<I> {
+    interpreter: I,
+    trials: usize,
+    seed: u64,
+}
+
+impl<I> RuleFuzzer<I> {
+    /// Creates a fuzzer with the given interpreter.
+    pub fn new(interpreter: I) -> Self {
+        Self {
+            interpreter,
+            trials: 100,
+            seed: 0,
+        }
+    }
+
+    /// Sets the number of random substitutions to try per rule. Default: 100
+    pub fn with_trials(self, trials: usize) -> Self {
+        Self { trials, ..self }
+    }
+
+    /// Sets the seed for the random number generator. Default: 0
+    pub fn with_seed(self, seed: u64) -> Self {
+        Self { seed, ..self }
+    }
+
+    /// Fuzzes a single rewrite, returning the first counterexample found.
+    ///
+    /// # Panics
+    /// Panics if the rewrite's searcher or applier isn't pattern-based
+    /// (see [`Searcher::get_pattern_ast`] and [`Applier::get_pattern_ast`]).
+    pub fn check<L, N>(&self, rewrite: &Rewrite<L, N>) -> Option<Counterexample<L, I::Value>>
+    where
+        L: Language,
+        N: Analysis<L> + Default,
+        I: Interpreter<L>,
+    {
+        let (lhs, rhs) = match (
+            rewrite.searcher.get_pattern_ast(),
+            rewrite.applier.get_pattern_ast(),
+        ) {
+            (Some(lhs), Some(rhs)) => (lhs, rhs),
+            _ => panic!("Rule {} must be pattern-based to be fuzzed", rewrite.name),
+        };
+        let vars = rewrite.searcher.vars();
+
+        let mut rng = FuzzRng::new(self.seed);
+        let mut skipped = 0;
+        for _ in 0..self.trials {
+            let terms: Vec<(Var, RecExpr<L>)> = vars
+                .iter()
+                .map(|&v| (v, self.interpreter.arbitrary(&mut rng)))
+                .collect();
+            let lhs_expr = instantiate(lhs, &terms);
+            let lhs_value = match self.interpreter.eval_expr(&lhs_expr) {
+                Some(value) => value,
+                None => {
+                    skipped += 1;
+                    continue;
+                }
+            };
+
+            let mut egraph = EGraph::<L, N>::default();
+            let mut subst = Subst::with_capacity(terms.len());
+            for (v, term) in &terms {
+                subst.insert(*v, egraph.add_expr(term));
+            }
+            let eclass = egraph.add_expr(&lhs_expr);
+            egraph.rebuild();
+            let applied =
+                rewrite
+                    .applier
+                    .apply_one(&mut egraph, eclass, &subst, None, rewrite.name);
+            if applied.is_empty() {
+                skipped += 1;
+                continue;
+            }
+
+            let rhs_expr = instantiate(rhs, &terms);
+            let rhs_value = self.interpreter.eval_expr(&rhs_expr);
+            if rhs_value.as_ref() != Some(&lhs_value) {
+                return Some(Counterexample {
+                    rule: rewrite.name,
+                    subst: terms,
+                    lhs: lhs_expr,
+                    rhs: rhs_expr,
+                    lhs_value,
+                    rhs_value,
+                });
+            }
+        }
+
+        debug!(
+            "Fuzzed {}: {} trials, {} skipped",
+            rewrite.name, self.trials, skipped
+        );
+        None
+    }
+
+    /// Fuzzes each rewrite, returning a counterexample for each unsound one.
+    ///
+    /// Rewrites that aren't pattern-based are skipped with a warning.
+    pub fn check_all<'a, L, N, R>(&self, rewrites: R) -> Vec<Counterexample<L, I::Value>>
+    where
+        L: Language + 'a,
+        N: Analysis<L> + Default + 'a,
+        I: Interpreter<L>,
+        R: IntoIterator<Item = &'a Rewrite<L, N>>,
+    {
+        rewrites
+            .into_iter()
+            .filter(|rw| {
+                let ok = rw.searcher.get_pattern_ast().is_some()
+                    && rw.applier.get_pattern_ast().is_some();
+                if !ok {
+                    warn!("Skipping rule {}, it is not pattern-based", rw.name);
+                }
+                ok
+            })
+            .filter_map(|rw| self.check(rw))
+            .collect()
+    }
+}
+
+fn instantiate<L: Language>(pat: &PatternAst<L>, terms: &[(Var, RecExpr<L>)]) -> RecExpr<L> {
+    let mut expr = RecExpr::default();
+    let mut ids: Vec<Id> = Vec::with_capacity(pat.as_ref().len());
+    for node in pat.as_ref() {
+        let id = match node {
+            ENodeOrVar::ENode(n) => expr.add(n.clone().map_children(|c| ids[usize::from(c)])),
+            ENodeOrVar::Var(v) => {
+                let term = match terms.iter().find(|(var, _)| var == v) {
+                    Some((_, term)) => term,
+                    None => panic!("Variable {} is not bound by the left-hand side", v),
+                };
+                let mut term_ids: Vec<Id> = Vec::with_capacity(term.as_ref().len());
+                for n in term.as_ref() {
+                    let id = expr.add(n.clone().map_children(|c| term_ids[usize::from(c)]));
+                    term_ids.push(id);
+                }
+                *term_ids.last().unwrap()
+            }
+        };
+        ids.push(id);
+    }
+    expr
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    define_language! {
+        enum Arith {
+            Num(i64),
+            "+" = Add([Id; 2]),
+            "*" = Mul([Id; 2]),
+            "/" = Div([Id; 2]),
+        }
+    }
+
+    struct Eval;
+
+    impl Interpreter<Arith> for Eval {
+        type Value = i64;
+
+        fn eval(&self, enode: &Arith, args: &[i64]) -> Option<i64> {
+            match enode {
+                Arith::Num(n) => Some(*n),
+                Arith::Add(_) => args[0].checked_add(args[1]),
+                Arith::Mul(_) => args[0].checked_mul(args[1]),
+                Arith::Div(_) => args[0].checked_div(args[1]),
+            }
+        }
+
+        fn arbitrary(&self, rng: &mut FuzzRng) -> RecExpr<Arith> {
+            let mut expr = RecExpr::default();
+            let a = expr.add(Arith::Num(rng.below(5) as i64));
+            if rng.below(2) == 0 {
+                let b = expr.add(Arith::Num(rng.below(5) as i64));
+                expr.add(Arith::Add([a, b]));
+            }
+            expr
+        }
+    }
+
+    fn is_num(var: &str, n: i64) -> impl Fn(&mut EGraph<Arith, ()>, Id, &Subst) -> bool {
+        let var: Var = var.parse().unwrap();
+        move |egraph, _, subst| egraph[subst[var]].nodes.contains(&Arith::Num(n))
+    }
+
+    #[test]
+    fn finds_unsound_rules() {
+        crate::init_logger();
+        let rules: Vec<Rewrite<Arith, ()>> = vec![
+            rewrite!("mul-comm"; "(* ?a ?b)" => "(* ?b ?a)"),
+            rewrite!("distribute"; "(* ?a (+ ?b ?c))" => "(+ (* ?a ?b) (* ?a ?c))"),
+            rewrite!("bad-mul-add"; "(* ?a ?b)" => "(+ ?a ?b)"),
+            rewrite!("div-self"; "(/ ?a ?a)" => "1"),
+        ];
+        let bad = RuleFuzzer::new(Eval).check_all(&rules);
+        let names: Vec<_> = bad.iter().map(|c| c.rule.as_str()).collect();
+        assert_eq!(names, ["bad-mul-add"]);
+
+        let c = &bad[0];
+        assert_ne!(Some(&c.lhs_value), c.rhs_value.as_ref());
+        let shown = c.to_string();
+        assert!(shown.contains("?a = "), "{}", shown);
+        assert!(shown.contains("?b = "), "{}", shown);
+    }
+
+    #[test]
+    fn checks_conditions_first() {
+        crate::init_logger();
+        let unconditional: Rewrite<Arith, ()> = rewrite!("mul-1"; "(* ?a ?b)" => "?b");
+        assert!(RuleFuzzer::new(Eval).check(&unconditional).is_some());
+
+        let conditional: Rewrite<Arith, ()> =
+            rewrite!("mul-1"; "(* ?a ?b)" => "?b" if is_num("?a", 1));
+        assert!(RuleFuzzer::new(Eval)
+            .with_trials(500)
+            .check(&conditional)
+            .is_none());
+    }
+
+    #[test]
+    fn rng_never_stuck() {
+        let mut rng = FuzzRng::new(0x9E37_79B9_7F4A_7C15);
+        let first = rng.next_u64();
+        assert_ne!(first, 0);
+        assert_ne!(rng.next_u64(), first);
+    }
+}
@@ -39,6 +39,62 @@ pub trait Language: Debug + Clone + Eq + Ord + Hash {
     /// Returns a mutable slice of the children of this e-node.
     fn children_mut(&mut self) -> &mut [Id];
 
+    /// Returns a copy of this enode with the given children, which may be
+    /// more or fewer than this enode has.
+    /// Returns `None` if this operator can't have that many children.
+    ///
+    /// This is used to match and instantiate sequence variables like
+    /// `?args...` in [`Pattern`]s.
+    /// The default implementation only supports the current number of
+    /// children, so languages with variable-arity operators should override
+    /// it. The [`define_language!`] macro and [`SymbolLang`] do so.
+    fn with_children(&self, children: Vec<Id>) -> Option<Self> {
+        if children.len() == self.len() {
+            let mut children = children.into_iter();
+            Some(self.clone().map_children(|_| children.next().unwrap()))
+        } else {
+            None
+        }
+    }
+
+    /// Returns true if this enode's operator is associative and commutative.
+    ///
+    /// The [`EGraph`] sorts the children of AC enodes, so enodes that only
+    /// differ by the order of their children are the same enode, and
+    /// [`Pattern`]s match AC enodes modulo associativity and commutativity.
+    /// This makes rules like `(+ ?a ?b) => (+ ?b ?a)` unnecessary.
+    ///
+    /// The default implementation returns `false`.
+    /// In [`define_language!`], mark a variant with `#[ac]` to make it AC.
+    fn is_ac(&self) -> bool {
+        false
+    }
+
+    /// Returns the sort (type) of the enodes with this operator, if it has
+    /// one.
+    ///
+    /// Sorts are optional: enodes without a sort can be used anywhere.
+    /// When they are declared, [`Rewrite::new`] rejects rules whose patterns
+    /// are not well-sorted (see [`PatternSorts`]), and debug builds check
+    /// that unions only merge eclasses of the same sort.
+    ///
+    /// The default implementation returns `None`.
+    /// In [`define_language!`] and `#[derive(Language)]`, declare the sorts
+    /// of a variant with `#[sort(i32 <- i32, i32)]`, giving the sort of the
+    /// enode and then the sorts of its children.
+    fn sort(&self) -> Option<Symbol> {
+        None
+    }
+
+    /// Returns the sort that the `i`th child of this enode must have,
+    /// if any. See [`Language::sort`].
+    ///
+    /// The default implementation returns `None`.
+    #[allow(unused_variables)]
+    fn child_sort(&self, i: usize) -> Option<Symbol> {
+        None
+    }
+
     /// Runs a given function on each child `Id`.
     fn for_each<F: FnMut(Id)>(&self, f: F) {
         self.children().iter().copied().for_each(f)
@@ -412,22 +468,219 @@ impl<L: Language> RecExpr<L> {
         Id::from(self.nodes.len() - 1)
     }
 
-    pub(crate) fn compact(mut self) -> Self {
+    /// Returns the [`Id`] of the root, which is the last node.
+    ///
+    /// Panics if this expression is empty.
+    pub fn root(&self) -> Id {
+        assert!(
+            !self.nodes.is_empty(),
+            "Tried to get the root of an empty RecExpr"
+        );
+        Id::from(self.nodes.len() - 1)
+    }
+
+    /** Removes duplicate subterms, keeping the remaining nodes in order and
+    the root last.
+
+    Extracted expressions and hand-built ones often contain the same
+    subterm more than once; after compacting, each distinct subterm is a
+    single node shared by all its uses.
+    Nodes that aren't reachable from the root are kept; to drop them too,
+    use [`extract`](RecExpr::extract()) on the root.
+
+    # Example
+    ```
+    # use egg::*;
+    let expr: RecExpr<SymbolLang> = "(+ (* x 2) (* x 2))".parse().unwrap();
+    assert_eq!(expr.as_ref().len(), 7);
+    let compacted = expr.clone().compact();
+    assert_eq!(compacted.as_ref().len(), 4);
+    assert_eq!(compacted.to_string(), expr.to_string());
+    ```
+    **/
+    pub fn compact(mut self) -> Self {
         let mut ids = HashMap::<Id, Id>::default();
         let mut set = IndexSet::default();
+        let mut root = None;
         for (i, node) in self.nodes.drain(..).enumerate() {
             let node = node.map_children(|id| ids[&id]);
-            let new_id = set.insert_full(node).0;
+            let (new_id, _) = set.insert_full(node.clone());
             ids.insert(Id::from(i), Id::from(new_id));
+            root = Some(node);
         }
+        let n_nodes = set.len();
         self.nodes.extend(set);
+        // the root can only equal an earlier node if that one is unreachable,
+        // but the root must stay last
+        if let Some(root) = root {
+            if self.nodes[n_nodes - 1] != root {
+                self.nodes.push(root);
+            }
+        }
         self
     }
 
-    pub(crate) fn extract(&self, new_root: Id) -> Self {
+    /// Returns the subterm rooted at the given node as a new, compact
+    /// expression.
+    pub fn extract(&self, new_root: Id) -> Self {
         self[new_root].build_recexpr(|id| self[id].clone())
     }
 
+    /** Returns the node at the given position in the tree this expression
+    represents, or `None` if there is no such position, like in an empty
+    expression.
+
+    A position is the path of child indexes leading from the root to the
+    subterm, so the root is at `[]` and the second child of its first child
+    at `[0, 1]`.
+
+    # Example
+    ```
+    # use egg::*;
+    let expr: RecExpr<SymbolLang> = "(+ (* x 2) y)".parse().unwrap();
+    assert_eq!(expr[expr.get_at(&[0, 1]).unwrap()], SymbolLang::leaf("2"));
+    assert_eq!(expr.get_at(&[]), Some(expr.root()));
+    assert_eq!(expr.get_at(&[1, 0]), None);
+    ```
+    **/
+    pub fn get_at(&self, pos: &[usize]) -> Option<Id> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+        let mut id = self.root();
+        for &i in pos {
+            id = *self[id].children().get(i)?;
+        }
+        Some(id)
+    }
+
+    /** Iterates over the subterms of the tree this expression represents
+    in pre-order, yielding each subterm's position (see
+    [`get_at`](RecExpr::get_at())) and node.
+
+    A subterm shared in the DAG is visited once for every position it
+    appears at.
+
+    # Example
+    ```
+    # use egg::*;
+    let expr: RecExpr<SymbolLang> = "(+ (* x 2) y)".parse().unwrap();
+    let subterms: Vec<(Vec<usize>, String)> = expr
+        .subterms()
+        .map(|(pos, id)| (pos, expr[id].to_string()))
+        .collect();
+    assert_eq!(subterms, vec![
+        (vec![], "+".to_string()),
+        (vec![0], "*".to_string()),
+        (vec![0, 0], "x".to_string()),
+        (vec![0, 1], "2".to_string()),
+        (vec![1], "y".to_string()),
+    ]);
+    ```
+    **/
+    pub fn subterms(&self) -> impl Iterator<Item = (Vec<usize>, Id)> + '_ {
+        let mut stack = vec![];
+        if !self.nodes.is_empty() {
+            stack.push((vec![], self.root()));
+        }
+        std::iter::from_fn(move || {
+            let (pos, id) = stack.pop()?;
+            for (i, &child) in self[id].children().iter().enumerate().rev() {
+                let mut child_pos = pos.clone();
+                child_pos.push(i);
+                stack.push((child_pos, child));
+            }
+            Some((pos, id))
+        })
+    }
+
+    /** Returns a copy of this expression with the subterm at the given
+    position replaced by `with`, or `None` if there is no such position or
+    `with` is empty.
+
+    Only that one occurrence is replaced, even if the subterm is shared with
+    other positions. The result is [compact](RecExpr::compact()) and
+    only has nodes reachable from its root.
+
+    # Example
+    ```
+    # use egg::*;
+    let expr: RecExpr<SymbolLang> = "(+ (* x 2) (* x 2))".parse().unwrap();
+    let with = "(<< x 1)".parse().unwrap();
+    let replaced = expr.replace_at(&[1], &with).unwrap();
+    assert_eq!(replaced.to_string(), "(+ (* x 2) (<< x 1))");
+    assert!(expr.replace_at(&[2], &with).is_none());
+    ```
+    **/
+    pub fn replace_at(&self, pos: &[usize], with: &RecExpr<L>) -> Option<Self> {
+        if with.nodes.is_empty() {
+            return None;
+        }
+        let mut path = vec![self.get_at(&[])?];
+        for &i in pos {
+            path.push(*self[*path.last().unwrap()].children().get(i)?);
+        }
+
+        let mut nodes = self.nodes.clone();
+        let offset = nodes.len();
+        nodes.extend(with.nodes.iter().map(|n| {
+            n.clone()
+                .map_children(|id| Id::from(usize::from(id) + offset))
+        }));
+        let mut new_id = Id::from(nodes.len() - 1);
+        for (&i, &ancestor) in pos.iter().zip(&path).rev() {
+            let mut node = self[ancestor].clone();
+            node.children_mut()[i] = new_id;
+            nodes.push(node);
+            new_id = Id::from(nodes.len() - 1);
+        }
+        Some(RecExpr::from(nodes).extract(new_id))
+    }
+
+    /// Returns the number of nodes in the tree this expression represents,
+    /// counting shared subterms once for every use, like [`AstSize`].
+    /// Saturates at [`usize::MAX`].
+    pub fn size(&self) -> usize {
+        let mut sizes: Vec<usize> = Vec::with_capacity(self.nodes.len());
+        for node in &self.nodes {
+            let size = node.fold(1usize, |size, id| {
+                size.saturating_add(sizes[usize::from(id)])
+            });
+            sizes.push(size);
+        }
+        sizes.last().copied().unwrap_or(0)
+    }
+
+    /// Returns the depth of this expression, where a leaf has depth 1,
+    /// like [`AstDepth`].
+    pub fn depth(&self) -> usize {
+        let mut depths: Vec<usize> = Vec::with_capacity(self.nodes.len());
+        for node in &self.nodes {
+            let depth = 1 + node.fold(0, |depth, id| depth.max(depths[usize::from(id)]));
+            depths.push(depth);
+        }
+        depths.last().copied().unwrap_or(0)
+    }
+
+    /// Counts how many times each node is used as a child by the nodes
+    /// reachable from the root, which is the last node.
+    pub(crate) fn use_counts(&self) -> Vec<usize> {
+        let mut uses = vec![0; self.nodes.len()];
+        let mut reachable = vec![false; self.nodes.len()];
+        if let Some(root) = reachable.last_mut() {
+            *root = true;
+        }
+        for i in (0..self.nodes.len()).rev() {
+            if reachable[i] {
+                for &child in self.nodes[i].children() {
+                    uses[usize::from(child)] += 1;
+                    reachable[usize::from(child)] = true;
+                }
+            }
+        }
+        uses
+    }
+
     /// Checks if this expr is a DAG, i.e. doesn't have any back edges
     pub fn is_dag(&self) -> bool {
         for (i, n) in self.nodes.iter().enumerate() {
@@ -498,6 +751,7 @@ impl<L: Language + Display> RecExpr<L> {
     /// Pretty print with a maximum line length.
     ///
     /// This gives you a nice, indented, pretty-printed s-expression.
+    /// For conventional notation with infix operators, see [`PrettyPrinter`].
     ///
     /// # Example
     /// ```
@@ -540,6 +794,11 @@ pub enum RecExprParseError<E> {
     /// because the input had an invalid structure (e.g. unpaired parentheses).
     #[error(transparent)]
     BadSexp(SexpError),
+
+    /// The input was not a well-formed SMT-LIB2 term, see
+    /// [`RecExpr::from_smtlib`].
+    #[error("bad SMT-LIB term: {0}")]
+    BadSmtLib(String),
 }
 
 impl<L: FromOp> FromStr for RecExpr<L> {
@@ -741,6 +1000,40 @@ pub trait Analysis<L: Language>: Sized {
     /// `Analysis::merge` when unions are performed.
     #[allow(unused_variables)]
     fn modify(egraph: &mut EGraph<L, Self>, id: Id) {}
+
+    /// Checks an eclass's data against the named predicate of an
+    /// annotated pattern variable, like `const` in `?x:const`.
+    ///
+    /// [`Pattern`]s check these while matching, as soon as the variable
+    /// is bound, so eclasses that fail are pruned early.
+    ///
+    /// By default this returns `true`, accepting every eclass, so
+    /// annotations are ignored by analyses that don't define predicates.
+    #[allow(unused_variables)]
+    fn check_predicate(&self, predicate: Symbol, data: &Self::Data) -> bool {
+        true
+    }
+
+    /// Produces the [`Value`] that an annotated pattern variable like
+    /// `?n:const` is bound to in the [`Subst`] of a match,
+    /// given the data of the eclass it matched.
+    ///
+    /// This is called for every annotated variable of every match after
+    /// [`Analysis::check_predicate`] accepted it.
+    /// By default this returns `None`, binding no value.
+    #[allow(unused_variables)]
+    fn bind_value(&self, predicate: Symbol, data: &Self::Data) -> Option<Value> {
+        None
+    }
+
+    /// Makes an enode for a variable that is only bound to a [`Value`]
+    /// when a [`Pattern`] is instantiated, see [`Subst::insert_value`].
+    ///
+    /// By default this returns `None`, which makes the instantiation panic.
+    #[allow(unused_variables)]
+    fn value_node(&self, value: &Value) -> Option<L> {
+        None
+    }
 }
 
 impl<L: Language> Analysis<L> for () {
@@ -837,6 +1130,10 @@ impl Language for SymbolLang {
     fn children_mut(&mut self) -> &mut [Id] {
         &mut self.children
     }
+
+    fn with_children(&self, children: Vec<Id>) -> Option<Self> {
+        Some(Self::new(self.op, children))
+    }
 }
 
 impl Display for SymbolLang {
@@ -855,3 +1152,64 @@ impl FromOp for SymbolLang {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{SymbolLang as S, *};
+
+    #[test]
+    fn compact_keeps_root_last() {
+        crate::init_logger();
+        // the root duplicates an earlier node, and the g is unreachable
+        let mut expr = RecExpr::default();
+        let x = expr.add(S::leaf("x"));
+        let f = expr.add(S::new("f", vec![x]));
+        expr.add(S::new("g", vec![f, x]));
+        expr.add(S::leaf("x"));
+        expr.add(S::new("f", vec![x]));
+        let expr = expr.compact();
+        assert_eq!(expr.as_ref().len(), 4);
+        assert_eq!(expr.root(), Id::from(3));
+        assert_eq!(expr.to_string(), "(f x)");
+
+        let pruned = expr.extract(expr.root());
+        assert_eq!(pruned.as_ref(), &[S::leaf("x"), S::new("f", vec![x])]);
+    }
+
+    #[test]
+    fn size_and_depth() {
+        crate::init_logger();
+        let mut expr = RecExpr::default();
+        let mut id = expr.add(S::leaf("x"));
+        for _ in 0..100 {
+            id = expr.add(S::new("+", vec![id, id]));
+        }
+        assert_eq!(expr.depth(), 101);
+        assert_eq!(expr.size(), usize::MAX);
+        assert_eq!(expr.extract(Id::from(3)).size(), 15);
+
+        let expr: RecExpr<S> = "(f (g a) b (h (g a)))".parse().unwrap();
+        assert_eq!(expr.size(), 7);
+        assert_eq!(expr.depth(), 4);
+        let positions: Vec<Vec<usize>> = expr.subterms().map(|(pos, _)| pos).collect();
+        assert_eq!(positions.len(), expr.size());
+        for pos in positions {
+            let id = expr.get_at(&pos).unwrap();
+            let same = expr.replace_at(&pos, &expr.extract(id)).unwrap();
+            assert_eq!(same.to_string(), expr.to_string());
+            assert_eq!(same.as_ref().len(), 5);
+        }
+    }
+
+    #[test]
+    fn empty_positions() {
+        crate::init_logger();
+        let empty = RecExpr::<S>::default();
+        let expr: RecExpr<S> = "(f a b)".parse().unwrap();
+        assert_eq!(empty.get_at(&[]), None);
+        assert_eq!(empty.get_at(&[0]), None);
+        assert!(empty.replace_at(&[], &expr).is_none());
+        assert!(expr.replace_at(&[1], &empty).is_none());
+        assert!(expr.replace_at(&[], &empty).is_none());
+    }
+}
@@ -42,15 +42,26 @@ mod eclass;
 mod egraph;
 mod explain;
 mod extract;
+mod fuzz;
 mod language;
 #[cfg(feature = "lp")]
 mod lp_extract;
 mod machine;
 mod multipattern;
 mod pattern;
+mod pattern_set;
+mod printer;
+#[cfg(feature = "reports")]
+mod profile;
+mod query;
 mod rewrite;
+mod rule_file;
 mod run;
+mod smtlib;
+mod sort;
 mod subst;
+mod synth;
+mod table;
 mod unionfind;
 mod util;
 
@@ -96,18 +107,36 @@ pub use {
         UnionEqualities,
     },
     extract::*,
+    fuzz::{Counterexample, FuzzRng, Interpreter, RuleFuzzer},
     language::*,
+    machine::OpStats,
     multipattern::*,
     pattern::{ENodeOrVar, Pattern, PatternAst, SearchMatches},
-    rewrite::{Applier, Condition, ConditionEqual, ConditionalApplier, Rewrite, Searcher},
+    pattern_set::PatternSet,
+    printer::{Assoc, Fixity, PrettyLanguage, PrettyPrinter},
+    query::Query,
+    rewrite::{
+        Applier, Condition, ConditionAnd, ConditionData, ConditionEqual, ConditionMatches,
+        ConditionNot, ConditionNotEqual, ConditionOr, ConditionalApplier, Rewrite, Searcher,
+    },
+    rule_file::{print_rules, RuleDecl, RuleParseError, RuleParser},
     run::*,
-    subst::{Subst, Var},
+    sort::{PatternSorts, SortError},
+    subst::{Subst, Value, Var},
+    synth::Synthesizer,
+    table::{TableLookup, TableSet},
     util::*,
 };
 
 #[cfg(feature = "lp")]
 pub use lp_extract::*;
 
+#[cfg(feature = "derive")]
+pub use egg_derive::{Display, FromOp, Language};
+
+#[cfg(feature = "reports")]
+pub use profile::{Profiler, TraceEvent};
+
 #[cfg(test)]
 fn init_logger() {
     let _ = env_logger::builder().is_test(true).try_init();
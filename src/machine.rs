@@ -6,6 +6,8 @@ type Result = result::Result<(), ()>;
 #[derive(Default)]
 struct Machine {
     reg: Vec<Id>,
+    // the bindings of sequence variables, indexed like `reg`
+    seqs: Vec<Vec<Id>>,
     // a buffer to re-use for lookups
     lookup: Vec<Id>,
 }
@@ -17,14 +19,64 @@ struct Reg(u32);
 pub struct Program<L> {
     instructions: Vec<Instruction<L>>,
     subst: Subst,
+    seqs: Vec<(Var, u32)>,
+    predicates: Vec<(Var, Symbol)>,
+}
+
+impl<L> Default for Program<L> {
+    fn default() -> Self {
+        Self {
+            instructions: vec![],
+            subst: Default::default(),
+            seqs: vec![],
+            predicates: vec![],
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 enum Instruction<L> {
-    Bind { node: L, i: Reg, out: Reg },
-    Compare { i: Reg, j: Reg },
-    Lookup { term: Vec<ENodeOrReg<L>>, i: Reg },
-    Scan { out: Reg },
+    Bind {
+        node: L,
+        i: Reg,
+        out: Reg,
+    },
+    BindSequence {
+        node: L,
+        i: Reg,
+        out: Reg,
+        prefix: usize,
+        seq: u32,
+    },
+    Compare {
+        i: Reg,
+        j: Reg,
+    },
+    CompareSequence {
+        seq: u32,
+        other: u32,
+    },
+    Lookup {
+        term: Vec<ENodeOrReg<L>>,
+        i: Reg,
+    },
+    Scan {
+        out: Reg,
+    },
+    CheckPredicate {
+        i: Reg,
+        predicate: Symbol,
+    },
+}
+
+impl<L> Instruction<L> {
+    /// Branching instructions can bind registers in more than one way.
+    fn is_branching(&self) -> bool {
+        matches!(
+            self,
+            Instruction::Bind { .. } | Instruction::BindSequence { .. } | Instruction::Scan { .. }
+        )
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -83,6 +135,52 @@ where
     }
 }
 
+/// Like [`for_each_matching_node`], but one child of `node` is a sequence
+/// variable, so matching nodes can have any number of children in its place.
+fn for_each_matching_sequence_node<L, D>(
+    eclass: &EClass<L, D>,
+    node: &L,
+    f: impl FnMut(&L) -> Result,
+) -> Result
+where
+    L: Language,
+{
+    let fixed = node.len() - 1;
+    #[allow(enum_intrinsics_non_enums)]
+    let discrim = std::mem::discriminant(node);
+    eclass
+        .nodes
+        .iter()
+        .filter(|n| {
+            #[allow(enum_intrinsics_non_enums)]
+            let same_discrim = std::mem::discriminant(*n) == discrim;
+            same_discrim
+                && n.len() >= fixed
+                && match node.with_children(vec![Id::from(0); n.len()]) {
+                    Some(resized) => resized.matches(n),
+                    None => false,
+                }
+        })
+        .try_for_each(f)
+}
+
+/// The most orders of an AC enode's children tried by
+/// [`Instruction::Bind`], which is 6 factorial.
+const MAX_AC_PERMUTATIONS: usize = 720;
+
+/// Rearranges `ids` into the next lexicographic permutation,
+/// returning false if it was already the last one.
+fn next_permutation(ids: &mut [Id]) -> bool {
+    let i = match (1..ids.len()).rev().find(|&i| ids[i - 1] < ids[i]) {
+        Some(i) => i,
+        None => return false,
+    };
+    let j = (i..ids.len()).rev().find(|&j| ids[i - 1] < ids[j]).unwrap();
+    ids.swap(i - 1, j);
+    ids[i..].reverse();
+    true
+}
+
 impl Machine {
     #[inline(always)]
     fn reg(&self, reg: Reg) -> Id {
@@ -102,86 +200,294 @@ impl Machine {
     {
         let mut instructions = instructions.iter();
         while let Some(instruction) = instructions.next() {
-            match instruction {
-                Instruction::Bind { i, out, node } => {
-                    let remaining_instructions = instructions.as_slice();
-                    return for_each_matching_node(&egraph[self.reg(*i)], node, |matched| {
-                        self.reg.truncate(out.0 as usize);
-                        matched.for_each(|id| self.reg.push(id));
-                        self.run(egraph, remaining_instructions, subst, yield_fn)
-                    });
-                }
-                Instruction::Scan { out } => {
-                    let remaining_instructions = instructions.as_slice();
-                    for class in egraph.classes() {
-                        self.reg.truncate(out.0 as usize);
-                        self.reg.push(class.id);
-                        self.run(egraph, remaining_instructions, subst, yield_fn)?
-                    }
-                    return Ok(());
-                }
-                Instruction::Compare { i, j } => {
-                    if egraph.find(self.reg(*i)) != egraph.find(self.reg(*j)) {
+            if instruction.is_branching() {
+                let remaining_instructions = instructions.as_slice();
+                return self.branch(egraph, instruction, |machine| {
+                    machine.run(egraph, remaining_instructions, subst, yield_fn)
+                });
+            } else if !self.check(egraph, instruction) {
+                return Ok(());
+            }
+        }
+
+        yield_fn(self, subst)
+    }
+
+    /// Runs every program in the trie below `node`, calling `yield_fn` with
+    /// the index of each program that matches.
+    fn run_trie<L, N>(
+        &mut self,
+        egraph: &EGraph<L, N>,
+        trie: &ProgramTrie<L>,
+        node: usize,
+        yield_fn: &mut impl FnMut(&Self, usize) -> Result,
+    ) -> Result
+    where
+        L: Language,
+        N: Analysis<L>,
+    {
+        let node = &trie.nodes[node];
+        for &program in &node.accepts {
+            yield_fn(self, program)?;
+        }
+        for &child in &node.children {
+            let instruction = trie.nodes[child].instruction.as_ref().unwrap();
+            if instruction.is_branching() {
+                self.branch(egraph, instruction, |machine| {
+                    machine.run_trie(egraph, trie, child, yield_fn)
+                })?;
+            } else if self.check(egraph, instruction) {
+                // checks don't write registers, so the siblings can reuse them
+                self.run_trie(egraph, trie, child, yield_fn)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs a branching instruction, calling `k` once for each way it can
+    /// bind registers.
+    fn branch<L, N>(
+        &mut self,
+        egraph: &EGraph<L, N>,
+        instruction: &Instruction<L>,
+        mut k: impl FnMut(&mut Self) -> Result,
+    ) -> Result
+    where
+        L: Language,
+        N: Analysis<L>,
+    {
+        match instruction {
+            Instruction::Bind { i, out, node } => {
+                for_each_matching_node(&egraph[self.reg(*i)], node, |matched| {
+                    if node.is_ac() {
+                        // AC enodes match their children in any order
+                        let mut children = matched.children().to_vec();
+                        children.sort_unstable();
+                        for _ in 0..MAX_AC_PERMUTATIONS {
+                            self.reg.truncate(out.0 as usize);
+                            self.reg.extend_from_slice(&children);
+                            k(self)?;
+                            if !next_permutation(&mut children) {
+                                break;
+                            }
+                        }
                         return Ok(());
                     }
+                    self.reg.truncate(out.0 as usize);
+                    matched.for_each(|id| self.reg.push(id));
+                    k(self)
+                })
+            }
+            Instruction::BindSequence {
+                i,
+                out,
+                node,
+                prefix,
+                seq,
+            } => {
+                let suffix = node.len() - prefix - 1;
+                let eclass = &egraph[self.reg(*i)];
+                for_each_matching_sequence_node(eclass, node, |matched| {
+                    let children = matched.children();
+                    let end = children.len() - suffix;
+                    self.reg.truncate(out.0 as usize);
+                    self.reg.extend_from_slice(&children[..*prefix]);
+                    self.reg.extend_from_slice(&children[end..]);
+                    self.seqs.truncate(*seq as usize);
+                    self.seqs.push(children[*prefix..end].to_vec());
+                    k(self)
+                })
+            }
+            Instruction::Scan { out } => {
+                for class in egraph.classes() {
+                    self.reg.truncate(out.0 as usize);
+                    self.reg.push(class.id);
+                    k(self)?
                 }
-                Instruction::Lookup { term, i } => {
-                    self.lookup.clear();
-                    for node in term {
-                        match node {
-                            ENodeOrReg::ENode(node) => {
-                                let look = |i| self.lookup[usize::from(i)];
-                                match egraph.lookup(node.clone().map_children(look)) {
-                                    Some(id) => self.lookup.push(id),
-                                    None => return Ok(()),
-                                }
-                            }
-                            ENodeOrReg::Reg(r) => {
-                                self.lookup.push(egraph.find(self.reg(*r)));
+                Ok(())
+            }
+            _ => unreachable!("{:?} is not a branching instruction", instruction),
+        }
+    }
+
+    /// Runs a non-branching instruction, returning whether it succeeded.
+    fn check<L, N>(&mut self, egraph: &EGraph<L, N>, instruction: &Instruction<L>) -> bool
+    where
+        L: Language,
+        N: Analysis<L>,
+    {
+        match instruction {
+            Instruction::CheckPredicate { i, predicate } => {
+                let data = &egraph[self.reg(*i)].data;
+                egraph.analysis.check_predicate(*predicate, data)
+            }
+            Instruction::Compare { i, j } => egraph.find(self.reg(*i)) == egraph.find(self.reg(*j)),
+            Instruction::CompareSequence { seq, other } => {
+                let a = &self.seqs[*seq as usize];
+                let b = &self.seqs[*other as usize];
+                a.len() == b.len()
+                    && a.iter()
+                        .zip(b)
+                        .all(|(&x, &y)| egraph.find(x) == egraph.find(y))
+            }
+            Instruction::Lookup { term, i } => {
+                self.lookup.clear();
+                for node in term {
+                    match node {
+                        ENodeOrReg::ENode(node) => {
+                            let look = |i| self.lookup[usize::from(i)];
+                            match egraph.lookup(node.clone().map_children(look)) {
+                                Some(id) => self.lookup.push(id),
+                                None => return false,
                             }
                         }
-                    }
-
-                    let id = egraph.find(self.reg(*i));
-                    if self.lookup.last().copied() != Some(id) {
-                        return Ok(());
+                        ENodeOrReg::Reg(r) => {
+                            self.lookup.push(egraph.find(self.reg(*r)));
+                        }
                     }
                 }
+
+                let id = egraph.find(self.reg(*i));
+                self.lookup.last().copied() == Some(id)
             }
+            _ => unreachable!("{:?} is a branching instruction", instruction),
         }
+    }
+}
 
-        yield_fn(self, subst)
+/** Statistics about the enodes of an [`EGraph`], used to order the joins of
+a compiled pattern.
+
+By default, a [`Pattern`] decides which of its nodes to match first with a
+fixed heuristic.
+[`Pattern::recompile`] and [`MultiPattern::recompile`] use these statistics
+instead, so patterns start from the operators with the fewest enodes per
+eclass, and multipatterns start from their most selective atom.
+Recompile between iterations as the egraph changes; the [`Runner`] does so
+when it searches rules with a [`PatternSet`].
+
+# Example
+```
+use egg::*;
+let mut egraph = EGraph::<SymbolLang, ()>::default();
+egraph.add_expr(&"(+ (f a) (f b))".parse().unwrap());
+egraph.add_expr(&"(+ (f a) (g c))".parse().unwrap());
+egraph.rebuild();
+
+let stats = OpStats::new(&egraph);
+let f = SymbolLang::new("f", vec![Id::from(0)]);
+assert_eq!(stats.nodes(&f), 2);
+assert_eq!(stats.classes(&f), 2);
+assert_eq!(stats.fanout(&f), 1.0);
+
+let mut pat: Pattern<SymbolLang> = "(+ (f ?x) (g ?y))".parse().unwrap();
+pat.recompile(&stats);
+assert_eq!(pat.search(&egraph).len(), 1);
+```
+*/
+#[derive(Debug, Clone)]
+pub struct OpStats<L> {
+    // keyed by operator, that is, enodes with all children set to 0
+    nodes: HashMap<L, usize>,
+    classes: HashMap<L, usize>,
+    n_classes: usize,
+}
+
+impl<L: Language> OpStats<L> {
+    /// Collects the statistics of the given egraph.
+    pub fn new<N: Analysis<L>>(egraph: &EGraph<L, N>) -> Self {
+        let mut nodes = HashMap::<L, usize>::default();
+        let mut classes = HashMap::<L, usize>::default();
+        for class in egraph.classes() {
+            let mut ops: Vec<L> = class.iter().map(Self::op).collect();
+            for op in &ops {
+                *nodes.entry(op.clone()).or_default() += 1;
+            }
+            ops.sort();
+            ops.dedup();
+            for op in ops {
+                *classes.entry(op).or_default() += 1;
+            }
+        }
+        Self {
+            nodes,
+            classes,
+            n_classes: egraph.number_of_classes(),
+        }
+    }
+
+    fn op(node: &L) -> L {
+        node.clone().map_children(|_| Id::from(0))
+    }
+
+    /// Returns the number of enodes with the same operator as `node`.
+    pub fn nodes(&self, node: &L) -> usize {
+        self.nodes.get(&Self::op(node)).copied().unwrap_or(0)
+    }
+
+    /// Returns the number of eclasses with an enode with the same operator
+    /// as `node`.
+    pub fn classes(&self, node: &L) -> usize {
+        self.classes.get(&Self::op(node)).copied().unwrap_or(0)
+    }
+
+    /// Returns the number of eclasses in the egraph.
+    pub fn number_of_classes(&self) -> usize {
+        self.n_classes
+    }
+
+    /// Returns the average number of enodes with the same operator as
+    /// `node` in the eclasses that have one, which is how many ways
+    /// matching `node` in such an eclass branches.
+    pub fn fanout(&self, node: &L) -> f64 {
+        match self.classes(node) {
+            0 => 0.0,
+            classes => self.nodes(node) as f64 / classes as f64,
+        }
     }
 }
 
-struct Compiler<L> {
+struct Compiler<'a, L> {
+    stats: Option<&'a OpStats<L>>,
     v2r: IndexMap<Var, Reg>,
+    s2r: IndexMap<Var, u32>,
+    predicates: Vec<(Var, Symbol)>,
     free_vars: Vec<HashSet<Var>>,
     subtree_size: Vec<usize>,
     todo_nodes: HashMap<(Id, Reg), L>,
     instructions: Vec<Instruction<L>>,
     next_reg: Reg,
+    next_seq: u32,
 }
 
-impl<L: Language> Compiler<L> {
-    fn new() -> Self {
+impl<'a, L: Language> Compiler<'a, L> {
+    fn new(stats: Option<&'a OpStats<L>>) -> Self {
         Self {
+            stats,
             free_vars: Default::default(),
             subtree_size: Default::default(),
             v2r: Default::default(),
+            s2r: Default::default(),
+            predicates: Default::default(),
             todo_nodes: Default::default(),
             instructions: Default::default(),
             next_reg: Reg(0),
+            next_seq: 0,
         }
     }
 
     fn add_todo(&mut self, pattern: &PatternAst<L>, id: Id, reg: Reg) {
         match &pattern[id] {
             ENodeOrVar::Var(v) => {
+                assert!(
+                    !v.is_sequence(),
+                    "sequence variable {} must be a child of an enode",
+                    v
+                );
                 if let Some(&j) = self.v2r.get(v) {
                     self.instructions.push(Instruction::Compare { i: reg, j })
                 } else {
-                    self.v2r.insert(*v, reg);
+                    self.bind_var(*v, reg);
                 }
             }
             ENodeOrVar::ENode(pat) => {
@@ -190,6 +496,17 @@ impl<L: Language> Compiler<L> {
         }
     }
 
+    fn bind_var(&mut self, var: Var, reg: Reg) {
+        self.v2r.insert(var, reg);
+        // check predicates right away to prune failing matches early
+        for &(v, predicate) in &self.predicates {
+            if v == var {
+                self.instructions
+                    .push(Instruction::CheckPredicate { i: reg, predicate });
+            }
+        }
+    }
+
     fn load_pattern(&mut self, pattern: &PatternAst<L>) {
         let len = pattern.as_ref().len();
         self.free_vars = Vec::with_capacity(len);
@@ -218,9 +535,10 @@ impl<L: Language> Compiler<L> {
     fn next(&mut self) -> Option<((Id, Reg), L)> {
         // we take the max todo according to this key
         // - prefer grounded
+        // - prefer operators that branch less, if we have stats
         // - prefer more free variables
         // - prefer smaller term
-        let key = |(id, _): &&(Id, Reg)| {
+        let key = |(id, reg): &&(Id, Reg)| {
             let i = usize::from(*id);
             let n_bound = self.free_vars[i]
                 .iter()
@@ -228,7 +546,11 @@ impl<L: Language> Compiler<L> {
                 .count();
             let n_free = self.free_vars[i].len() - n_bound;
             let size = self.subtree_size[i] as isize;
-            (n_free == 0, n_free, -size)
+            let fanout = match self.stats {
+                Some(stats) => (stats.fanout(&self.todo_nodes[&(*id, *reg)]) * 1024.0) as u64,
+                None => 0,
+            };
+            (n_free == 0, std::cmp::Reverse(fanout), n_free, -size)
         };
 
         self.todo_nodes
@@ -271,7 +593,7 @@ impl<L: Language> Compiler<L> {
                 // patternbinder is new variable
                 next_out.0 += 1;
                 add_new_pattern(self);
-                self.v2r.insert(v, self.next_reg); //add to known variables.
+                self.bind_var(v, self.next_reg); //add to known variables.
             }
         } else {
             // No pattern binder
@@ -293,12 +615,50 @@ impl<L: Language> Compiler<L> {
                         })
                         .collect(),
                 });
+            } else if let Some((pos, var)) = Self::sequence_child(pattern, &node) {
+                let out = next_out;
+                next_out.0 += node.len() as u32 - 1;
+                let seq = self.next_seq;
+                self.next_seq += 1;
+
+                let op = node.clone().map_children(|_| Id::from(0));
+                self.instructions.push(Instruction::BindSequence {
+                    i: reg,
+                    node: op,
+                    out,
+                    prefix: pos,
+                    seq,
+                });
+
+                let fixed = node
+                    .children()
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| *i != pos);
+                for (i, (_, &child)) in fixed.enumerate() {
+                    self.add_todo(pattern, child, Reg(out.0 + i as u32));
+                }
+                if let Some(&other) = self.s2r.get(&var) {
+                    self.instructions
+                        .push(Instruction::CompareSequence { seq, other });
+                } else {
+                    self.s2r.insert(var, seq);
+                }
             } else {
                 let out = next_out;
                 next_out.0 += node.len() as u32;
 
                 // zero out the children so Bind can use it to sort
                 let op = node.clone().map_children(|_| Id::from(0));
+                let permutations = (1..=op.len()).try_fold(1usize, |n, i| n.checked_mul(i));
+                if op.is_ac() && !matches!(permutations, Some(n) if n <= MAX_AC_PERMUTATIONS) {
+                    log::warn!(
+                        "Only matching {} of the orders of the {} children of AC enode {:?}",
+                        MAX_AC_PERMUTATIONS,
+                        op.len(),
+                        op
+                    );
+                }
                 self.instructions.push(Instruction::Bind {
                     i: reg,
                     node: op,
@@ -313,6 +673,25 @@ impl<L: Language> Compiler<L> {
         self.next_reg = next_out;
     }
 
+    /// Finds the position of the sequence variable among this pattern node's
+    /// children, if it has one.
+    fn sequence_child(pattern: &PatternAst<L>, node: &L) -> Option<(usize, Var)> {
+        let mut seqs = node
+            .children()
+            .iter()
+            .enumerate()
+            .filter_map(|(i, &child)| match &pattern[child] {
+                ENodeOrVar::Var(v) if v.is_sequence() => Some((i, *v)),
+                _ => None,
+            });
+        let first = seqs.next();
+        assert!(
+            seqs.next().is_none(),
+            "an enode can have at most one sequence variable child"
+        );
+        first
+    }
+
     fn extract(self) -> Program<L> {
         let mut subst = Subst::default();
         for (v, r) in self.v2r {
@@ -321,22 +700,75 @@ impl<L: Language> Compiler<L> {
         Program {
             instructions: self.instructions,
             subst,
+            seqs: self.s2r.into_iter().collect(),
+            predicates: self.predicates,
+        }
+    }
+}
+
+/// Greedily orders the atoms of a multipattern, preferring atoms whose
+/// variable is already bound (so they don't need a scan), then atoms that
+/// share a variable with the atoms before them, then atoms whose root
+/// operator is in the fewest eclasses.
+fn join_order<L: Language>(patterns: &[(Var, PatternAst<L>)], stats: &OpStats<L>) -> Vec<usize> {
+    let mut bound = HashSet::<Var>::default();
+    let mut todo: Vec<usize> = (0..patterns.len()).collect();
+    let mut order = vec![];
+    while !todo.is_empty() {
+        let key = |&i: &usize| {
+            let (var, pattern) = &patterns[i];
+            let shares_var = pattern.as_ref().iter().any(|n| match n {
+                ENodeOrVar::Var(v) => bound.contains(v),
+                ENodeOrVar::ENode(_) => false,
+            });
+            let candidates = match pattern.as_ref().last().unwrap() {
+                ENodeOrVar::ENode(root) => stats.classes(root),
+                ENodeOrVar::Var(_) => stats.number_of_classes(),
+            };
+            (!bound.contains(var), !shares_var, candidates, i)
+        };
+        let best = todo.iter().copied().min_by_key(key).unwrap();
+        todo.retain(|&i| i != best);
+        order.push(best);
+
+        let (var, pattern) = &patterns[best];
+        bound.insert(*var);
+        for n in pattern.as_ref() {
+            if let ENodeOrVar::Var(v) = n {
+                bound.insert(*v);
+            }
         }
     }
+    order
 }
 
 impl<L: Language> Program<L> {
-    pub(crate) fn compile_from_pat(pattern: &PatternAst<L>) -> Self {
-        let mut compiler = Compiler::new();
+    pub(crate) fn compile_from_pat(
+        pattern: &PatternAst<L>,
+        predicates: &[(Var, Symbol)],
+        stats: Option<&OpStats<L>>,
+    ) -> Self {
+        let mut compiler = Compiler::new(stats);
+        compiler.predicates = predicates.to_vec();
         compiler.compile(None, pattern);
         let program = compiler.extract();
         log::debug!("Compiled {:?} to {:?}", pattern.as_ref(), program);
         program
     }
 
-    pub(crate) fn compile_from_multi_pat(patterns: &[(Var, PatternAst<L>)]) -> Self {
-        let mut compiler = Compiler::new();
-        for (var, pattern) in patterns {
+    pub(crate) fn compile_from_multi_pat(
+        patterns: &[(Var, PatternAst<L>)],
+        predicates: &[(Var, Symbol)],
+        stats: Option<&OpStats<L>>,
+    ) -> Self {
+        let mut compiler = Compiler::new(stats);
+        compiler.predicates = predicates.to_vec();
+        let order: Vec<usize> = match stats {
+            Some(stats) => join_order(patterns, stats),
+            None => (0..patterns.len()).collect(),
+        };
+        for i in order {
+            let (var, pattern) = &patterns[i];
             compiler.compile(Some(*var), pattern);
         }
         compiler.extract()
@@ -367,14 +799,8 @@ impl<L: Language> Program<L> {
                 egraph,
                 &self.instructions,
                 &self.subst,
-                &mut |machine, subst| {
-                    let subst_vec = subst
-                        .vec
-                        .iter()
-                        // HACK we are reusing Ids here, this is bad
-                        .map(|(v, reg_id)| (*v, machine.reg(Reg(usize::from(*reg_id) as u32))))
-                        .collect();
-                    matches.push(Subst { vec: subst_vec });
+                &mut |machine, _| {
+                    matches.push(self.make_subst(egraph, machine));
                     limit -= 1;
                     if limit != 0 {
                         Ok(())
@@ -388,4 +814,118 @@ impl<L: Language> Program<L> {
         log::trace!("Ran program, found {:?}", matches);
         matches
     }
+
+    /// Reads this program's variables out of the registers after a match,
+    /// binding the values of annotated variables.
+    fn make_subst<A: Analysis<L>>(&self, egraph: &EGraph<L, A>, machine: &Machine) -> Subst {
+        let vec = self
+            .subst
+            .vec
+            .iter()
+            // HACK we are reusing Ids here, this is bad
+            .map(|(v, reg_id)| (*v, machine.reg(Reg(usize::from(*reg_id) as u32))))
+            .collect();
+        let seqs = self
+            .seqs
+            .iter()
+            .map(|(v, seq)| (*v, machine.seqs[*seq as usize].clone()))
+            .collect();
+        let mut subst = Subst {
+            vec,
+            seqs,
+            values: vec![],
+        };
+        for &(v, predicate) in &self.predicates {
+            let data = &egraph[subst[v]].data;
+            if let Some(value) = egraph.analysis.bind_value(predicate, data) {
+                subst.insert_raw_value(v, value);
+            }
+        }
+        subst
+    }
+}
+
+/// Many [`Program`]s merged into a trie, so programs that start with the
+/// same instructions share the work of running them.
+#[derive(Debug, Clone)]
+pub(crate) struct ProgramTrie<L> {
+    nodes: Vec<TrieNode<L>>,
+    programs: Vec<Program<L>>,
+}
+
+#[derive(Debug, Clone)]
+struct TrieNode<L> {
+    // `None` only for the root
+    instruction: Option<Instruction<L>>,
+    children: Vec<usize>,
+    // the programs that end at this node
+    accepts: Vec<usize>,
+}
+
+impl<L> Default for ProgramTrie<L> {
+    fn default() -> Self {
+        let root = TrieNode {
+            instruction: None,
+            children: vec![],
+            accepts: vec![],
+        };
+        Self {
+            nodes: vec![root],
+            programs: vec![],
+        }
+    }
+}
+
+impl<L: Language> ProgramTrie<L> {
+    /// Adds a program to the trie; programs are numbered in insertion order.
+    pub fn insert(&mut self, program: &Program<L>) {
+        let mut node = 0;
+        for instruction in &program.instructions {
+            let existing = self.nodes[node]
+                .children
+                .iter()
+                .copied()
+                .find(|&c| self.nodes[c].instruction.as_ref() == Some(instruction));
+            node = match existing {
+                Some(child) => child,
+                None => {
+                    let child = self.nodes.len();
+                    self.nodes.push(TrieNode {
+                        instruction: Some(instruction.clone()),
+                        children: vec![],
+                        accepts: vec![],
+                    });
+                    self.nodes[node].children.push(child);
+                    child
+                }
+            };
+        }
+        self.nodes[node].accepts.push(self.programs.len());
+        self.programs.push(program.clone());
+    }
+
+    /// Returns the number of instructions in the trie, which is less than
+    /// the total number of instructions of its programs if they share
+    /// prefixes.
+    pub fn size(&self) -> usize {
+        self.nodes.len() - 1
+    }
+
+    /// Runs every program on `eclass` at once,
+    /// calling `yield_fn` with the program index and [`Subst`] of each match.
+    pub fn run<A>(&self, egraph: &EGraph<L, A>, eclass: Id, mut yield_fn: impl FnMut(usize, Subst))
+    where
+        A: Analysis<L>,
+    {
+        assert!(egraph.clean, "Tried to search a dirty e-graph!");
+
+        let mut machine = Machine::default();
+        machine.reg.push(eclass);
+        machine
+            .run_trie(egraph, self, 0, &mut |machine, program| {
+                yield_fn(program, self.programs[program].make_subst(egraph, machine));
+                Ok(())
+            })
+            .unwrap_or_default();
+    }
 }
@@ -15,9 +15,16 @@ See [`LanguageChildren`] for acceptable types of children `Id`s.
 
 Note that you can always implement [`Language`] yourself by just not using this
 macro.
+With the `derive` feature, `egg` also provides `#[derive(Language, FromOp, Display)]`
+from the `egg-derive` crate, which supports generic languages and attributes
+on variants.
 
-Presently, the macro does not support data variant with children, but that may
-be added later.
+Variants can also use named fields, with the same forms as the tuple
+variants: a single field of children after an operator string, a single
+data field, or a data field followed by a children field.
+A data field followed by a children field can also come after an operator
+string; such variants print the operator and the data joined by a dot,
+like `load.i32`, and parse back from the same form.
 
 # Example
 
@@ -35,8 +42,17 @@ define_language! {
         "-" = Sub([Id; 2]),
         "*" = Mul([Id; 2]),
 
+        // mark operators that are associative and commutative with `#[ac]`,
+        // see `Language::is_ac`
+        #[ac] "max" = Max([Id; 2]),
+
+        // declare the sort of an operator and the sorts of its children
+        // with `#[sort(...)]`, see `Language::sort`
+        #[sort(num <- num, num)] "/" = Div([Id; 2]),
+
         // can also do a variable number of children in a boxed slice
-        // this will only match if the lengths are the same
+        // this will only match if the lengths are the same,
+        // unless the pattern uses a sequence variable like `?args...`
         "list" = List(Box<[Id]>),
 
         // string variants with a single child `Id`
@@ -50,12 +66,49 @@ define_language! {
         // language items are parsed in order, and we want symbol to
         // be a fallback, so we put it last
         Symbol(Symbol),
+        // data variants with children
+        // these are parsed and printed with the data as the operator,
+        // and only match enodes with equal data
+        Cast(Type, [Id; 1]),
+        // data variants with children can also have an operator string,
+        // then they are parsed and printed as the operator and the data
+        // joined by a dot, like `(ext.i64 x)`
+        "ext" = Ext(Type, Id),
+
+        // variants can also have named fields, in the same forms as above
+        "load" = Load { ty: Type, addr: Id },
+        "store" = Store { ty: Type, args: [Id; 2] },
+        "addr" = Addr { base: Id },
+
         // This is the ultimate fallback, it will parse any operator (as a string)
         // and any number of children.
         // Note that if there were 0 children, the previous branch would have succeeded
         Other(Symbol, Vec<Id>),
     }
 }
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
+enum Type { I32, I64 }
+# impl std::str::FromStr for Type {
+#     type Err = ();
+#     fn from_str(s: &str) -> Result<Self, ()> {
+#         match s { "i32" => Ok(Type::I32), "i64" => Ok(Type::I64), _ => Err(()) }
+#     }
+# }
+# impl std::fmt::Display for Type {
+#     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+#         f.write_str(match self { Type::I32 => "i32", Type::I64 => "i64" })
+#     }
+# }
+
+let expr: RecExpr<SimpleLanguage> = "(i64 (ext.i64 (load.i32 (addr p))))".parse().unwrap();
+assert_eq!(expr.to_string(), "(i64 (ext.i64 (load.i32 (addr p))))");
+let load = &expr.as_ref()[2];
+assert!(matches!(load, SimpleLanguage::Load { ty: Type::I32, .. }));
+assert!(!load.matches(&SimpleLanguage::Load { ty: Type::I64, addr: 0.into() }));
+
+let store: RecExpr<SimpleLanguage> = "(store.i32 (addr p) 1)".parse().unwrap();
+assert!(matches!(store.as_ref()[3], SimpleLanguage::Store { ty: Type::I32, .. }));
 ```
 
 [`Display`]: std::fmt::Display
@@ -63,7 +116,7 @@ define_language! {
 #[macro_export]
 macro_rules! define_language {
     ($(#[$meta:meta])* $vis:vis enum $name:ident $variants:tt) => {
-        $crate::__define_language!($(#[$meta])* $vis enum $name $variants -> {} {} {} {} {} {});
+        $crate::__define_language!($(#[$meta])* $vis enum $name $variants -> {} {} {} {} {} {} {} {} {});
     };
 }
 
@@ -72,7 +125,7 @@ macro_rules! define_language {
 macro_rules! __define_language {
     ($(#[$meta:meta])* $vis:vis enum $name:ident {} ->
      $decl:tt {$($matches:tt)*} $children:tt $children_mut:tt
-     $display:tt {$($from_op:tt)*}
+     {$($with_children:tt)*} {$($is_ac:tt)*} {$($sorts:tt)*} $display:tt {$($from_op:tt)*}
     ) => {
         $(#[$meta])*
         #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
@@ -87,6 +140,39 @@ macro_rules! __define_language {
 
             fn children(&self) -> &[Id] { match self $children }
             fn children_mut(&mut self) -> &mut [Id] { match self $children_mut }
+
+            fn with_children(&self, children: ::std::vec::Vec<$crate::Id>) -> ::std::option::Option<Self> {
+                match (self, children) {
+                    $($with_children)*
+                    _ => None,
+                }
+            }
+
+            #[allow(unreachable_patterns)]
+            fn is_ac(&self) -> bool {
+                match self {
+                    $($is_ac)*
+                    _ => false,
+                }
+            }
+
+            #[allow(unreachable_patterns)]
+            fn sort(&self) -> ::std::option::Option<$crate::Symbol> {
+                let sorts: ::std::option::Option<(&str, &[&str])> = match self {
+                    $($sorts)*
+                    _ => None,
+                };
+                sorts.map(|(sort, _)| sort.into())
+            }
+
+            #[allow(unreachable_patterns)]
+            fn child_sort(&self, i: usize) -> ::std::option::Option<$crate::Symbol> {
+                let sorts: ::std::option::Option<(&str, &[&str])> = match self {
+                    $($sorts)*
+                    _ => None,
+                };
+                sorts.and_then(|(_, children)| children.get(i)).map(|&sort| sort.into())
+            }
         }
 
         impl ::std::fmt::Display for $name {
@@ -109,13 +195,161 @@ macro_rules! __define_language {
         }
     };
 
+    ($(#[$meta:meta])* $vis:vis enum $name:ident
+     {
+         #[ac] #[sort($($sort:tt)*)]
+         $($variants:tt)*
+     } ->
+     $decl:tt $matches:tt $children:tt $children_mut:tt
+     $with_children:tt $is_ac:tt $sorts:tt $display:tt $from_op:tt
+    ) => {
+        $crate::__define_language!(
+            $(#[$meta])* $vis enum $name
+            { #[sort($($sort)*)] #[ac] $($variants)* } ->
+            $decl $matches $children $children_mut
+            $with_children $is_ac $sorts $display $from_op
+        );
+    };
+
+    ($(#[$meta:meta])* $vis:vis enum $name:ident
+     {
+         #[sort($res:ident $(<- $($child:ident),*)?)] $(#[$ac:ident])? $string:literal = $variant:ident
+         $($variants:tt)*
+     } ->
+     $decl:tt $matches:tt $children:tt $children_mut:tt
+     $with_children:tt $is_ac:tt { $($sorts:tt)* } $display:tt $from_op:tt
+    ) => {
+        $crate::__define_language!(
+            $(#[$meta])* $vis enum $name
+            { $(#[$ac])? $string = $variant $($variants)* } ->
+            $decl $matches $children $children_mut
+            $with_children $is_ac
+            { $($sorts)* $name::$variant { .. } => Some((stringify!($res), &[$($(stringify!($child)),*)?])), }
+            $display $from_op
+        );
+    };
+
+    ($(#[$meta:meta])* $vis:vis enum $name:ident
+     {
+         #[sort($res:ident $(<- $($child:ident),*)?)] $(#[$ac:ident])? $variant:ident
+         $($variants:tt)*
+     } ->
+     $decl:tt $matches:tt $children:tt $children_mut:tt
+     $with_children:tt $is_ac:tt { $($sorts:tt)* } $display:tt $from_op:tt
+    ) => {
+        $crate::__define_language!(
+            $(#[$meta])* $vis enum $name
+            { $(#[$ac])? $variant $($variants)* } ->
+            $decl $matches $children $children_mut
+            $with_children $is_ac
+            { $($sorts)* $name::$variant { .. } => Some((stringify!($res), &[$($(stringify!($child)),*)?])), }
+            $display $from_op
+        );
+    };
+
+    ($(#[$meta:meta])* $vis:vis enum $name:ident
+     {
+         #[ac] $string:literal = $variant:ident ($ids:ty),
+         $($variants:tt)*
+     } ->
+     $decl:tt $matches:tt $children:tt $children_mut:tt
+     $with_children:tt { $($is_ac:tt)* } $sorts:tt $display:tt $from_op:tt
+    ) => {
+        $crate::__define_language!(
+            $(#[$meta])* $vis enum $name
+            { $string = $variant($ids), $($variants)* } ->
+            $decl $matches $children $children_mut
+            $with_children { $($is_ac)* $name::$variant(..) => true, } $sorts $display $from_op
+        );
+    };
+
+    ($(#[$meta:meta])* $vis:vis enum $name:ident
+     {
+         #[ac] $string:literal = $variant:ident ($data:ty, $ids:ty),
+         $($variants:tt)*
+     } ->
+     $decl:tt $matches:tt $children:tt $children_mut:tt
+     $with_children:tt { $($is_ac:tt)* } $sorts:tt $display:tt $from_op:tt
+    ) => {
+        $crate::__define_language!(
+            $(#[$meta])* $vis enum $name
+            { $string = $variant($data, $ids), $($variants)* } ->
+            $decl $matches $children $children_mut
+            $with_children { $($is_ac)* $name::$variant(..) => true, } $sorts $display $from_op
+        );
+    };
+
+    ($(#[$meta:meta])* $vis:vis enum $name:ident
+     {
+         #[ac] $variant:ident ($data:ty, $ids:ty),
+         $($variants:tt)*
+     } ->
+     $decl:tt $matches:tt $children:tt $children_mut:tt
+     $with_children:tt { $($is_ac:tt)* } $sorts:tt $display:tt $from_op:tt
+    ) => {
+        $crate::__define_language!(
+            $(#[$meta])* $vis enum $name
+            { $variant($data, $ids), $($variants)* } ->
+            $decl $matches $children $children_mut
+            $with_children { $($is_ac)* $name::$variant(..) => true, } $sorts $display $from_op
+        );
+    };
+
+    ($(#[$meta:meta])* $vis:vis enum $name:ident
+     {
+         #[ac] $string:literal = $variant:ident { $ids_field:ident : $ids:ty $(,)? },
+         $($variants:tt)*
+     } ->
+     $decl:tt $matches:tt $children:tt $children_mut:tt
+     $with_children:tt { $($is_ac:tt)* } $sorts:tt $display:tt $from_op:tt
+    ) => {
+        $crate::__define_language!(
+            $(#[$meta])* $vis enum $name
+            { $string = $variant { $ids_field: $ids }, $($variants)* } ->
+            $decl $matches $children $children_mut
+            $with_children { $($is_ac)* $name::$variant { .. } => true, } $sorts $display $from_op
+        );
+    };
+
+    ($(#[$meta:meta])* $vis:vis enum $name:ident
+     {
+         #[ac] $string:literal = $variant:ident { $data_field:ident : $data:ty, $ids_field:ident : $ids:ty $(,)? },
+         $($variants:tt)*
+     } ->
+     $decl:tt $matches:tt $children:tt $children_mut:tt
+     $with_children:tt { $($is_ac:tt)* } $sorts:tt $display:tt $from_op:tt
+    ) => {
+        $crate::__define_language!(
+            $(#[$meta])* $vis enum $name
+            { $string = $variant { $data_field: $data, $ids_field: $ids }, $($variants)* } ->
+            $decl $matches $children $children_mut
+            $with_children { $($is_ac)* $name::$variant { .. } => true, } $sorts $display $from_op
+        );
+    };
+
+    ($(#[$meta:meta])* $vis:vis enum $name:ident
+     {
+         #[ac] $variant:ident { $data_field:ident : $data:ty, $ids_field:ident : $ids:ty $(,)? },
+         $($variants:tt)*
+     } ->
+     $decl:tt $matches:tt $children:tt $children_mut:tt
+     $with_children:tt { $($is_ac:tt)* } $sorts:tt $display:tt $from_op:tt
+    ) => {
+        $crate::__define_language!(
+            $(#[$meta])* $vis enum $name
+            { $variant { $data_field: $data, $ids_field: $ids }, $($variants)* } ->
+            $decl $matches $children $children_mut
+            $with_children { $($is_ac)* $name::$variant { .. } => true, } $sorts $display $from_op
+        );
+    };
+
     ($(#[$meta:meta])* $vis:vis enum $name:ident
      {
          $string:literal = $variant:ident,
          $($variants:tt)*
      } ->
      { $($decl:tt)* } { $($matches:tt)* } { $($children:tt)* } { $($children_mut:tt)* }
-     { $($display:tt)* } { $($from_op:tt)* }
+     { $($with_children:tt)* } { $($is_ac:tt)* } $sorts:tt { $($display:tt)* } { $($from_op:tt)* }
     ) => {
         $crate::__define_language!(
             $(#[$meta])* $vis enum $name
@@ -124,6 +358,9 @@ macro_rules! __define_language {
             { $($matches)*       ($name::$variant, $name::$variant) => true, }
             { $($children)*      $name::$variant => &[], }
             { $($children_mut)*  $name::$variant => &mut [], }
+            { $($with_children)* ($name::$variant, children) if children.is_empty() => Some($name::$variant), }
+            { $($is_ac)* }
+            $sorts
             { $($display)*       ($name::$variant, f) => f.write_str($string), }
             { $($from_op)*       ($string, children) if children.is_empty() => Ok($name::$variant), }
         );
@@ -135,7 +372,7 @@ macro_rules! __define_language {
          $($variants:tt)*
      } ->
      { $($decl:tt)* } { $($matches:tt)* } { $($children:tt)* } { $($children_mut:tt)* }
-     { $($display:tt)* } { $($from_op:tt)* }
+     { $($with_children:tt)* } { $($is_ac:tt)* } $sorts:tt { $($display:tt)* } { $($from_op:tt)* }
     ) => {
         $crate::__define_language!(
             $(#[$meta])* $vis enum $name
@@ -144,6 +381,12 @@ macro_rules! __define_language {
             { $($matches)*       ($name::$variant(l), $name::$variant(r)) => $crate::LanguageChildren::len(l) == $crate::LanguageChildren::len(r), }
             { $($children)*      $name::$variant(ids) => $crate::LanguageChildren::as_slice(ids), }
             { $($children_mut)*  $name::$variant(ids) => $crate::LanguageChildren::as_mut_slice(ids), }
+            { $($with_children)* ($name::$variant(..), children) if <$ids as $crate::LanguageChildren>::can_be_length(children.len()) => {
+                  Some($name::$variant(<$ids as $crate::LanguageChildren>::from_vec(children)))
+              },
+            }
+            { $($is_ac)* }
+            $sorts
             { $($display)*       ($name::$variant(..), f) => f.write_str($string), }
             { $($from_op)*       (op, children) if op == $string && <$ids as $crate::LanguageChildren>::can_be_length(children.len()) => {
                   let children = <$ids as $crate::LanguageChildren>::from_vec(children);
@@ -153,13 +396,45 @@ macro_rules! __define_language {
         );
     };
 
+    ($(#[$meta:meta])* $vis:vis enum $name:ident
+     {
+         $string:literal = $variant:ident ($data:ty, $ids:ty),
+         $($variants:tt)*
+     } ->
+     { $($decl:tt)* } { $($matches:tt)* } { $($children:tt)* } { $($children_mut:tt)* }
+     { $($with_children:tt)* } { $($is_ac:tt)* } $sorts:tt { $($display:tt)* } { $($from_op:tt)* }
+    ) => {
+        $crate::__define_language!(
+            $(#[$meta])* $vis enum $name
+            { $($variants)* } ->
+            { $($decl)*          $variant($data, $ids), }
+            { $($matches)*       ($name::$variant(d1, l), $name::$variant(d2, r)) => d1 == d2 && $crate::LanguageChildren::len(l) == $crate::LanguageChildren::len(r), }
+            { $($children)*      $name::$variant(_, ids) => $crate::LanguageChildren::as_slice(ids), }
+            { $($children_mut)*  $name::$variant(_, ids) => $crate::LanguageChildren::as_mut_slice(ids), }
+            { $($with_children)* ($name::$variant(data, _), children) if <$ids as $crate::LanguageChildren>::can_be_length(children.len()) => {
+                  Some($name::$variant(data.clone(), <$ids as $crate::LanguageChildren>::from_vec(children)))
+              },
+            }
+            { $($is_ac)* }
+            $sorts
+            { $($display)*       ($name::$variant(data, _), f) => ::std::write!(f, "{}.{}", $string, data), }
+            { $($from_op)*       (op, children) if op.strip_prefix($string).and_then(|rest| rest.strip_prefix('.')).map(|data| data.parse::<$data>().is_ok()) == Some(true)
+                                     && <$ids as $crate::LanguageChildren>::can_be_length(children.len()) => {
+                  let data = op[$string.len() + 1..].parse::<$data>().unwrap();
+                  let children = <$ids as $crate::LanguageChildren>::from_vec(children);
+                  Ok($name::$variant(data, children))
+              },
+            }
+        );
+    };
+
     ($(#[$meta:meta])* $vis:vis enum $name:ident
      {
          $variant:ident ($data:ty),
          $($variants:tt)*
      } ->
      { $($decl:tt)* } { $($matches:tt)* } { $($children:tt)* } { $($children_mut:tt)* }
-     { $($display:tt)* } { $($from_op:tt)* }
+     { $($with_children:tt)* } { $($is_ac:tt)* } $sorts:tt { $($display:tt)* } { $($from_op:tt)* }
     ) => {
         $crate::__define_language!(
             $(#[$meta])* $vis enum $name
@@ -168,6 +443,9 @@ macro_rules! __define_language {
             { $($matches)*       ($name::$variant(data1), $name::$variant(data2)) => data1 == data2, }
             { $($children)*      $name::$variant(_data) => &[], }
             { $($children_mut)*  $name::$variant(_data) => &mut [], }
+            { $($with_children)* ($name::$variant(data), children) if children.is_empty() => Some($name::$variant(data.clone())), }
+            { $($is_ac)* }
+            $sorts
             { $($display)*       ($name::$variant(data), f) => ::std::fmt::Display::fmt(data, f), }
             { $($from_op)*       (op, children) if op.parse::<$data>().is_ok() && children.is_empty() => Ok($name::$variant(op.parse().unwrap())), }
         );
@@ -179,7 +457,7 @@ macro_rules! __define_language {
          $($variants:tt)*
      } ->
      { $($decl:tt)* } { $($matches:tt)* } { $($children:tt)* } { $($children_mut:tt)* }
-     { $($display:tt)* } { $($from_op:tt)* }
+     { $($with_children:tt)* } { $($is_ac:tt)* } $sorts:tt { $($display:tt)* } { $($from_op:tt)* }
     ) => {
         $crate::__define_language!(
             $(#[$meta])* $vis enum $name
@@ -188,6 +466,12 @@ macro_rules! __define_language {
             { $($matches)*       ($name::$variant(d1, l), $name::$variant(d2, r)) => d1 == d2 && $crate::LanguageChildren::len(l) == $crate::LanguageChildren::len(r), }
             { $($children)*      $name::$variant(_, ids) => $crate::LanguageChildren::as_slice(ids), }
             { $($children_mut)*  $name::$variant(_, ids) => $crate::LanguageChildren::as_mut_slice(ids), }
+            { $($with_children)* ($name::$variant(data, _), children) if <$ids as $crate::LanguageChildren>::can_be_length(children.len()) => {
+                  Some($name::$variant(data.clone(), <$ids as $crate::LanguageChildren>::from_vec(children)))
+              },
+            }
+            { $($is_ac)* }
+            $sorts
             { $($display)*       ($name::$variant(data, _), f) => ::std::fmt::Display::fmt(data, f), }
             { $($from_op)*       (op, children) if op.parse::<$data>().is_ok() && <$ids as $crate::LanguageChildren>::can_be_length(children.len()) => {
                   let data = op.parse::<$data>().unwrap();
@@ -197,6 +481,122 @@ macro_rules! __define_language {
             }
         );
     };
+
+    ($(#[$meta:meta])* $vis:vis enum $name:ident
+     {
+         $string:literal = $variant:ident { $ids_field:ident : $ids:ty $(,)? },
+         $($variants:tt)*
+     } ->
+     { $($decl:tt)* } { $($matches:tt)* } { $($children:tt)* } { $($children_mut:tt)* }
+     { $($with_children:tt)* } { $($is_ac:tt)* } $sorts:tt { $($display:tt)* } { $($from_op:tt)* }
+    ) => {
+        $crate::__define_language!(
+            $(#[$meta])* $vis enum $name
+            { $($variants)* } ->
+            { $($decl)*          $variant { $ids_field: $ids }, }
+            { $($matches)*       ($name::$variant { $ids_field: l }, $name::$variant { $ids_field: r }) => $crate::LanguageChildren::len(l) == $crate::LanguageChildren::len(r), }
+            { $($children)*      $name::$variant { $ids_field: ids } => $crate::LanguageChildren::as_slice(ids), }
+            { $($children_mut)*  $name::$variant { $ids_field: ids } => $crate::LanguageChildren::as_mut_slice(ids), }
+            { $($with_children)* ($name::$variant { .. }, children) if <$ids as $crate::LanguageChildren>::can_be_length(children.len()) => {
+                  Some($name::$variant { $ids_field: <$ids as $crate::LanguageChildren>::from_vec(children) })
+              },
+            }
+            { $($is_ac)* }
+            $sorts
+            { $($display)*       ($name::$variant { .. }, f) => f.write_str($string), }
+            { $($from_op)*       (op, children) if op == $string && <$ids as $crate::LanguageChildren>::can_be_length(children.len()) => {
+                  let children = <$ids as $crate::LanguageChildren>::from_vec(children);
+                  Ok($name::$variant { $ids_field: children })
+              },
+            }
+        );
+    };
+
+    ($(#[$meta:meta])* $vis:vis enum $name:ident
+     {
+         $string:literal = $variant:ident { $data_field:ident : $data:ty, $ids_field:ident : $ids:ty $(,)? },
+         $($variants:tt)*
+     } ->
+     { $($decl:tt)* } { $($matches:tt)* } { $($children:tt)* } { $($children_mut:tt)* }
+     { $($with_children:tt)* } { $($is_ac:tt)* } $sorts:tt { $($display:tt)* } { $($from_op:tt)* }
+    ) => {
+        $crate::__define_language!(
+            $(#[$meta])* $vis enum $name
+            { $($variants)* } ->
+            { $($decl)*          $variant { $data_field: $data, $ids_field: $ids }, }
+            { $($matches)*       ($name::$variant { $data_field: d1, $ids_field: l }, $name::$variant { $data_field: d2, $ids_field: r }) => d1 == d2 && $crate::LanguageChildren::len(l) == $crate::LanguageChildren::len(r), }
+            { $($children)*      $name::$variant { $ids_field: ids, .. } => $crate::LanguageChildren::as_slice(ids), }
+            { $($children_mut)*  $name::$variant { $ids_field: ids, .. } => $crate::LanguageChildren::as_mut_slice(ids), }
+            { $($with_children)* ($name::$variant { $data_field: data, .. }, children) if <$ids as $crate::LanguageChildren>::can_be_length(children.len()) => {
+                  Some($name::$variant { $data_field: data.clone(), $ids_field: <$ids as $crate::LanguageChildren>::from_vec(children) })
+              },
+            }
+            { $($is_ac)* }
+            $sorts
+            { $($display)*       ($name::$variant { $data_field: data, .. }, f) => ::std::write!(f, "{}.{}", $string, data), }
+            { $($from_op)*       (op, children) if op.strip_prefix($string).and_then(|rest| rest.strip_prefix('.')).map(|data| data.parse::<$data>().is_ok()) == Some(true)
+                                     && <$ids as $crate::LanguageChildren>::can_be_length(children.len()) => {
+                  let data = op[$string.len() + 1..].parse::<$data>().unwrap();
+                  let children = <$ids as $crate::LanguageChildren>::from_vec(children);
+                  Ok($name::$variant { $data_field: data, $ids_field: children })
+              },
+            }
+        );
+    };
+
+    ($(#[$meta:meta])* $vis:vis enum $name:ident
+     {
+         $variant:ident { $data_field:ident : $data:ty $(,)? },
+         $($variants:tt)*
+     } ->
+     { $($decl:tt)* } { $($matches:tt)* } { $($children:tt)* } { $($children_mut:tt)* }
+     { $($with_children:tt)* } { $($is_ac:tt)* } $sorts:tt { $($display:tt)* } { $($from_op:tt)* }
+    ) => {
+        $crate::__define_language!(
+            $(#[$meta])* $vis enum $name
+            { $($variants)* } ->
+            { $($decl)*          $variant { $data_field: $data }, }
+            { $($matches)*       ($name::$variant { $data_field: data1 }, $name::$variant { $data_field: data2 }) => data1 == data2, }
+            { $($children)*      $name::$variant { .. } => &[], }
+            { $($children_mut)*  $name::$variant { .. } => &mut [], }
+            { $($with_children)* ($name::$variant { $data_field: data }, children) if children.is_empty() => Some($name::$variant { $data_field: data.clone() }), }
+            { $($is_ac)* }
+            $sorts
+            { $($display)*       ($name::$variant { $data_field: data }, f) => ::std::fmt::Display::fmt(data, f), }
+            { $($from_op)*       (op, children) if op.parse::<$data>().is_ok() && children.is_empty() => Ok($name::$variant { $data_field: op.parse().unwrap() }), }
+        );
+    };
+
+    ($(#[$meta:meta])* $vis:vis enum $name:ident
+     {
+         $variant:ident { $data_field:ident : $data:ty, $ids_field:ident : $ids:ty $(,)? },
+         $($variants:tt)*
+     } ->
+     { $($decl:tt)* } { $($matches:tt)* } { $($children:tt)* } { $($children_mut:tt)* }
+     { $($with_children:tt)* } { $($is_ac:tt)* } $sorts:tt { $($display:tt)* } { $($from_op:tt)* }
+    ) => {
+        $crate::__define_language!(
+            $(#[$meta])* $vis enum $name
+            { $($variants)* } ->
+            { $($decl)*          $variant { $data_field: $data, $ids_field: $ids }, }
+            { $($matches)*       ($name::$variant { $data_field: d1, $ids_field: l }, $name::$variant { $data_field: d2, $ids_field: r }) => d1 == d2 && $crate::LanguageChildren::len(l) == $crate::LanguageChildren::len(r), }
+            { $($children)*      $name::$variant { $ids_field: ids, .. } => $crate::LanguageChildren::as_slice(ids), }
+            { $($children_mut)*  $name::$variant { $ids_field: ids, .. } => $crate::LanguageChildren::as_mut_slice(ids), }
+            { $($with_children)* ($name::$variant { $data_field: data, .. }, children) if <$ids as $crate::LanguageChildren>::can_be_length(children.len()) => {
+                  Some($name::$variant { $data_field: data.clone(), $ids_field: <$ids as $crate::LanguageChildren>::from_vec(children) })
+              },
+            }
+            { $($is_ac)* }
+            $sorts
+            { $($display)*       ($name::$variant { $data_field: data, .. }, f) => ::std::fmt::Display::fmt(data, f), }
+            { $($from_op)*       (op, children) if op.parse::<$data>().is_ok() && <$ids as $crate::LanguageChildren>::can_be_length(children.len()) => {
+                  let data = op.parse::<$data>().unwrap();
+                  let children = <$ids as $crate::LanguageChildren>::from_vec(children);
+                  Ok($name::$variant { $data_field: data, $ids_field: children })
+              },
+            }
+        );
+    };
 }
 
 /** A macro to easily make [`Rewrite`]s.
@@ -365,6 +765,45 @@ mod tests {
         assert_eq!(add, Simple::Add([1.into(), 1.into()]));
     }
 
+    define_language! {
+        enum Typed {
+            "load" = Load { addr: Id },
+            #[ac] "max" = Max { args: [Id; 2] },
+            Cast(Symbol, [Id; 1]),
+            #[ac] Op { op: Symbol, args: [Id; 2] },
+            Const { value: i32 },
+        }
+    }
+
+    #[test]
+    fn named_fields() {
+        let expr: RecExpr<Typed> = "(load (i32 (max 1 (add 2 3))))".parse().unwrap();
+        assert_eq!(expr.to_string(), "(load (i32 (max 1 (add 2 3))))");
+        let nodes = expr.as_ref();
+        assert_eq!(nodes[0], Typed::Const { value: 1 });
+        assert_eq!(
+            nodes[3],
+            Typed::Op {
+                op: "add".into(),
+                args: [1.into(), 2.into()]
+            }
+        );
+        assert!(nodes[3].is_ac() && nodes[4].is_ac());
+        assert!(matches!(nodes[5], Typed::Cast(..)));
+        assert_eq!(nodes[6].children(), &[5.into()]);
+
+        // the data is part of the operator
+        let sub = Typed::Op {
+            op: "sub".into(),
+            args: [1.into(), 2.into()],
+        };
+        assert!(!nodes[3].matches(&sub));
+        assert_eq!(sub.to_string(), "sub");
+        let moved = sub.with_children(vec![3.into(), 4.into()]).unwrap();
+        assert!(moved.matches(&sub));
+        assert_eq!(moved.children(), &[3.into(), 4.into()]);
+    }
+
     #[test]
     fn some_rewrites() {
         let mut rws: Vec<Rewrite<Simple, ()>> = vec![
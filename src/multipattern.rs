@@ -24,6 +24,7 @@ use crate::*;
 #[derive(Debug, PartialEq, Clone)]
 pub struct MultiPattern<L> {
     asts: Vec<(Var, PatternAst<L>)>,
+    var_predicates: Vec<(Var, Symbol)>,
     program: machine::Program<L>,
 }
 
@@ -51,9 +52,39 @@ impl<L: Language> MultiPattern<L> {
     ///
     /// assert_eq!(multipattern.n_matches(&egraph), 2);
     /// ```
+    ///
+    /// Like in a [`Pattern`], annotated variables like `?x:const` are split
+    /// into the variable `?x` and the predicate `const`, which is checked
+    /// while matching.
     pub fn new(asts: Vec<(Var, PatternAst<L>)>) -> Self {
-        let program = machine::Program::compile_from_multi_pat(&asts);
-        Self { asts, program }
+        let mut var_predicates = vec![];
+        let asts: Vec<(Var, PatternAst<L>)> = asts
+            .iter()
+            .map(|(v, ast)| {
+                let v = pattern::split_var_predicate(*v, &mut var_predicates);
+                (v, pattern::split_predicates(ast, &mut var_predicates))
+            })
+            .collect();
+        let program = machine::Program::compile_from_multi_pat(&asts, &var_predicates, None);
+        Self {
+            asts,
+            var_predicates,
+            program,
+        }
+    }
+
+    /// Returns the predicates of the annotated variables in this multipattern,
+    /// like `(?x, const)` for `?x:const`.
+    pub fn var_predicates(&self) -> &[(Var, Symbol)] {
+        &self.var_predicates
+    }
+
+    /// Recompiles this multipattern, ordering its atoms and joins with the
+    /// given statistics of the egraph it will search.
+    /// See [`OpStats`].
+    pub fn recompile(&mut self, stats: &OpStats<L>) {
+        self.program =
+            machine::Program::compile_from_multi_pat(&self.asts, &self.var_predicates, Some(stats));
     }
 }
 
@@ -157,6 +188,9 @@ impl<L: Language, A: Analysis<L>> Applier<L, A> for MultiPattern<L> {
         // the ids returned are kinda garbage
         let mut added = vec![];
         for mat in matches {
+            if egraph.is_cancelled() {
+                break;
+            }
             for subst in &mat.substs {
                 let mut subst = subst.clone();
                 let mut id_buf = vec![];
@@ -291,4 +325,83 @@ mod tests {
         assert_ne!(runner.egraph.find(y1), runner.egraph.find(z1));
         assert_ne!(runner.egraph.find(x1), runner.egraph.find(z1));
     }
+
+    #[test]
+    fn recompile_with_stats() {
+        crate::init_logger();
+        let mut egraph = EGraph::default();
+        for i in 0..10 {
+            egraph.add_string(&format!("(edge {} {})", i, i + 1));
+        }
+        egraph.add_string("(start 3)");
+        egraph.rebuild();
+
+        let mut pat: MultiPattern<S> = "?e = (edge ?a ?b), ?s = (start ?a)".parse().unwrap();
+        let before = pat.clone();
+        pat.recompile(&OpStats::new(&egraph));
+        // the rare `start` atom is matched first now
+        assert_ne!(pat.program, before.program);
+
+        let substs = |pat: &MultiPattern<S>| {
+            let mut substs: Vec<Vec<(Var, Id)>> = pat
+                .search(&egraph)
+                .into_iter()
+                .flat_map(|m| m.substs)
+                .map(|s| {
+                    // the variables are bound in a different order
+                    let mut pairs = s.vec.to_vec();
+                    pairs.sort();
+                    pairs
+                })
+                .collect();
+            substs.sort();
+            substs
+        };
+        assert_eq!(substs(&pat).len(), 1);
+        assert_eq!(substs(&pat), substs(&before));
+    }
+
+    #[derive(Default)]
+    struct Leaves;
+
+    // `leaf` holds in the eclasses that contain a leaf
+    impl Analysis<S> for Leaves {
+        type Data = bool;
+        fn make(_egraph: &crate::EGraph<S, Self>, enode: &S) -> bool {
+            enode.is_leaf()
+        }
+        fn merge(&mut self, a: &mut bool, b: bool) -> DidMerge {
+            let merged = *a || b;
+            let did = DidMerge(merged != *a, merged != b);
+            *a = merged;
+            did
+        }
+        fn check_predicate(&self, predicate: Symbol, data: &bool) -> bool {
+            assert_eq!(predicate.as_str(), "leaf");
+            *data
+        }
+    }
+
+    #[test]
+    fn annotated_vars() {
+        crate::init_logger();
+        let mut egraph = crate::EGraph::<S, Leaves>::default();
+        let fx = egraph.add_expr(&"(f x)".parse().unwrap());
+        egraph.add_expr(&"(f (g x))".parse().unwrap());
+        egraph.rebuild();
+
+        let pat: MultiPattern<S> = "?r = (f ?x:leaf)".parse().unwrap();
+        assert_eq!(
+            pat.var_predicates(),
+            &[("?x".parse().unwrap(), "leaf".into())]
+        );
+        let matches = pat.search(&egraph);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].eclass, fx);
+        assert_eq!(Searcher::<S, Leaves>::vars(&pat).len(), 2);
+
+        // the variable bound to an atom can be annotated too
+        let pat: MultiPattern<S> = "?r:leaf = (f ?x)".parse().unwrap();
+        assert_eq!(pat.n_matches(&egraph), 0);
+    }
 }
@@ -60,12 +60,102 @@ use crate::*;
 /// assert_eq!(matched_eclasses, vec![a11, a22]);
 /// ```
 ///
+/// Pattern variables can be annotated with a predicate, like `?x:const`.
+/// The pattern only matches where the eclass bound to the variable
+/// satisfies the predicate, as decided by
+/// [`Analysis::check_predicate`] on the eclass's data.
+/// The predicate is checked while matching, as soon as the variable is bound.
+/// An annotation applies to every occurrence of the variable, and the
+/// annotation is not part of the variable itself, so `?x` can be used
+/// on the right-hand side of a rewrite.
+///
+/// ```
+/// use egg::*;
+/// #[derive(Default)]
+/// struct Consts;
+/// impl Analysis<SymbolLang> for Consts {
+///     type Data = Option<i32>;
+///     fn make(_egraph: &EGraph<SymbolLang, Self>, enode: &SymbolLang) -> Self::Data {
+///         enode.op.as_str().parse().ok()
+///     }
+///     fn merge(&mut self, a: &mut Self::Data, b: Self::Data) -> DidMerge {
+///         merge_option(a, b, |_, _| DidMerge(false, false))
+///     }
+///     fn check_predicate(&self, predicate: Symbol, data: &Self::Data) -> bool {
+///         match predicate.as_str() {
+///             "const" => data.is_some(),
+///             _ => panic!("unknown predicate {}", predicate),
+///         }
+///     }
+/// }
+///
+/// let mut egraph = EGraph::<SymbolLang, Consts>::default();
+/// egraph.add_expr(&"(+ 1 x)".parse().unwrap());
+/// let both = egraph.add_expr(&"(+ 1 2)".parse().unwrap());
+/// egraph.rebuild();
+///
+/// let pat: Pattern<SymbolLang> = "(+ ?a:const ?b:const)".parse().unwrap();
+/// assert_eq!(pat.vars().len(), 2);
+/// assert_eq!(pat.to_string(), "(+ ?a:const ?b:const)");
+/// let matches = pat.search(&egraph);
+/// assert_eq!(matches.len(), 1);
+/// assert_eq!(matches[0].eclass, both);
+/// ```
+///
+/// An annotated variable can also bind a [`Value`] in the [`Subst`] of each
+/// match, as produced by [`Analysis::bind_value`], so conditions and
+/// appliers can read the constant directly with [`Subst::get_value`].
+/// The other way around, when a pattern is instantiated, a variable that is
+/// only bound to a value (see [`Subst::insert_value`]) is turned into an
+/// enode by [`Analysis::value_node`].
+///
+/// A variable ending in `...`, like `?args...`, is a sequence variable
+/// (see [`Var::is_sequence`]).
+/// It stands for any number of consecutive children, so one pattern can
+/// match every arity of a variable-arity operator, like one with
+/// `Box<[Id]>` children.
+/// A sequence variable must be a child of an enode, and each enode can have
+/// at most one.
+/// Its binding is a list of eclasses, see [`Subst::get_sequence`], and it can
+/// be spliced into a right-hand side pattern.
+/// Instantiating it relies on [`Language::with_children`].
+///
+/// ```
+/// use egg::*;
+/// let mut egraph = EGraph::<SymbolLang, ()>::default();
+/// let call = egraph.add_expr(&"(call f a b c)".parse().unwrap());
+/// egraph.rebuild();
+///
+/// let uncurry: Rewrite<SymbolLang, ()> =
+///     rewrite!("uncurry"; "(call ?f ?args...)" => "(apply ?f (list ?args...))");
+/// let runner = Runner::default().with_egraph(egraph).run(&[uncurry]);
+/// let apply = runner.egraph.lookup_expr(&"(apply f (list a b c))".parse().unwrap());
+/// assert_eq!(apply, Some(runner.egraph.find(call)));
+/// ```
+///
+/// If the [`Language`] declares associative-commutative operators
+/// (see [`Language::is_ac`]), patterns match modulo AC: the children of an
+/// AC enode are matched in any order, and nested binary AC enodes in the
+/// pattern are also matched in every grouping.
+/// Variables still only bind to eclasses, so `(+ ?a ?b)` only binds `?a` to
+/// `(+ x y)` in `(+ x (+ y z))` if `(+ x y)` is in the egraph.
+/// [`MultiPattern`]s only match AC enodes modulo commutativity.
+///
+/// To keep matching tractable, AC matching is incomplete for large patterns:
+/// only the first 720 orders of an AC enode's children are tried, so an AC
+/// enode with more than 6 children may miss matches, and only the first 64
+/// regroupings of a pattern are tried, so a pattern with many nested AC
+/// enodes may miss matches too.
+/// Compiling such a pattern logs a warning.
+///
 /// [`FromStr`]: std::str::FromStr
 #[derive(Debug, PartialEq, Clone)]
 pub struct Pattern<L> {
     /// The actual pattern as a [`RecExpr`]
     pub ast: PatternAst<L>,
+    var_predicates: Vec<(Var, Symbol)>,
     program: machine::Program<L>,
+    pub(crate) ac_programs: Vec<machine::Program<L>>,
 }
 
 /// A [`RecExpr`] that represents a
@@ -100,12 +190,78 @@ impl<L: Language> PatternAst<L> {
     }
 }
 
+/// Splits the annotated variables of a pattern ast like `?x:const` into
+/// `?x` and `const`, adding the predicates to `var_predicates`.
+pub(crate) fn split_predicates<L: Language>(
+    ast: &PatternAst<L>,
+    var_predicates: &mut Vec<(Var, Symbol)>,
+) -> PatternAst<L> {
+    let ast: PatternAst<L> = ast
+        .as_ref()
+        .iter()
+        .map(|n| match n {
+            ENodeOrVar::Var(v) => ENodeOrVar::Var(split_var_predicate(*v, var_predicates)),
+            n => n.clone(),
+        })
+        .collect::<Vec<_>>()
+        .into();
+    ast.compact()
+}
+
+pub(crate) fn split_var_predicate(v: Var, var_predicates: &mut Vec<(Var, Symbol)>) -> Var {
+    let (v, predicate) = v.split_predicate();
+    if let Some(p) = predicate {
+        if !var_predicates.contains(&(v, p)) {
+            var_predicates.push((v, p));
+        }
+    }
+    v
+}
+
 impl<L: Language> Pattern<L> {
     /// Creates a new pattern from the given pattern ast.
+    ///
+    /// Annotated variables like `?x:const` are split into the variable
+    /// `?x` and the predicate `const`.
     pub fn new(ast: PatternAst<L>) -> Self {
-        let ast = ast.compact();
-        let program = machine::Program::compile_from_pat(&ast);
-        Pattern { ast, program }
+        let mut var_predicates = vec![];
+        let ast = split_predicates(&ast, &mut var_predicates);
+        let mut pattern = Pattern {
+            ast,
+            var_predicates,
+            program: Default::default(),
+            ac_programs: vec![],
+        };
+        pattern.compile(None);
+        pattern
+    }
+
+    /// Recompiles this pattern, ordering its joins with the given statistics
+    /// of the egraph it will search.
+    /// See [`OpStats`].
+    pub fn recompile(&mut self, stats: &OpStats<L>) {
+        self.compile(Some(stats))
+    }
+
+    fn compile(&mut self, stats: Option<&OpStats<L>>) {
+        let predicates = &self.var_predicates;
+        self.program = machine::Program::compile_from_pat(&self.ast, predicates, stats);
+        self.ac_programs = ac_variants(&self.ast)
+            .iter()
+            .map(|variant| machine::Program::compile_from_pat(variant, predicates, stats))
+            .collect();
+    }
+
+    /// Returns the predicates of the annotated variables in this pattern,
+    /// like `(?x, const)` for `?x:const`.
+    pub fn var_predicates(&self) -> &[(Var, Symbol)] {
+        &self.var_predicates
+    }
+
+    /// Returns the compiled programs for this pattern, including its
+    /// AC variants.
+    pub(crate) fn programs(&self) -> impl Iterator<Item = &machine::Program<L>> {
+        std::iter::once(&self.program).chain(&self.ac_programs)
     }
 
     /// Returns a list of the [`Var`]s in this pattern.
@@ -236,7 +392,26 @@ impl<L: Language> TryFrom<Pattern<L>> for RecExpr<L> {
 
 impl<L: Language + Display> Display for Pattern<L> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        Display::fmt(&self.ast, f)
+        if self.var_predicates.is_empty() {
+            return Display::fmt(&self.ast, f);
+        }
+        let annotated: PatternAst<L> = self
+            .ast
+            .as_ref()
+            .iter()
+            .map(|n| match n {
+                ENodeOrVar::Var(v) => {
+                    let mut name = v.to_string();
+                    for (_, p) in self.var_predicates.iter().filter(|(var, _)| var == v) {
+                        name = format!("{}:{}", name, p);
+                    }
+                    ENodeOrVar::Var(name.parse().unwrap())
+                }
+                n => n.clone(),
+            })
+            .collect::<Vec<_>>()
+            .into();
+        Display::fmt(&annotated, f)
     }
 }
 
@@ -262,6 +437,10 @@ impl<L: Language, A: Analysis<L>> Searcher<L, A> for Pattern<L> {
         Some(&self.ast)
     }
 
+    fn get_pattern(&self) -> Option<&Pattern<L>> {
+        Some(self)
+    }
+
     fn search_with_limit(&self, egraph: &EGraph<L, A>, limit: usize) -> Vec<SearchMatches<L>> {
         match self.ast.as_ref().last().unwrap() {
             ENodeOrVar::ENode(e) => {
@@ -292,7 +471,17 @@ impl<L: Language, A: Analysis<L>> Searcher<L, A> for Pattern<L> {
         eclass: Id,
         limit: usize,
     ) -> Option<SearchMatches<L>> {
-        let substs = self.program.run_with_limit(egraph, eclass, limit);
+        let mut substs = self.program.run_with_limit(egraph, eclass, limit);
+        if !self.ac_programs.is_empty() {
+            for program in &self.ac_programs {
+                if substs.len() >= limit {
+                    break;
+                }
+                substs.extend(program.run_with_limit(egraph, eclass, limit - substs.len()));
+            }
+            substs.sort();
+            substs.dedup();
+        }
         if substs.is_empty() {
             None
         } else {
@@ -329,6 +518,9 @@ where
         let ast = self.ast.as_ref();
         let mut id_buf = vec![0.into(); ast.len()];
         for mat in matches {
+            if egraph.is_cancelled() {
+                break;
+            }
             let sast = mat.ast.as_ref().map(|cow| cow.as_ref());
             for subst in &mat.substs {
                 let did_something;
@@ -383,6 +575,150 @@ where
     }
 }
 
+/// The most regroupings of AC operators tried by [`ac_variants`].
+const MAX_AC_VARIANTS: usize = 64;
+
+/// Returns the other patterns that are equal to `ast` modulo associativity of
+/// its binary AC operators (see [`Language::is_ac`]), by regrouping the
+/// operands of nested AC enodes in every way.
+/// Commutativity is handled by the machine, so this only regroups.
+fn ac_variants<L: Language>(ast: &PatternAst<L>) -> Vec<PatternAst<L>> {
+    let nodes = ast.as_ref();
+    let has_ac = nodes
+        .iter()
+        .any(|n| matches!(n, ENodeOrVar::ENode(e) if e.is_ac() && e.len() == 2));
+    if !has_ac {
+        return vec![];
+    }
+
+    let root = Id::from(nodes.len() - 1);
+    // enumerate one more than the cap to tell if there are too many
+    let mut variants = regroup(nodes, root, MAX_AC_VARIANTS + 1);
+    if variants.len() > MAX_AC_VARIANTS {
+        warn!(
+            "Only matching the first {} AC variants of {:?}",
+            MAX_AC_VARIANTS, ast
+        );
+        variants.truncate(MAX_AC_VARIANTS);
+    }
+    variants
+        .into_iter()
+        .filter(|v| v.as_slice() != nodes)
+        .map(|v| RecExpr::from(v).compact())
+        .collect()
+}
+
+/// Returns at most `limit` distinct regroupings of the subpattern rooted at
+/// `id`.
+fn regroup<L: Language>(nodes: &[ENodeOrVar<L>], id: Id, limit: usize) -> Vec<Vec<ENodeOrVar<L>>> {
+    let node = match &nodes[usize::from(id)] {
+        ENodeOrVar::Var(v) => return vec![vec![ENodeOrVar::Var(*v)]],
+        ENodeOrVar::ENode(node) => node,
+    };
+
+    let mut variants = if node.is_ac() && node.len() == 2 {
+        let mut operands = vec![];
+        flatten_ac(nodes, node, &mut operands);
+        let operands: Vec<_> = operands.iter().map(|&o| regroup(nodes, o, limit)).collect();
+        let all: Vec<usize> = (0..operands.len()).collect();
+        bracketings(node, &operands, &all, limit)
+    } else {
+        // every combination of the children's regroupings
+        let children: Vec<_> = node
+            .children()
+            .iter()
+            .map(|&c| regroup(nodes, c, limit))
+            .collect();
+        let mut combos: Vec<Vec<&[ENodeOrVar<L>]>> = vec![vec![]];
+        for child_variants in &children {
+            combos = combos
+                .iter()
+                .flat_map(|combo| {
+                    child_variants.iter().map(move |c| {
+                        let mut combo = combo.clone();
+                        combo.push(c);
+                        combo
+                    })
+                })
+                .take(limit)
+                .collect();
+        }
+        combos.iter().map(|combo| build_node(node, combo)).collect()
+    };
+    variants.sort();
+    variants.dedup();
+    variants
+}
+
+/// Collects the operands of nested applications of the binary AC `node`.
+fn flatten_ac<L: Language>(nodes: &[ENodeOrVar<L>], node: &L, operands: &mut Vec<Id>) {
+    for &child in node.children() {
+        match &nodes[usize::from(child)] {
+            ENodeOrVar::ENode(n) if n.matches(node) => flatten_ac(nodes, n, operands),
+            _ => operands.push(child),
+        }
+    }
+}
+
+/// Returns at most `limit` distinct ways to combine the operands in `set`
+/// with the binary AC `node`, up to commutativity.
+fn bracketings<L: Language>(
+    node: &L,
+    operands: &[Vec<Vec<ENodeOrVar<L>>>],
+    set: &[usize],
+    limit: usize,
+) -> Vec<Vec<ENodeOrVar<L>>> {
+    let (first, rest) = set.split_first().unwrap();
+    if rest.is_empty() {
+        return operands[*first].clone();
+    }
+
+    let mut variants = vec![];
+    // split into two nonempty groups, keeping `first` on the left
+    for mask in 1..(1usize << rest.len()) {
+        let mut left = vec![*first];
+        let mut right = vec![];
+        for (k, &o) in rest.iter().enumerate() {
+            if mask & (1 << k) != 0 {
+                right.push(o)
+            } else {
+                left.push(o)
+            }
+        }
+        let lefts = bracketings(node, operands, &left, limit);
+        let rights = bracketings(node, operands, &right, limit);
+        for l in &lefts {
+            for r in &rights {
+                let variant = build_node(node, &[l, r]);
+                // equal operands, like in `(+ ?a ?a)`, give duplicates
+                if !variants.contains(&variant) {
+                    variants.push(variant);
+                    if variants.len() == limit {
+                        return variants;
+                    }
+                }
+            }
+        }
+    }
+    variants
+}
+
+/// Builds the pattern nodes for `node` applied to the given subpatterns.
+fn build_node<L: Language>(node: &L, children: &[&[ENodeOrVar<L>]]) -> Vec<ENodeOrVar<L>> {
+    let mut nodes = vec![];
+    let mut ids = vec![];
+    for child in children {
+        let offset = nodes.len();
+        let shift = |id: Id| Id::from(usize::from(id) + offset);
+        nodes.extend(child.iter().map(|n| n.clone().map_children(shift)));
+        ids.push(Id::from(nodes.len() - 1));
+    }
+    let mut ids = ids.into_iter();
+    let node = node.clone().map_children(|_| ids.next().unwrap());
+    nodes.push(ENodeOrVar::ENode(node));
+    nodes
+}
+
 pub(crate) fn apply_pat<L: Language, A: Analysis<L>>(
     ids: &mut [Id],
     pat: &[ENodeOrVar<L>],
@@ -394,9 +730,18 @@ pub(crate) fn apply_pat<L: Language, A: Analysis<L>>(
 
     for (i, pat_node) in pat.iter().enumerate() {
         let id = match pat_node {
-            ENodeOrVar::Var(w) => subst[*w],
+            // sequence variables are spliced into their parent instead
+            ENodeOrVar::Var(w) if w.is_sequence() => Id::from(0),
+            ENodeOrVar::Var(w) => match subst.get(*w) {
+                Some(id) => *id,
+                None => {
+                    let n = value_node(egraph, *w, subst);
+                    trace!("adding: {:?}", n);
+                    egraph.add(n)
+                }
+            },
             ENodeOrVar::ENode(e) => {
-                let n = e.clone().map_children(|child| ids[usize::from(child)]);
+                let n = instantiate_node(e, pat, ids, subst);
                 trace!("adding: {:?}", n);
                 egraph.add(n)
             }
@@ -407,6 +752,51 @@ pub(crate) fn apply_pat<L: Language, A: Analysis<L>>(
     *ids.last().unwrap()
 }
 
+/// Makes the enode for a variable that is only bound to a [`Value`].
+pub(crate) fn value_node<L: Language, A: Analysis<L>>(
+    egraph: &EGraph<L, A>,
+    var: Var,
+    subst: &Subst,
+) -> L {
+    let value = match subst.get_raw_value(var) {
+        Some(value) => value,
+        None => panic!("Var '{}' not found in {:?}", var, subst),
+    };
+    match egraph.analysis.value_node(value) {
+        Some(n) => n,
+        None => panic!("Can't make an enode for the value of '{}'", var),
+    }
+}
+
+/// Instantiates the pattern enode `node` given the `Id`s of its already
+/// instantiated children, splicing in the bindings of a sequence variable.
+pub(crate) fn instantiate_node<L: Language>(
+    node: &L,
+    pat: &[ENodeOrVar<L>],
+    ids: &[Id],
+    subst: &Subst,
+) -> L {
+    let is_sequence =
+        |child: Id| matches!(&pat[usize::from(child)], ENodeOrVar::Var(v) if v.is_sequence());
+    if !node.children().iter().any(|&child| is_sequence(child)) {
+        return node.clone().map_children(|child| ids[usize::from(child)]);
+    }
+
+    let mut children = vec![];
+    for &child in node.children() {
+        match &pat[usize::from(child)] {
+            ENodeOrVar::Var(v) if v.is_sequence() => match subst.get_sequence(*v) {
+                Some(seq) => children.extend_from_slice(seq),
+                None => panic!("Sequence var '{}' not found in {:?}", v, subst),
+            },
+            _ => children.push(ids[usize::from(child)]),
+        }
+    }
+    let len = children.len();
+    node.with_children(children)
+        .unwrap_or_else(|| panic!("{:?} can't have {} children", node, len))
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -510,4 +900,384 @@ mod tests {
             assert_eq!(m1.substs.len(), usize::min(limit, match_size));
         }
     }
+
+    #[derive(Default)]
+    struct Leaves;
+
+    // `leaf` holds for eclasses containing a leaf
+    impl Analysis<S> for Leaves {
+        type Data = bool;
+        fn make(_egraph: &crate::EGraph<S, Self>, enode: &S) -> bool {
+            enode.is_leaf()
+        }
+        fn merge(&mut self, a: &mut bool, b: bool) -> DidMerge {
+            let merged = *a || b;
+            let did = DidMerge(merged != *a, merged != b);
+            *a = merged;
+            did
+        }
+        fn check_predicate(&self, predicate: Symbol, data: &bool) -> bool {
+            assert_eq!(predicate.as_str(), "leaf");
+            *data
+        }
+    }
+
+    #[test]
+    fn annotated_vars() {
+        crate::init_logger();
+        let mut egraph = crate::EGraph::<S, Leaves>::default();
+        let xx = egraph.add_expr(&"(+ x x)".parse().unwrap());
+        let fx = egraph.add_expr(&"(+ (f x) (f x))".parse().unwrap());
+        let mixed = egraph.add_expr(&"(+ x (f x))".parse().unwrap());
+        egraph.rebuild();
+
+        let eclasses = |pat: &str| -> Vec<Id> {
+            let pat: Pattern<S> = pat.parse().unwrap();
+            let mut ids: Vec<Id> = pat.search(&egraph).iter().map(|m| m.eclass).collect();
+            ids.sort();
+            ids
+        };
+        assert_eq!(eclasses("(+ ?a ?a)"), vec![xx, fx]);
+        assert!(xx < fx && fx < mixed);
+        // the annotation applies to every occurrence
+        assert_eq!(eclasses("(+ ?a:leaf ?a)"), vec![xx]);
+        assert_eq!(eclasses("(+ ?a ?b:leaf)"), vec![xx]);
+        assert_eq!(eclasses("(+ ?a:leaf ?b)"), vec![xx, mixed]);
+        assert_eq!(eclasses("?a:leaf").len(), 1);
+
+        let pat: Pattern<S> = "(+ ?a:leaf ?a)".parse().unwrap();
+        assert_eq!(pat.vars(), vec!["?a".parse().unwrap()]);
+        assert_eq!(
+            pat.var_predicates(),
+            &[("?a".parse().unwrap(), "leaf".into())]
+        );
+        assert_eq!(pat.to_string(), "(+ ?a:leaf ?a:leaf)");
+        assert_eq!(pat.to_string().parse::<Pattern<S>>().unwrap(), pat);
+
+        // the annotation isn't part of the variable, so rewrites can use it
+        let double: Rewrite<S, Leaves> = rewrite!("double"; "(+ ?a:leaf ?a)" => "(* 2 ?a)");
+        let runner = Runner::default()
+            .with_egraph(egraph)
+            .run(std::iter::once(&double));
+        let doubled = runner.egraph.lookup_expr(&"(* 2 x)".parse().unwrap());
+        assert_eq!(doubled, Some(runner.egraph.find(xx)));
+        assert!(runner
+            .egraph
+            .lookup_expr(&"(* 2 (f x))".parse().unwrap())
+            .is_none());
+    }
+
+    #[test]
+    fn annotated_vars_without_predicates() {
+        crate::init_logger();
+        let mut egraph = crate::EGraph::<S, ()>::default();
+        let fx = egraph.add_expr(&"(f x)".parse().unwrap());
+        egraph.rebuild();
+
+        // analyses that don't define predicates accept every eclass
+        let pat: Pattern<S> = "(f ?a:b)".parse().unwrap();
+        let matches = pat.search(&egraph);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].eclass, fx);
+    }
+
+    #[derive(Default)]
+    struct Consts;
+
+    // `const` binds the number in a constant eclass
+    impl Analysis<S> for Consts {
+        type Data = Option<i32>;
+        fn make(_egraph: &crate::EGraph<S, Self>, enode: &S) -> Option<i32> {
+            enode.op.as_str().parse().ok()
+        }
+        fn merge(&mut self, a: &mut Option<i32>, b: Option<i32>) -> DidMerge {
+            merge_option(a, b, |_, _| DidMerge(false, false))
+        }
+        fn check_predicate(&self, predicate: Symbol, data: &Option<i32>) -> bool {
+            assert_eq!(predicate.as_str(), "const");
+            data.is_some()
+        }
+        fn bind_value(&self, _predicate: Symbol, data: &Option<i32>) -> Option<Value> {
+            data.map(Value::new)
+        }
+        fn value_node(&self, value: &Value) -> Option<S> {
+            let n = value.downcast_ref::<i32>()?;
+            Some(S::leaf(n.to_string()))
+        }
+    }
+
+    struct Fold {
+        a: Var,
+        b: Var,
+        sum: Pattern<S>,
+    }
+
+    impl Applier<S, Consts> for Fold {
+        fn apply_one(
+            &self,
+            egraph: &mut crate::EGraph<S, Consts>,
+            eclass: Id,
+            subst: &Subst,
+            _searcher_ast: Option<&PatternAst<S>>,
+            _rule_name: Symbol,
+        ) -> Vec<Id> {
+            let a: i32 = *subst.get_value(self.a).unwrap();
+            let b: i32 = *subst.get_value(self.b).unwrap();
+            let mut subst = subst.clone();
+            subst.insert_value("?sum".parse().unwrap(), a + b);
+            let id = egraph.add_instantiation(&self.sum.ast, &subst);
+            if egraph.union(eclass, id) {
+                vec![id]
+            } else {
+                vec![]
+            }
+        }
+    }
+
+    #[test]
+    fn value_bindings() {
+        crate::init_logger();
+        let mut egraph = crate::EGraph::<S, Consts>::default();
+        let expr = egraph.add_expr(&"(* (+ 1 2) (+ x 0))".parse().unwrap());
+        egraph.rebuild();
+
+        let pat: Pattern<S> = "(+ ?a:const ?b:const)".parse().unwrap();
+        let matches = pat.search(&egraph);
+        assert_eq!(matches.len(), 1);
+        let subst = &matches[0].substs[0];
+        let (a, b) = ("?a".parse().unwrap(), "?b".parse().unwrap());
+        assert_eq!(subst.get_value::<i32>(a), Some(&1));
+        assert_eq!(subst.get_value::<i32>(b), Some(&2));
+        assert_eq!(subst.get_value::<i64>(a), None);
+        // values don't take part in comparisons
+        let mut other = subst.clone();
+        other.insert_value(a, 5);
+        assert_eq!(&other, subst);
+
+        let is_zero = |_: &mut crate::EGraph<S, Consts>, _, subst: &Subst| {
+            subst.get_value::<i32>("?b".parse().unwrap()) == Some(&0)
+        };
+        let rules: Vec<Rewrite<S, Consts>> = vec![
+            Rewrite::new(
+                "fold-add",
+                pat.clone(),
+                Fold {
+                    a,
+                    b,
+                    sum: "(+ ?sum 0)".parse().unwrap(),
+                },
+            )
+            .unwrap(),
+            rewrite!("zero-add"; "(+ ?a ?b:const)" => "?a" if is_zero),
+        ];
+        let runner = Runner::default().with_egraph(egraph).run(&rules);
+        let folded = runner.egraph.lookup_expr(&"(* 3 x)".parse().unwrap());
+        assert_eq!(folded, Some(runner.egraph.find(expr)));
+    }
+
+    #[test]
+    fn sequence_vars() {
+        crate::init_logger();
+        let mut egraph = EGraph::default();
+        let f = egraph.add_expr(&"(call f a b)".parse().unwrap());
+        let g = egraph.add_expr(&"(call g)".parse().unwrap());
+        let h = egraph.add_expr(&"(call h a b c)".parse().unwrap());
+        let p1 = egraph.add_expr(&"(pair (list a b) (list a b))".parse().unwrap());
+        let p2 = egraph.add_expr(&"(pair (list a b) (list a))".parse().unwrap());
+        egraph.rebuild();
+
+        let search = |pat: &str| -> Vec<Subst> {
+            let pat: Pattern<S> = pat.parse().unwrap();
+            let mut matches = pat.search(&egraph);
+            matches.sort_by_key(|m| m.eclass);
+            matches.into_iter().flat_map(|m| m.substs).collect()
+        };
+        let args: Var = "?args...".parse().unwrap();
+        assert!(args.is_sequence());
+        assert!(!"?args".parse::<Var>().unwrap().is_sequence());
+
+        let lens: Vec<usize> = search("(call ?f ?args...)")
+            .iter()
+            .map(|s| s.get_sequence(args).unwrap().len())
+            .collect();
+        assert_eq!(lens, vec![2, 0, 3]);
+
+        let c = egraph.lookup_expr(&"c".parse().unwrap()).unwrap();
+        let substs = search("(call ?f ?args... c)");
+        assert_eq!(substs.len(), 1);
+        assert_eq!(substs[0].get_sequence(args).unwrap().len(), 2);
+        assert_eq!(
+            substs[0].get("?f".parse().unwrap()),
+            Some(&egraph.find(egraph.lookup_expr(&"h".parse().unwrap()).unwrap()))
+        );
+        assert!(substs[0]
+            .get_sequence(args)
+            .unwrap()
+            .iter()
+            .all(|&id| id != c));
+
+        // repeated sequence variables must bind equal sequences
+        let pat: Pattern<S> = "(pair (list ?xs...) (list ?xs...))".parse().unwrap();
+        let matched: Vec<Id> = pat.search(&egraph).iter().map(|m| m.eclass).collect();
+        assert_eq!(matched, vec![p1]);
+        assert_ne!(p1, p2);
+
+        let rules: Vec<Rewrite<S, ()>> =
+            vec![rewrite!("uncurry"; "(call ?f ?args...)" => "(apply ?f (list ?args...))")];
+        let runner = Runner::default().with_egraph(egraph).run(&rules);
+        for (id, expr) in [
+            (f, "(apply f (list a b))"),
+            (g, "(apply g (list))"),
+            (h, "(apply h (list a b c))"),
+        ] {
+            let found = runner.egraph.lookup_expr(&expr.parse().unwrap());
+            assert_eq!(found, Some(runner.egraph.find(id)));
+        }
+    }
+
+    #[test]
+    fn sequence_var_explanations() {
+        crate::init_logger();
+        let rules: Vec<Rewrite<S, ()>> = vec![
+            rewrite!("call-list"; "(f ?args...)" => "(list ?args...)"),
+            rewrite!("rotate"; "(list ?x ?rest...)" => "(rot ?rest... ?x)"),
+        ];
+        let start: RecExpr<S> = "(g (f 1 2 3))".parse().unwrap();
+        let mut runner = Runner::default()
+            .with_explanations_enabled()
+            .with_expr(&start)
+            .run(&rules);
+
+        let end: RecExpr<S> = "(g (rot 2 3 1))".parse().unwrap();
+        let mut explanation = runner.explain_equivalence(&start, &end);
+        explanation.check_proof(&rules);
+        assert_eq!(
+            explanation.get_flat_strings(),
+            [
+                "(g (f 1 2 3))",
+                "(g (Rewrite=> call-list (list 1 2 3)))",
+                "(g (Rewrite=> rotate (rot 2 3 1)))"
+            ]
+        );
+    }
+
+    define_language! {
+        enum AcLang {
+            Num(i32),
+            #[ac] "+" = Add([Id; 2]),
+            #[ac] "sum" = Sum(Box<[Id]>),
+            "-" = Neg(Id),
+            Symbol(Symbol),
+        }
+    }
+
+    #[test]
+    fn ac_matching() {
+        crate::init_logger();
+        let mut egraph = crate::EGraph::<AcLang, ()>::default();
+        let ab = egraph.add_expr(&"(+ a b)".parse().unwrap());
+        let ba = egraph.add_expr(&"(+ b a)".parse().unwrap());
+        assert_eq!(ab, ba);
+        let a0 = egraph.add_expr(&"(+ a 0)".parse().unwrap());
+        let cancel = egraph.add_expr(&"(+ (+ y x) (- x))".parse().unwrap());
+        egraph.rebuild();
+
+        let pat: Pattern<AcLang> = "(+ ?a ?b)".parse().unwrap();
+        let matches = pat.search_eclass(&egraph, ab).unwrap();
+        assert_eq!(matches.substs.len(), 2);
+
+        let pat: Pattern<AcLang> = "(+ 0 ?x)".parse().unwrap();
+        let matched: Vec<Id> = pat.search(&egraph).iter().map(|m| m.eclass).collect();
+        assert_eq!(matched, vec![a0]);
+
+        // matches (+ (+ y x) (- x)) by regrouping and reordering
+        let rules: Vec<Rewrite<AcLang, ()>> =
+            vec![rewrite!("cancel"; "(+ ?a (+ ?b (- ?b)))" => "?a")];
+        let runner = Runner::default().with_egraph(egraph).run(&rules);
+        let y = runner.egraph.lookup_expr(&"y".parse().unwrap()).unwrap();
+        assert_eq!(runner.egraph.find(cancel), runner.egraph.find(y));
+
+        // the 8! orders of a wide AC enode are capped
+        let mut egraph = crate::EGraph::<AcLang, ()>::default();
+        let sum = egraph.add_expr(&"(sum a b c d e f g h)".parse().unwrap());
+        egraph.rebuild();
+        let pat: Pattern<AcLang> = "(sum ?a ?b ?c ?d ?e ?f ?g ?h)".parse().unwrap();
+        let matches = pat.search_eclass(&egraph, sum).unwrap();
+        assert_eq!(matches.substs.len(), 720);
+
+        // a chain of 16 operands has millions of regroupings
+        let vars: Vec<String> = (0..16).map(|i| format!("?x{}", i)).collect();
+        let chain = vars.iter().rev().fold(String::new(), |acc, v| match acc {
+            acc if acc.is_empty() => v.clone(),
+            acc => format!("(+ {} {})", v, acc),
+        });
+        let ast: PatternAst<AcLang> = chain.parse().unwrap();
+        assert!(super::ac_variants(&ast).len() >= super::MAX_AC_VARIANTS - 1);
+    }
+
+    #[test]
+    fn ac_explanations() {
+        crate::init_logger();
+        let mut egraph = crate::EGraph::<AcLang, ()>::default().with_explanations_enabled();
+        let ab: RecExpr<AcLang> = "(+ a b)".parse().unwrap();
+        let ba: RecExpr<AcLang> = "(+ b a)".parse().unwrap();
+        egraph.add_expr(&ab);
+        egraph.add_expr(&ba);
+        egraph.rebuild();
+        let mut explanation = egraph.explain_equivalence(&ab, &ba);
+        explanation.check_proof::<_, ()>(&[]);
+        assert_eq!(
+            explanation.get_flat_strings(),
+            ["(+ a b)", "(Rewrite=> ac-reorder (+ b a))"]
+        );
+
+        // (+ x z) and (+ w y) become congruent with their children swapped
+        let xz: RecExpr<AcLang> = "(+ x z)".parse().unwrap();
+        let wy: RecExpr<AcLang> = "(+ w y)".parse().unwrap();
+        egraph.add_expr(&xz);
+        egraph.add_expr(&wy);
+        for (from, to) in [("x", "y"), ("z", "w")] {
+            egraph.union_instantiations(
+                &from.parse().unwrap(),
+                &to.parse().unwrap(),
+                &Default::default(),
+                format!("{}{}", from, to),
+            );
+        }
+        egraph.rebuild();
+        let mut explanation = egraph.explain_equivalence(&xz, &wy);
+        explanation.check_proof::<_, ()>(&[]);
+        assert_eq!(
+            explanation.get_flat_strings(),
+            [
+                "(+ x z)",
+                "(+ (Rewrite=> xy y) z)",
+                "(+ y (Rewrite=> zw w))",
+                "(Rewrite=> ac-reorder (+ w y))"
+            ]
+        );
+
+        // rewrites that match modulo AC
+        let rules: Vec<Rewrite<AcLang, ()>> = vec![
+            rewrite!("zero-add"; "(+ ?a 0)" => "?a"),
+            rewrite!("cancel"; "(+ ?a (- ?a))" => "0"),
+        ];
+        let start: RecExpr<AcLang> = "(+ (- c) (+ 0 c))".parse().unwrap();
+        let mut runner = Runner::default()
+            .with_explanations_enabled()
+            .with_expr(&start)
+            .run(&rules);
+        let mut explanation = runner.explain_equivalence(&start, &"0".parse().unwrap());
+        explanation.check_proof(&rules);
+        assert_eq!(
+            explanation.get_flat_strings(),
+            [
+                "(+ (- c) (+ 0 c))",
+                "(+ (- c) (Rewrite=> ac-reorder (+ c 0)))",
+                "(+ (- c) (Rewrite=> zero-add c))",
+                "(Rewrite=> ac-reorder (+ c (- c)))",
+                "(Rewrite=> cancel 0)"
+            ]
+        );
+    }
 }
@@ -0,0 +1,234 @@
+use std::borrow::Cow;
+
+use crate::machine::ProgramTrie;
+use crate::*;
+
+/** Many [`Pattern`]s compiled into one shared matching automaton.
+
+Each [`Pattern`] compiles to its own program of matching instructions.
+A [`PatternSet`] merges those programs into a trie, so patterns that start
+the same way, like the many rules of the form `(+ ?a ...)`, share the work of
+matching their common prefix.
+[`search`](PatternSet::search()) finds the matches of every pattern in a
+single pass over the [`EGraph`].
+
+The [`Runner`] uses a [`PatternSet`] for the rules with [`Pattern`] searchers
+when its scheduler allows it, see
+[`RewriteScheduler::can_search_together`].
+
+# Example
+```
+use egg::*;
+
+let patterns: Vec<Pattern<SymbolLang>> = ["(+ ?a 0)", "(+ ?a ?b)", "(* ?a 1)"]
+    .iter()
+    .map(|p| p.parse().unwrap())
+    .collect();
+let set = PatternSet::new(patterns.iter().collect());
+// `(+ ?a 0)` and `(+ ?a ?b)` share the instruction that binds the `+`
+assert_eq!(set.size(), 4);
+
+let mut egraph = EGraph::<SymbolLang, ()>::default();
+egraph.add_expr(&"(+ x 0)".parse().unwrap());
+egraph.rebuild();
+
+let matches = set.search(&egraph);
+assert_eq!(matches.len(), 3);
+assert_eq!(matches[0].len(), 1);
+assert_eq!(matches[1].len(), 1);
+assert!(matches[2].is_empty());
+```
+*/
+#[derive(Debug, Clone)]
+pub struct PatternSet<'a, L> {
+    patterns: Vec<&'a Pattern<L>>,
+    program: SetProgram<L>,
+}
+
+impl<'a, L: Language> PatternSet<'a, L> {
+    /// Compiles the given patterns into one automaton.
+    pub fn new(patterns: Vec<&'a Pattern<L>>) -> Self {
+        let program = SetProgram::new(&patterns, None);
+        Self { patterns, program }
+    }
+
+    /// Like [`new`](PatternSet::new()), but first recompiles the patterns
+    /// with the given statistics, see [`Pattern::recompile`].
+    pub fn new_with_stats(patterns: Vec<&'a Pattern<L>>, stats: &OpStats<L>) -> Self {
+        let program = SetProgram::new(&patterns, Some(stats));
+        Self { patterns, program }
+    }
+
+    /// Returns the patterns in this set.
+    pub fn patterns(&self) -> &[&'a Pattern<L>] {
+        &self.patterns
+    }
+
+    /// Returns the number of patterns in this set.
+    pub fn len(&self) -> usize {
+        self.patterns.len()
+    }
+
+    /// Returns true if this set has no patterns.
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    /// Returns the number of instructions in the shared automaton.
+    pub fn size(&self) -> usize {
+        self.program.size()
+    }
+
+    /// Searches for every pattern at once, returning the matches of each
+    /// pattern in the same order as [`patterns`](PatternSet::patterns()).
+    pub fn search<N: Analysis<L>>(&self, egraph: &EGraph<L, N>) -> Vec<Vec<SearchMatches<'a, L>>> {
+        self.search_with_limit(egraph, usize::MAX)
+    }
+
+    /// Like [`search`](PatternSet::search()), but finds at most `limit`
+    /// substitutions for each pattern.
+    pub fn search_with_limit<N: Analysis<L>>(
+        &self,
+        egraph: &EGraph<L, N>,
+        limit: usize,
+    ) -> Vec<Vec<SearchMatches<'a, L>>> {
+        self.program
+            .search_with_limit(&self.patterns, egraph, limit)
+    }
+}
+
+/// The shared automaton of a [`PatternSet`].
+/// It doesn't borrow the patterns, so the [`Runner`] can keep it across
+/// iterations.
+#[derive(Debug, Clone)]
+pub(crate) struct SetProgram<L> {
+    trie: ProgramTrie<L>,
+    // which pattern each program in the trie belongs to
+    owners: Vec<usize>,
+}
+
+impl<L: Language> SetProgram<L> {
+    /// Compiles the given patterns, recompiling them first if there are
+    /// `stats`.
+    pub(crate) fn new(patterns: &[&Pattern<L>], stats: Option<&OpStats<L>>) -> Self {
+        let mut trie = ProgramTrie::default();
+        let mut owners = vec![];
+        for (i, pattern) in patterns.iter().enumerate() {
+            let recompiled;
+            let pattern = match stats {
+                Some(stats) => {
+                    let mut pattern = (*pattern).clone();
+                    pattern.recompile(stats);
+                    recompiled = pattern;
+                    &recompiled
+                }
+                None => *pattern,
+            };
+            for program in pattern.programs() {
+                trie.insert(program);
+                owners.push(i);
+            }
+        }
+        Self { trie, owners }
+    }
+
+    pub(crate) fn size(&self) -> usize {
+        self.trie.size()
+    }
+
+    /// Searches for the `patterns` this was compiled from.
+    pub(crate) fn search_with_limit<'a, N: Analysis<L>>(
+        &self,
+        patterns: &[&'a Pattern<L>],
+        egraph: &EGraph<L, N>,
+        limit: usize,
+    ) -> Vec<Vec<SearchMatches<'a, L>>> {
+        let mut matches: Vec<Vec<SearchMatches<'a, L>>> = patterns.iter().map(|_| vec![]).collect();
+        let mut counts = vec![0; patterns.len()];
+        let mut substs: Vec<Vec<Subst>> = patterns.iter().map(|_| vec![]).collect();
+        for class in egraph.classes() {
+            if egraph.is_cancelled() {
+                break;
+            }
+            self.trie.run(egraph, class.id, |program, subst| {
+                let p = self.owners[program];
+                if counts[p] + substs[p].len() < limit {
+                    substs[p].push(subst);
+                }
+            });
+            for (p, pattern) in patterns.iter().enumerate() {
+                if substs[p].is_empty() {
+                    continue;
+                }
+                let mut found = std::mem::take(&mut substs[p]);
+                if !pattern.ac_programs.is_empty() {
+                    // AC variants of a pattern can find the same match
+                    found.sort();
+                    found.dedup();
+                }
+                counts[p] += found.len();
+                matches[p].push(SearchMatches {
+                    eclass: class.id,
+                    substs: found,
+                    ast: Some(Cow::Borrowed(&pattern.ast)),
+                });
+            }
+        }
+        matches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{SymbolLang as S, *};
+
+    #[test]
+    fn same_as_separate_search() {
+        crate::init_logger();
+        let rules: Vec<Rewrite<S, ()>> = vec![
+            rewrite!("comm-add"; "(+ ?a ?b)" => "(+ ?b ?a)"),
+            rewrite!("assoc-add"; "(+ ?a (+ ?b ?c))" => "(+ (+ ?a ?b) ?c)"),
+            rewrite!("zero-add"; "(+ ?a 0)" => "?a"),
+            rewrite!("comm-mul"; "(* ?a ?b)" => "(* ?b ?a)"),
+            rewrite!("distribute"; "(* ?a (+ ?b ?c))" => "(+ (* ?a ?b) (* ?a ?c))"),
+            rewrite!("double"; "(+ ?a ?a)" => "(* 2 ?a)"),
+        ];
+        let expr = "(* x (+ (+ y 0) (+ x y)))".parse().unwrap();
+        let runner = Runner::default()
+            .with_expr(&expr)
+            .with_iter_limit(3)
+            .with_scheduler(SimpleScheduler)
+            .run(&rules);
+        let egraph = &runner.egraph;
+
+        // the shared search time is split among the rules
+        let report = runner.report();
+        assert!(report.pattern_set_search_time > 0.0);
+        assert!(report.rules.values().all(|r| r.search_time > 0.0));
+        let split: f64 = report.rules.values().map(|r| r.search_time).sum();
+        let diff = split - report.pattern_set_search_time;
+        assert!(diff.abs() <= 1e-9 * report.pattern_set_search_time.max(1.0));
+
+        let patterns: Vec<&Pattern<S>> = rules
+            .iter()
+            .map(|rw| rw.searcher.get_pattern().unwrap())
+            .collect();
+        let set = PatternSet::new(patterns.clone());
+        assert_eq!(set.len(), rules.len());
+
+        let normalize = |mut ms: Vec<SearchMatches<S>>| {
+            let mut ms: Vec<(Id, Vec<Subst>)> = ms
+                .drain(..)
+                .map(|mut m| {
+                    m.substs.sort();
+                    (m.eclass, m.substs)
+                })
+                .collect();
+            ms.sort();
+            ms
+        };
+        for (pattern, matches) in patterns.iter().zip(set.search(egraph)) {
+            assert_eq!(normalize(matches), normalize(pattern.search(egraph)));
+        }
+    }
+}
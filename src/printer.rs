@@ -0,0 +1,299 @@
+use std::fmt::{Display, Write};
+
+use crate::*;
+
+/// How a [`PrettyLanguage`] operator is written, see
+/// [`PrettyLanguage::fixity`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Fixity {
+    /// Written as a function application `f(a, b)`, or just `f` for leaves.
+    Prefix,
+    /// A binary operator written between its children, like `a + b`.
+    /// Operators with a higher precedence bind tighter.
+    Infix(u32, Assoc),
+    /// A unary operator written before its child, like `-a`.
+    Unary(u32),
+}
+
+/// The associativity of an infix operator, which decides how chains of
+/// operators with the same precedence are parenthesized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Assoc {
+    /// `a - b - c` means `(a - b) - c`.
+    Left,
+    /// `a ^ b ^ c` means `a ^ (b ^ c)`.
+    Right,
+    /// Chains always need parentheses, like `(a == b) == c`.
+    None,
+}
+
+/** A [`Language`] that can be printed in conventional notation by a
+[`PrettyPrinter`].
+
+[`fixity`](PrettyLanguage::fixity()) says how an enode's operator is
+written, and [`op_str`](PrettyLanguage::op_str()) gives its text.
+They default to [`Fixity::Prefix`] and the [`Display`] output, so
+implementing this trait with an empty body prints everything as function
+applications.
+
+# Example
+```
+use egg::*;
+
+define_language! {
+    enum Math {
+        "+" = Add([Id; 2]),
+        "-" = Sub([Id; 2]),
+        "*" = Mul([Id; 2]),
+        "^" = Pow([Id; 2]),
+        "neg" = Neg(Id),
+        Num(i32),
+        Symbol(Symbol),
+        Call(Symbol, Vec<Id>),
+    }
+}
+
+impl PrettyLanguage for Math {
+    fn fixity(&self) -> Fixity {
+        match self {
+            Math::Add(_) | Math::Sub(_) => Fixity::Infix(1, Assoc::Left),
+            Math::Mul(_) => Fixity::Infix(2, Assoc::Left),
+            Math::Pow(_) => Fixity::Infix(4, Assoc::Right),
+            Math::Neg(_) => Fixity::Unary(3),
+            _ => Fixity::Prefix,
+        }
+    }
+
+    fn op_str(&self) -> String {
+        match self {
+            Math::Neg(_) => "-".into(),
+            _ => self.to_string(),
+        }
+    }
+}
+
+let expr: RecExpr<Math> = "(* (+ a 1) (- (- b (neg c)) (^ d (^ 2 e))))".parse().unwrap();
+assert_eq!(
+    PrettyPrinter::default().print(&expr),
+    "(a + 1) * (b - -c - d ^ 2 ^ e)"
+);
+
+// build a DAG that shares `(^ (+ x 1) 2)`
+let mut expr = RecExpr::default();
+let x = expr.add(Math::Symbol("x".into()));
+let one = expr.add(Math::Num(1));
+let add = expr.add(Math::Add([x, one]));
+let two = expr.add(Math::Num(2));
+let pow = expr.add(Math::Pow([add, two]));
+expr.add(Math::Call("f".into(), vec![pow, pow]));
+assert_eq!(
+    PrettyPrinter::default().print(&expr),
+    "f((x + 1) ^ 2, (x + 1) ^ 2)"
+);
+assert_eq!(
+    PrettyPrinter::default().with_let_sharing(true).print(&expr),
+    "let v_0 = (x + 1) ^ 2;\nf(v_0, v_0)"
+);
+```
+**/
+pub trait PrettyLanguage: Language + Display {
+    /// Returns how this enode's operator is written.
+    fn fixity(&self) -> Fixity {
+        Fixity::Prefix
+    }
+
+    /// Returns the text of this enode's operator.
+    /// Defaults to its [`Display`] output.
+    fn op_str(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl PrettyLanguage for SymbolLang {}
+
+/** Prints a [`RecExpr`] of a [`PrettyLanguage`] in conventional notation,
+with infix operators and only the necessary parentheses.
+
+Unlike [`RecExpr::pretty`], which expands every shared subterm and so can
+print output exponential in the size of a DAG,
+[`with_let_sharing`](PrettyPrinter::with_let_sharing()) prints each
+subterm that is used more than once only once, bound to a name with `let`.
+
+See [`PrettyLanguage`] for an example.
+**/
+#[derive(Debug, Clone)]
+pub struct PrettyPrinter {
+    let_sharing: bool,
+    let_prefix: String,
+}
+
+impl Default for PrettyPrinter {
+    fn default() -> Self {
+        Self {
+            let_sharing: false,
+            let_prefix: "v_".into(),
+        }
+    }
+}
+
+// the precedence of applications, leaves and names, which never need
+// parentheses
+const ATOM: u32 = u32::MAX;
+
+impl PrettyPrinter {
+    /// Sets whether to bind shared subterms with `let`.
+    /// Default: `false`
+    pub fn with_let_sharing(self, let_sharing: bool) -> Self {
+        Self {
+            let_sharing,
+            ..self
+        }
+    }
+
+    /// Sets the prefix of the names of `let` bound subterms, which are
+    /// followed by a number.
+    /// Default: `"v_"`
+    pub fn with_let_prefix(self, let_prefix: impl Into<String>) -> Self {
+        Self {
+            let_prefix: let_prefix.into(),
+            ..self
+        }
+    }
+
+    /// Prints the given expression.
+    ///
+    /// With [`let` sharing](PrettyPrinter::with_let_sharing()), each
+    /// binding is printed on its own line as `let name = term;`, followed by
+    /// the root term.
+    pub fn print<L: PrettyLanguage>(&self, expr: &RecExpr<L>) -> String {
+        let nodes = expr.as_ref();
+        if nodes.is_empty() {
+            return "()".into();
+        }
+
+        let mut names: Vec<Option<String>> = vec![None; nodes.len()];
+        let mut out = String::new();
+        if self.let_sharing {
+            let uses = expr.use_counts();
+            let mut count = 0;
+            for (i, node) in nodes.iter().enumerate() {
+                if uses[i] > 1 && !node.is_leaf() {
+                    let (term, _) = self.term(nodes, i, &names);
+                    let name = format!("{}{}", self.let_prefix, count);
+                    writeln!(out, "let {} = {};", name, term).unwrap();
+                    names[i] = Some(name);
+                    count += 1;
+                }
+            }
+        }
+        let (term, _) = self.term(nodes, nodes.len() - 1, &names);
+        out.push_str(&term);
+        out
+    }
+
+    // prints node i, returning the text and its precedence
+    fn term<L: PrettyLanguage>(
+        &self,
+        nodes: &[L],
+        i: usize,
+        names: &[Option<String>],
+    ) -> (String, u32) {
+        let node = &nodes[i];
+        let children = node.children();
+        let child = |j: usize| {
+            let c = usize::from(children[j]);
+            match &names[c] {
+                Some(name) => (name.clone(), ATOM),
+                None => self.term(nodes, c, names),
+            }
+        };
+        let parens = |s: String, needed: bool| if needed { format!("({})", s) } else { s };
+
+        match node.fixity() {
+            Fixity::Infix(prec, assoc) if children.len() == 2 => {
+                let (left, lp) = child(0);
+                let (right, rp) = child(1);
+                let left_parens = lp < prec || (lp == prec && assoc != Assoc::Left);
+                let right_parens = rp < prec || (rp == prec && assoc != Assoc::Right);
+                let s = format!(
+                    "{} {} {}",
+                    parens(left, left_parens),
+                    node.op_str(),
+                    parens(right, right_parens)
+                );
+                (s, prec)
+            }
+            Fixity::Unary(prec) if children.len() == 1 => {
+                // parenthesize nested unary operators too, avoiding `--a`
+                let (arg, ap) = child(0);
+                let s = format!("{}{}", node.op_str(), parens(arg, ap <= prec));
+                (s, prec)
+            }
+            _ if children.is_empty() => (node.op_str(), ATOM),
+            _ => {
+                let args: Vec<String> = (0..children.len()).map(|j| child(j).0).collect();
+                (format!("{}({})", node.op_str(), args.join(", ")), ATOM)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{SymbolLang as S, *};
+
+    define_language! {
+        enum Cmp {
+            "==" = Eq([Id; 2]),
+            "&&" = And([Id; 2]),
+            "!" = Not(Id),
+            Symbol(Symbol),
+        }
+    }
+
+    impl PrettyLanguage for Cmp {
+        fn fixity(&self) -> Fixity {
+            match self {
+                Cmp::Eq(_) => Fixity::Infix(2, Assoc::None),
+                Cmp::And(_) => Fixity::Infix(1, Assoc::Right),
+                Cmp::Not(_) => Fixity::Unary(3),
+                Cmp::Symbol(_) => Fixity::Prefix,
+            }
+        }
+    }
+
+    #[test]
+    fn precedence_and_assoc() {
+        crate::init_logger();
+        let print = |s: &str| PrettyPrinter::default().print(&s.parse::<RecExpr<Cmp>>().unwrap());
+        assert_eq!(print("(== (== a b) c)"), "(a == b) == c");
+        assert_eq!(print("(&& (== a b) (&& c d))"), "a == b && c && d");
+        assert_eq!(print("(&& (&& a b) c)"), "(a && b) && c");
+        assert_eq!(print("(! (! (== a b)))"), "!(!(a == b))");
+        assert_eq!(print("(== (! a) b)"), "!a == b");
+    }
+
+    #[test]
+    fn let_sharing() {
+        crate::init_logger();
+        // a chain of n shared squares expands to 2^n leaves
+        let mut expr = RecExpr::default();
+        let mut id = expr.add(S::leaf("x"));
+        for _ in 0..20 {
+            id = expr.add(S::new("*", vec![id, id]));
+        }
+        expr.add(S::new("f", vec![id]));
+
+        let printer = PrettyPrinter::default()
+            .with_let_sharing(true)
+            .with_let_prefix("t");
+        let printed = printer.print(&expr);
+        assert_eq!(printed.lines().count(), 20);
+        assert!(printed.starts_with("let t0 = *(x, x);\nlet t1 = *(t0, t0);\n"));
+        assert!(printed.ends_with("let t18 = *(t17, t17);\nf(*(t18, t18))"));
+
+        // leaves are never bound
+        let expr = "(g y y)".parse().unwrap();
+        assert_eq!(printer.print::<S>(&expr), "g(y, y)");
+    }
+}
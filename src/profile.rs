@@ -0,0 +1,141 @@
+use std::io::{self, Write};
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::util::Instant;
+
+/** Records timed spans of a [`Runner`](crate::Runner) run in the
+[Chrome Trace Event format](https://docs.google.com/document/d/1CvAClvFfyA5R-PhYUmn5OOQtYMH4h6I0nSsKchNAySU).
+
+Enable it with [`Runner::with_profiler`](crate::Runner::with_profiler()).
+The runner then records a span for each iteration, each hook,
+each rule search, each rule application, and each rebuild.
+After the run, write the trace with
+[`write_chrome_trace`](Profiler::write_chrome_trace()) or
+[`save_chrome_trace`](Profiler::save_chrome_trace()),
+and load it into `chrome://tracing` or [Perfetto](https://ui.perfetto.dev).
+
+This is only available with the `reports` feature.
+
+# Example
+```
+use egg::*;
+
+let rules: &[Rewrite<SymbolLang, ()>] = &[
+    rewrite!("commute-add"; "(+ ?a ?b)" => "(+ ?b ?a)"),
+    rewrite!("add-0"; "(+ ?a 0)" => "?a"),
+];
+
+let runner = Runner::default()
+    .with_expr(&"(+ 0 (+ 1 x))".parse().unwrap())
+    .with_profiler()
+    .run(rules);
+
+let profiler = runner.profiler.as_ref().unwrap();
+assert!(profiler.events().iter().any(|e| e.cat == "search" && e.name == "add-0"));
+
+let mut json = vec![];
+profiler.write_chrome_trace(&mut json).unwrap();
+assert!(String::from_utf8(json).unwrap().contains("traceEvents"));
+```
+*/
+#[cfg_attr(docsrs, doc(cfg(feature = "reports")))]
+#[derive(Debug, Clone)]
+pub struct Profiler {
+    start: Instant,
+    events: Vec<TraceEvent>,
+}
+
+/// A single complete (`"ph": "X"`) event recorded by a [`Profiler`].
+///
+/// Timestamps and durations are in microseconds, relative to when the
+/// [`Profiler`] was created.
+#[cfg_attr(docsrs, doc(cfg(feature = "reports")))]
+#[derive(Debug, Clone, Serialize)]
+#[non_exhaustive]
+pub struct TraceEvent {
+    /// The name of the span, e.g. the rule name for searches.
+    pub name: String,
+    /// The category of the span: `"iteration"`, `"hook"`, `"search"`,
+    /// `"apply"`, or `"rebuild"`.
+    pub cat: &'static str,
+    /// The event type, always `"X"` (a complete event).
+    pub ph: &'static str,
+    /// The start of the span in microseconds.
+    pub ts: f64,
+    /// The duration of the span in microseconds.
+    pub dur: f64,
+    /// The process id, always 1.
+    pub pid: u32,
+    /// The thread id, always 1.
+    pub tid: u32,
+    /// Extra data shown when the span is selected, like the number of matches.
+    pub args: serde_json::Value,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Trace<'a> {
+    trace_events: &'a [TraceEvent],
+    display_time_unit: &'static str,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Profiler {
+    /// Creates a new, empty profiler. Timestamps are relative to now.
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            events: vec![],
+        }
+    }
+
+    /// Returns the events recorded so far, in the order the spans ended.
+    pub fn events(&self) -> &[TraceEvent] {
+        &self.events
+    }
+
+    /// Records a span from `start` until now.
+    pub fn record(
+        &mut self,
+        name: impl Into<String>,
+        cat: &'static str,
+        start: Instant,
+        args: serde_json::Value,
+    ) {
+        let micros = |d: std::time::Duration| d.as_secs_f64() * 1e6;
+        let ts = micros(start.duration_since(self.start));
+        self.events.push(TraceEvent {
+            name: name.into(),
+            cat,
+            ph: "X",
+            ts,
+            dur: micros(start.elapsed()),
+            pid: 1,
+            tid: 1,
+            args,
+        })
+    }
+
+    /// Writes the recorded events as a Chrome Trace Event JSON object.
+    pub fn write_chrome_trace(&self, writer: impl Write) -> io::Result<()> {
+        let trace = Trace {
+            trace_events: &self.events,
+            display_time_unit: "ms",
+        };
+        serde_json::to_writer(writer, &trace).map_err(io::Error::from)
+    }
+
+    /// Writes the recorded events as a Chrome Trace Event JSON file at
+    /// the given path.
+    pub fn save_chrome_trace(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let file = std::fs::File::create(path)?;
+        self.write_chrome_trace(io::BufWriter::new(file))
+    }
+}
@@ -0,0 +1,189 @@
+use std::fmt::Display;
+
+use crate::util::IndexSet;
+use crate::*;
+
+/** A datalog-style query that turns the matches of a [`Searcher`] into a
+table of variable bindings.
+
+[`Searcher::search`] groups its substitutions by the eclass they matched.
+A [`Query`] instead projects each substitution onto the selected variables,
+giving one row per distinct combination of (canonical) eclasses.
+This makes it easy to use an [`EGraph`] as a knowledge base, asking for all
+the `?a` and `?b` such that some [`MultiPattern`] holds.
+
+Rows come in the order they were found, unless
+[`with_sorted`](Query::with_sorted()) asks for them to be sorted:
+by the text of their extracted terms in
+[`run_extract`](Query::run_extract()), but only by their [`Id`]s in
+[`run`](Query::run()), which follows the order the eclasses were created in
+rather than anything about their contents.
+[`with_limit`](Query::with_limit()) caps the number of rows, and stops the
+search early unless the rows are sorted.
+Every selected variable must be bound to an eclass by the searcher.
+
+# Example
+```
+use egg::*;
+
+let mut egraph = EGraph::<SymbolLang, ()>::default();
+let t = egraph.add_expr(&"true".parse().unwrap());
+for fact in ["(edge 1 2)", "(edge 2 3)", "(edge 1 3)", "(label 3 x)"] {
+    let id = egraph.add_expr(&fact.parse().unwrap());
+    egraph.union(t, id);
+}
+egraph.rebuild();
+
+// which nodes have an edge to a labeled node?
+let pattern: MultiPattern<SymbolLang> =
+    "?t = true = (edge ?a ?b), ?t = (label ?b ?l)".parse().unwrap();
+let query = Query::new(pattern, vec!["?a".parse().unwrap(), "?l".parse().unwrap()])
+    .with_sorted(true);
+let rows: Vec<Vec<String>> = query
+    .run_extract(&egraph, AstSize)
+    .iter()
+    .map(|row| row.iter().map(|e| e.to_string()).collect())
+    .collect();
+assert_eq!(rows, vec![vec!["1", "x"], vec!["2", "x"]]);
+
+// the same rows as eclass ids, but at most one of them
+assert_eq!(query.with_limit(1).run(&egraph).len(), 1);
+```
+**/
+#[derive(Debug, Clone)]
+pub struct Query<S> {
+    searcher: S,
+    select: Vec<Var>,
+    limit: usize,
+    sorted: bool,
+}
+
+impl<S> Query<S> {
+    /// Creates a query that selects the given variables from the matches
+    /// of `searcher`.
+    pub fn new(searcher: S, select: Vec<Var>) -> Self {
+        Self {
+            searcher,
+            select,
+            limit: usize::MAX,
+            sorted: false,
+        }
+    }
+
+    /// Sets the maximum number of rows returned.
+    /// Default: no limit
+    pub fn with_limit(self, limit: usize) -> Self {
+        Self { limit, ..self }
+    }
+
+    /// Sets whether the rows are sorted, by their [`Id`]s in
+    /// [`run`](Query::run()) and by their printed terms in
+    /// [`run_extract`](Query::run_extract()).
+    /// Otherwise they come in the order they were found.
+    /// When combined with a limit, the rows are sorted before the limit is
+    /// applied.
+    /// Default: `false`
+    pub fn with_sorted(self, sorted: bool) -> Self {
+        Self { sorted, ..self }
+    }
+
+    /// Returns the searcher this query runs.
+    pub fn searcher(&self) -> &S {
+        &self.searcher
+    }
+
+    /// Returns the selected variables, which are the columns of each row.
+    pub fn select(&self) -> &[Var] {
+        &self.select
+    }
+
+    /// Runs the query, returning one row of canonical eclass [`Id`]s per
+    /// distinct binding of the selected variables.
+    pub fn run<L, N>(&self, egraph: &EGraph<L, N>) -> Vec<Vec<Id>>
+    where
+        L: Language,
+        N: Analysis<L>,
+        S: Searcher<L, N>,
+    {
+        if !self.sorted {
+            return self.rows(egraph, self.limit);
+        }
+        let mut rows = self.rows(egraph, usize::MAX);
+        rows.sort();
+        rows.truncate(self.limit);
+        rows
+    }
+
+    /// Like [`run`](Query::run()), but extracts the best term of each
+    /// eclass in the table according to `cost_function`.
+    pub fn run_extract<L, N, CF>(
+        &self,
+        egraph: &EGraph<L, N>,
+        cost_function: CF,
+    ) -> Vec<Vec<RecExpr<L>>>
+    where
+        L: Language + Display,
+        N: Analysis<L>,
+        S: Searcher<L, N>,
+        CF: CostFunction<L>,
+    {
+        let limit = if self.sorted { usize::MAX } else { self.limit };
+        let extractor = Extractor::new(egraph, cost_function);
+        let rows = self.rows(egraph, limit).into_iter().map(|row| {
+            let terms: Vec<RecExpr<L>> = row.iter().map(|&id| extractor.find_best(id).1).collect();
+            let key: Vec<String> = terms.iter().map(|t| t.to_string()).collect();
+            (key, terms)
+        });
+        let mut rows: Vec<(Vec<String>, Vec<RecExpr<L>>)> = rows.collect();
+        if self.sorted {
+            rows.sort_by(|(a, _), (b, _)| a.cmp(b));
+            rows.truncate(self.limit);
+        }
+        rows.into_iter().map(|(_, terms)| terms).collect()
+    }
+
+    /// Returns at most `limit` distinct rows, in the order they were found.
+    fn rows<L, N>(&self, egraph: &EGraph<L, N>, limit: usize) -> Vec<Vec<Id>>
+    where
+        L: Language,
+        N: Analysis<L>,
+        S: Searcher<L, N>,
+    {
+        let bound = self.searcher.vars();
+        for var in &self.select {
+            assert!(
+                bound.contains(var),
+                "Query selects variable {} that its searcher doesn't bind",
+                var
+            );
+        }
+
+        // several substitutions can give the same row, so search for more
+        // of them until there are enough rows or no more substitutions
+        let mut n_substs = limit;
+        loop {
+            let mut rows = IndexSet::default();
+            let mut found = 0;
+            for matches in self.searcher.search_with_limit(egraph, n_substs) {
+                found += matches.substs.len();
+                for subst in &matches.substs {
+                    let row: Vec<Id> = self
+                        .select
+                        .iter()
+                        .map(|v| match subst.get(*v) {
+                            Some(id) => egraph.find(*id),
+                            None => panic!("Query variable {} is not bound to an eclass", v),
+                        })
+                        .collect();
+                    rows.insert(row);
+                }
+            }
+            if rows.len() >= limit || found < n_substs {
+                let mut rows: Vec<Vec<Id>> = rows.into_iter().collect();
+                rows.truncate(limit);
+                return rows;
+            }
+            n_substs = n_substs.saturating_mul(2);
+        }
+    }
+}
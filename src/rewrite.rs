@@ -13,7 +13,6 @@ use crate::*;
 /// It additionally stores a name used to refer to the rewrite and a
 /// long name used for debugging.
 ///
-#[derive(Clone)]
 #[non_exhaustive]
 pub struct Rewrite<L, N> {
     /// The name of the rewrite.
@@ -24,6 +23,17 @@ pub struct Rewrite<L, N> {
     pub applier: Arc<dyn Applier<L, N> + Sync + Send>,
 }
 
+// not derived, which would require `L: Clone` and `N: Clone`
+impl<L, N> Clone for Rewrite<L, N> {
+    fn clone(&self) -> Self {
+        Self {
+            name: self.name,
+            searcher: self.searcher.clone(),
+            applier: self.applier.clone(),
+        }
+    }
+}
+
 impl<L, N> Debug for Rewrite<L, N>
 where
     L: Language + Display + 'static,
@@ -70,6 +80,24 @@ impl<L: Language, N: Analysis<L>> Rewrite<L, N> {
             }
         }
 
+        let ill_sorted = |e| format!("Rewrite {} is not well-sorted: {}", name, e);
+        let mut sorts = PatternSorts::default();
+        let lhs = match searcher.get_pattern_ast() {
+            Some(ast) => sorts.check(ast).map_err(ill_sorted)?,
+            None => None,
+        };
+        if let Some(ast) = applier.get_pattern_ast() {
+            let rhs = sorts.check(ast).map_err(ill_sorted)?;
+            if let (Some(lhs), Some(rhs)) = (lhs, rhs) {
+                if lhs != rhs {
+                    return Err(format!(
+                        "Rewrite {} rewrites sort {} to sort {}",
+                        name, lhs, rhs
+                    ));
+                }
+            }
+        }
+
         Ok(Self {
             name,
             searcher,
@@ -137,7 +165,7 @@ where
 {
     let mut ms = vec![];
     for eclass in eclasses {
-        if limit == 0 {
+        if limit == 0 || egraph.is_cancelled() {
             break;
         }
         match searcher.search_eclass_with_limit(egraph, eclass, limit) {
@@ -186,12 +214,14 @@ where
 
     /// Search the whole [`EGraph`], returning a list of all the
     /// [`SearchMatches`] where something was found.
-    /// This just calls [`search_eclass`] on each eclass.
+    /// This just calls [`search_eclass`] on each eclass,
+    /// stopping early if the egraph [`is_cancelled`](EGraph::is_cancelled).
     ///
     /// [`search_eclass`]: Searcher::search_eclass
     fn search(&self, egraph: &EGraph<L, N>) -> Vec<SearchMatches<L>> {
         egraph
             .classes()
+            .take_while(|_| !egraph.is_cancelled())
             .filter_map(|e| self.search_eclass(egraph, e.id))
             .collect()
     }
@@ -213,6 +243,12 @@ where
         None
     }
 
+    /// For patterns, return the pattern itself, so it can be searched
+    /// together with others in a [`PatternSet`]
+    fn get_pattern(&self) -> Option<&Pattern<L>> {
+        None
+    }
+
     /// Returns a list of the variables bound by this Searcher
     fn vars(&self) -> Vec<Var>;
 }
@@ -333,6 +369,7 @@ where
     /// It returns the ids resulting from the calls to [`apply_one`].
     /// The default implementation does this and should suffice for
     /// most use cases.
+    /// It stops early if the egraph [`is_cancelled`](EGraph::is_cancelled).
     ///
     /// [`apply_one`]: Applier::apply_one()
     fn apply_matches(
@@ -343,6 +380,9 @@ where
     ) -> Vec<Id> {
         let mut added = vec![];
         for mat in matches {
+            if egraph.is_cancelled() {
+                break;
+            }
             let ast = if egraph.are_explanations_enabled() {
                 mat.ast.as_ref().map(|cow| cow.as_ref())
             } else {
@@ -543,6 +583,325 @@ where
     }
 }
 
+/// A [`Condition`] that checks if two terms are not equivalent.
+///
+/// Like [`ConditionEqual`], this adds its two [`Pattern`]s to the egraph,
+/// but it passes if and only if they are in different eclasses.
+///
+#[derive(Debug)]
+pub struct ConditionNotEqual<L> {
+    p1: Pattern<L>,
+    p2: Pattern<L>,
+}
+
+impl<L: Language> ConditionNotEqual<L> {
+    /// Create a new [`ConditionNotEqual`] condition given two patterns.
+    pub fn new(p1: Pattern<L>, p2: Pattern<L>) -> Self {
+        ConditionNotEqual { p1, p2 }
+    }
+}
+
+impl<L: FromOp> ConditionNotEqual<L> {
+    /// Create a ConditionNotEqual by parsing two pattern strings.
+    ///
+    /// This panics if the parsing fails.
+    pub fn parse(a1: &str, a2: &str) -> Self {
+        Self {
+            p1: a1.parse().unwrap(),
+            p2: a2.parse().unwrap(),
+        }
+    }
+}
+
+impl<L, N> Condition<L, N> for ConditionNotEqual<L>
+where
+    L: Language,
+    N: Analysis<L>,
+{
+    fn check(&self, egraph: &mut EGraph<L, N>, _eclass: Id, subst: &Subst) -> bool {
+        let mut id_buf_1 = vec![0.into(); self.p1.ast.as_ref().len()];
+        let mut id_buf_2 = vec![0.into(); self.p2.ast.as_ref().len()];
+        let a1 = apply_pat(&mut id_buf_1, self.p1.ast.as_ref(), egraph, subst);
+        let a2 = apply_pat(&mut id_buf_2, self.p2.ast.as_ref(), egraph, subst);
+        egraph.find(a1) != egraph.find(a2)
+    }
+
+    fn vars(&self) -> Vec<Var> {
+        let mut vars = self.p1.vars();
+        vars.extend(self.p2.vars());
+        vars
+    }
+}
+
+/// A [`Condition`] that checks if a [`Pattern`] matches in the eclass
+/// bound to a variable.
+///
+/// Variables of the pattern that are also bound by the match must be bound
+/// to the same eclass, so `(f ?a)` in `?x` checks that `?x` contains `f`
+/// applied to the `?a` of the match.
+/// Other variables of the pattern can match anything.
+///
+/// Conditions run while rewrites are being applied, when the egraph may not
+/// be clean, so this matches the pattern directly against the enodes
+/// instead of using the pattern's compiled program.
+/// It doesn't support sequence variables or matching modulo
+/// associativity and commutativity.
+#[derive(Debug)]
+pub struct ConditionMatches<L> {
+    pattern: Pattern<L>,
+    var: Var,
+}
+
+impl<L: Language> ConditionMatches<L> {
+    /// Create a new [`ConditionMatches`] condition checking that `pattern`
+    /// matches in the eclass of `var`.
+    pub fn new(pattern: Pattern<L>, var: Var) -> Self {
+        ConditionMatches { pattern, var }
+    }
+}
+
+impl<L: FromOp> ConditionMatches<L> {
+    /// Create a ConditionMatches by parsing a pattern and a variable.
+    ///
+    /// This panics if the parsing fails.
+    pub fn parse(pattern: &str, var: &str) -> Self {
+        Self {
+            pattern: pattern.parse().unwrap(),
+            var: var.parse().unwrap(),
+        }
+    }
+}
+
+/// Finds the ways `pat[index]` matches in eclass `id`, extending `subst`.
+fn match_in_class<L: Language, N: Analysis<L>>(
+    egraph: &EGraph<L, N>,
+    pat: &[ENodeOrVar<L>],
+    index: Id,
+    id: Id,
+    subst: Subst,
+) -> Vec<Subst> {
+    let id = egraph.find(id);
+    match &pat[usize::from(index)] {
+        ENodeOrVar::Var(v) => match subst.get(*v) {
+            Some(&bound) if egraph.find(bound) != id => vec![],
+            Some(_) => vec![subst],
+            None => {
+                assert!(!v.is_sequence(), "ConditionMatches can't use {}", v);
+                let mut subst = subst;
+                subst.insert(*v, id);
+                vec![subst]
+            }
+        },
+        ENodeOrVar::ENode(pnode) => {
+            let mut substs = vec![];
+            for node in egraph[id].iter().filter(|n| pnode.matches(n)) {
+                let mut partial = vec![subst.clone()];
+                for (&pchild, &child) in pnode.children().iter().zip(node.children()) {
+                    partial = partial
+                        .into_iter()
+                        .flat_map(|s| match_in_class(egraph, pat, pchild, child, s))
+                        .collect();
+                }
+                substs.extend(partial);
+            }
+            substs
+        }
+    }
+}
+
+impl<L, N> Condition<L, N> for ConditionMatches<L>
+where
+    L: Language,
+    N: Analysis<L>,
+{
+    fn check(&self, egraph: &mut EGraph<L, N>, _eclass: Id, subst: &Subst) -> bool {
+        let pat = self.pattern.ast.as_ref();
+        let root = Id::from(pat.len() - 1);
+        !match_in_class(egraph, pat, root, subst[self.var], subst.clone()).is_empty()
+    }
+
+    fn vars(&self) -> Vec<Var> {
+        vec![self.var]
+    }
+}
+
+/// A [`Condition`] that passes if both of its conditions pass.
+///
+/// The second condition is only checked if the first one passes.
+#[derive(Debug)]
+pub struct ConditionAnd<A, B> {
+    a: A,
+    b: B,
+}
+
+impl<A, B> ConditionAnd<A, B> {
+    /// Create a new [`ConditionAnd`] from two conditions.
+    pub fn new(a: A, b: B) -> Self {
+        ConditionAnd { a, b }
+    }
+}
+
+impl<L, N, A, B> Condition<L, N> for ConditionAnd<A, B>
+where
+    L: Language,
+    N: Analysis<L>,
+    A: Condition<L, N>,
+    B: Condition<L, N>,
+{
+    fn check(&self, egraph: &mut EGraph<L, N>, eclass: Id, subst: &Subst) -> bool {
+        self.a.check(egraph, eclass, subst) && self.b.check(egraph, eclass, subst)
+    }
+
+    fn vars(&self) -> Vec<Var> {
+        let mut vars = self.a.vars();
+        vars.extend(self.b.vars());
+        vars
+    }
+}
+
+/// A [`Condition`] that passes if either of its conditions passes.
+///
+/// The second condition is only checked if the first one fails.
+#[derive(Debug)]
+pub struct ConditionOr<A, B> {
+    a: A,
+    b: B,
+}
+
+impl<A, B> ConditionOr<A, B> {
+    /// Create a new [`ConditionOr`] from two conditions.
+    pub fn new(a: A, b: B) -> Self {
+        ConditionOr { a, b }
+    }
+}
+
+impl<L, N, A, B> Condition<L, N> for ConditionOr<A, B>
+where
+    L: Language,
+    N: Analysis<L>,
+    A: Condition<L, N>,
+    B: Condition<L, N>,
+{
+    fn check(&self, egraph: &mut EGraph<L, N>, eclass: Id, subst: &Subst) -> bool {
+        self.a.check(egraph, eclass, subst) || self.b.check(egraph, eclass, subst)
+    }
+
+    fn vars(&self) -> Vec<Var> {
+        let mut vars = self.a.vars();
+        vars.extend(self.b.vars());
+        vars
+    }
+}
+
+/// A [`Condition`] that passes if its condition fails.
+#[derive(Debug)]
+pub struct ConditionNot<C> {
+    c: C,
+}
+
+impl<C> ConditionNot<C> {
+    /// Create a new [`ConditionNot`] negating a condition.
+    pub fn new(c: C) -> Self {
+        ConditionNot { c }
+    }
+}
+
+impl<L, N, C> Condition<L, N> for ConditionNot<C>
+where
+    L: Language,
+    N: Analysis<L>,
+    C: Condition<L, N>,
+{
+    fn check(&self, egraph: &mut EGraph<L, N>, eclass: Id, subst: &Subst) -> bool {
+        !self.c.check(egraph, eclass, subst)
+    }
+
+    fn vars(&self) -> Vec<Var> {
+        self.c.vars()
+    }
+}
+
+/// A [`Condition`] that checks the analysis data of the eclass bound to a
+/// variable with a closure.
+///
+/// Unlike a plain closure condition, this reports its variable in
+/// [`vars`](Condition::vars()), so [`Rewrite::new`] checks that the
+/// searcher binds it.
+///
+/// # Example
+/// ```
+/// use egg::*;
+/// #[derive(Default)]
+/// struct Consts;
+/// impl Analysis<SymbolLang> for Consts {
+///     type Data = Option<i32>;
+///     fn make(_egraph: &EGraph<SymbolLang, Self>, enode: &SymbolLang) -> Self::Data {
+///         enode.op.as_str().parse().ok()
+///     }
+///     fn merge(&mut self, a: &mut Self::Data, b: Self::Data) -> DidMerge {
+///         merge_option(a, b, |_, _| DidMerge(false, false))
+///     }
+/// }
+///
+/// let is_zero = |c: &Option<i32>| *c == Some(0);
+/// let zero_add: Rewrite<SymbolLang, Consts> = rewrite!(
+///     "zero-add"; "(+ ?a ?b)" => "?a"
+///     if ConditionData::new("?b".parse().unwrap(), is_zero)
+/// );
+/// let div_self: Rewrite<SymbolLang, Consts> = rewrite!(
+///     "div-self"; "(/ ?a ?a)" => "1"
+///     if ConditionNot::new(ConditionData::new("?a".parse().unwrap(), is_zero))
+/// );
+///
+/// // conditions report their variables, so unbound ones are caught
+/// let bad = ConditionData::new("?c".parse().unwrap(), |c: &Option<i32>| c.is_some());
+/// let searcher: Pattern<SymbolLang> = "(+ ?a ?b)".parse().unwrap();
+/// let applier: Pattern<SymbolLang> = "?a".parse().unwrap();
+/// let cond = ConditionalApplier { condition: bad, applier };
+/// assert!(Rewrite::<SymbolLang, Consts>::new("bad", searcher, cond).is_err());
+///
+/// let runner = Runner::default()
+///     .with_expr(&"(+ (/ x x) 0)".parse().unwrap())
+///     .run(&[div_self, zero_add]);
+/// let one = runner.egraph.lookup_expr(&"1".parse().unwrap()).unwrap();
+/// assert_eq!(runner.egraph.find(runner.roots[0]), runner.egraph.find(one));
+/// ```
+pub struct ConditionData<F> {
+    var: Var,
+    f: F,
+}
+
+impl<F> ConditionData<F> {
+    /// Create a new [`ConditionData`] that calls `f` on the data of the
+    /// eclass bound to `var`.
+    pub fn new(var: Var, f: F) -> Self {
+        ConditionData { var, f }
+    }
+}
+
+impl<F> Debug for ConditionData<F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ConditionData")
+            .field("var", &self.var)
+            .finish()
+    }
+}
+
+impl<L, N, F> Condition<L, N> for ConditionData<F>
+where
+    L: Language,
+    N: Analysis<L>,
+    F: Fn(&N::Data) -> bool,
+{
+    fn check(&self, egraph: &mut EGraph<L, N>, _eclass: Id, subst: &Subst) -> bool {
+        (self.f)(&egraph[subst[self.var]].data)
+    }
+
+    fn vars(&self) -> Vec<Var> {
+        vec![self.var]
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -589,6 +948,73 @@ mod tests {
         assert_eq!(apps, vec![egraph.find(mul)]);
     }
 
+    #[test]
+    fn condition_combinators() {
+        crate::init_logger();
+        let mut egraph = EGraph::default();
+        let fx = egraph.add_expr(&"(f x)".parse().unwrap());
+        let gy = egraph.add_expr(&"(g y)".parse().unwrap());
+        let pair = egraph.add_expr(&"(pair (f x) (g y))".parse().unwrap());
+        let same = egraph.add_expr(&"(pair (f x) (f x))".parse().unwrap());
+        egraph.rebuild();
+
+        let (a, b, c) = (
+            "?a".parse().unwrap(),
+            "?b".parse().unwrap(),
+            "?c".parse().unwrap(),
+        );
+        let mut subst = Subst::default();
+        subst.insert(a, fx);
+        subst.insert(b, gy);
+        subst.insert(c, pair);
+
+        let mut check = |cond: &dyn Condition<S, ()>| cond.check(&mut egraph, pair, &subst);
+        assert!(check(&ConditionNotEqual::parse("?a", "?b")));
+        assert!(!check(&ConditionNotEqual::parse("?a", "?a")));
+        assert!(check(&ConditionMatches::parse("(f ?x)", "?a")));
+        assert!(!check(&ConditionMatches::parse("(f ?x)", "?b")));
+        // variables bound by the match must agree
+        assert!(check(&ConditionMatches::parse("(pair ?a ?b)", "?c")));
+        assert!(!check(&ConditionMatches::parse("(pair ?a ?a)", "?c")));
+        assert!(check(&ConditionMatches::parse("(pair ?x ?y)", "?c")));
+        assert!(!check(&ConditionNot::new(ConditionMatches::parse(
+            "(f x)", "?a"
+        ))));
+        assert!(check(&ConditionAnd::new(
+            ConditionMatches::parse("(f ?x)", "?a"),
+            ConditionMatches::parse("(g ?x)", "?b"),
+        )));
+        assert!(!check(&ConditionAnd::new(
+            ConditionMatches::parse("(f ?x)", "?a"),
+            ConditionMatches::parse("(f ?x)", "?b"),
+        )));
+        assert!(check(&ConditionOr::new(
+            ConditionMatches::parse("(f ?x)", "?b"),
+            ConditionMatches::parse("(g ?x)", "?b"),
+        )));
+        assert!(check(&ConditionData::new(a, |_: &()| true)));
+
+        let cond = ConditionAnd::new(
+            ConditionNotEqual::parse("?a", "?b"),
+            ConditionNot::new(ConditionMatches::parse("(g ?x)", "?c")),
+        );
+        assert_eq!(Condition::<S, ()>::vars(&cond), vec![a, b, c]);
+
+        // `?c` isn't bound by the searcher
+        let searcher: Pattern<S> = "(pair ?a ?b)".parse().unwrap();
+        let applier: Pattern<S> = "?a".parse().unwrap();
+        let condition = ConditionalApplier {
+            condition: cond,
+            applier,
+        };
+        assert!(Rewrite::<S, ()>::new("bad", searcher, condition).is_err());
+
+        let rw: Rewrite<S, ()> = rewrite!("distinct"; "(pair ?a ?b)" => "distinct" if ConditionNotEqual::parse("?a", "?b"));
+        let apps = rw.run(&mut egraph);
+        assert_eq!(apps, vec![pair]);
+        assert_ne!(egraph.find(same), egraph.find(pair));
+    }
+
     #[test]
     fn fn_rewrite() {
         crate::init_logger();
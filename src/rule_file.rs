@@ -0,0 +1,451 @@
+use std::fmt::{self, Display, Formatter};
+use std::sync::Arc;
+
+use thiserror::Error;
+
+use crate::*;
+
+/// An error from parsing a rules file, with the 1-based line it occurred on.
+#[derive(Debug, Clone, Error)]
+#[error("line {line}: {message}")]
+pub struct RuleParseError {
+    /// The line of the rules file the error is on.
+    pub line: usize,
+    /// What went wrong.
+    pub message: String,
+}
+
+/// A single rule as written in a rules file.
+///
+/// See [`RuleParser`] for the format.
+/// The [`Display`] implementation writes the rule back out in that format.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct RuleDecl<L> {
+    /// The name of the rule.
+    pub name: Symbol,
+    /// The left-hand side.
+    pub lhs: Pattern<L>,
+    /// The right-hand side.
+    pub rhs: Pattern<L>,
+    /// Whether the rule was written with `<=>`.
+    pub bidirectional: bool,
+    /// The conditions after `if`, as a name and its argument variables.
+    pub conditions: Vec<(Symbol, Vec<Var>)>,
+    /// The 1-based line the rule was declared on, or 0 if it wasn't parsed.
+    pub line: usize,
+}
+
+impl<L: Language + Display> Display for RuleDecl<L> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let arrow = if self.bidirectional { "<=>" } else { "=>" };
+        write!(f, "{}: {} {} {}", self.name, self.lhs, arrow, self.rhs)?;
+        if !self.conditions.is_empty() {
+            write!(f, " if")?;
+            for (name, args) in &self.conditions {
+                write!(f, " ({}", name)?;
+                for arg in args {
+                    write!(f, " {}", arg)?;
+                }
+                write!(f, ")")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Writes rules out in the rules file format, one per line.
+///
+/// Parsing the result with [`RuleParser::parse_decls`] gives back the same rules.
+pub fn print_rules<L: Language + Display>(rules: &[RuleDecl<L>]) -> String {
+    let mut s = String::new();
+    for rule in rules {
+        s += &rule.to_string();
+        s.push('\n');
+    }
+    s
+}
+
+type DynCondition<L, N> = Arc<dyn Condition<L, N> + Send + Sync>;
+type ConditionMaker<L, N> = Box<dyn Fn(&[Var]) -> Result<DynCondition<L, N>, String>>;
+
+// all of a rule's conditions, reporting their arguments as vars
+// so `Rewrite::new` checks that the searcher binds them
+struct RuleConditions<L, N> {
+    conditions: Vec<DynCondition<L, N>>,
+    args: Vec<Var>,
+}
+
+impl<L: Language, N: Analysis<L>> Condition<L, N> for RuleConditions<L, N> {
+    fn check(&self, egraph: &mut EGraph<L, N>, eclass: Id, subst: &Subst) -> bool {
+        self.conditions
+            .iter()
+            .all(|c| c.check(egraph, eclass, subst))
+    }
+
+    fn vars(&self) -> Vec<Var> {
+        let mut vars = self.args.clone();
+        for c in &self.conditions {
+            vars.extend(c.vars());
+        }
+        vars.sort();
+        vars.dedup();
+        vars
+    }
+}
+
+/** Loads [`Rewrite`]s from a text format, so rules can change without
+recompiling.
+
+Each non-empty line that doesn't start with `#` is a rule of the form
+```text
+name: lhs => rhs
+name: lhs <=> rhs
+name: lhs => rhs if (condition ?x ...) ...
+```
+where `lhs` and `rhs` are [`Pattern`]s.
+Like [`rewrite!`], `<=>` makes two rules, the second one named `name-rev`.
+Conditions are looked up by name in a table registered with
+[`with_condition`](RuleParser::with_condition());
+all of a rule's conditions must hold for it to apply, and their arguments
+must be bound by the left-hand side (of both rules, for `<=>`).
+Errors report the line they occur on.
+
+# Example
+```
+use egg::*;
+
+let text = "
+## arithmetic
+commute-add: (+ ?a ?b) => (+ ?b ?a)
+add-0: (+ ?a 0) <=> ?a
+div-self: (/ ?a ?a) => 1 if (is_not_zero ?a)
+";
+
+let parser = RuleParser::<SymbolLang, ()>::new().with_condition("is_not_zero", |args| {
+    if args.len() != 1 {
+        return Err("is_not_zero takes one variable".into());
+    }
+    let var = args[0];
+    let zero = SymbolLang::leaf("0");
+    Ok(move |egraph: &mut EGraph<SymbolLang, ()>, _, subst: &Subst| {
+        !egraph[subst[var]].nodes.contains(&zero)
+    })
+});
+let rules = parser.parse(text).unwrap();
+let names: Vec<_> = rules.iter().map(|rw| rw.name.as_str()).collect();
+assert_eq!(names, ["commute-add", "add-0", "add-0-rev", "div-self"]);
+
+let err = parser.parse("oops: (+ ?a ?b) => ?c").unwrap_err();
+assert_eq!(err.line, 1);
+```
+*/
+pub struct RuleParser<L: Language, N: Analysis<L>> {
+    conditions: HashMap<Symbol, ConditionMaker<L, N>>,
+}
+
+impl<L: Language, N: Analysis<L>> Default for RuleParser<L, N> {
+    fn default() -> Self {
+        Self {
+            conditions: Default::default(),
+        }
+    }
+}
+
+impl<L: Language, N: Analysis<L>> fmt::Debug for RuleParser<L, N> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RuleParser")
+            .field("conditions", &self.conditions.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl<L, N> RuleParser<L, N>
+where
+    L: FromOp + Send + Sync + 'static,
+    N: Analysis<L> + 'static,
+{
+    /// Creates a parser with no conditions.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a condition that can be used after `if` as `(name ?x ...)`.
+    ///
+    /// `make` is called with the arguments of each use, and returns the
+    /// [`Condition`] to check, or an error message (like for the wrong
+    /// number of arguments) that is reported with the line of the rule.
+    pub fn with_condition<C>(
+        mut self,
+        name: impl Into<Symbol>,
+        make: impl Fn(&[Var]) -> Result<C, String> + 'static,
+    ) -> Self
+    where
+        C: Condition<L, N> + Send + Sync + 'static,
+    {
+        let make: ConditionMaker<L, N> = Box::new(move |args| {
+            let condition: DynCondition<L, N> = Arc::new(make(args)?);
+            Ok(condition)
+        });
+        self.conditions.insert(name.into(), make);
+        self
+    }
+
+    /// Parses a rules file into [`Rewrite`]s, resolving conditions.
+    pub fn parse(&self, text: &str) -> Result<Vec<Rewrite<L, N>>, RuleParseError> {
+        self.to_rewrites(&Self::parse_decls(text)?)
+    }
+
+    /// Parses a rules file without resolving conditions.
+    pub fn parse_decls(text: &str) -> Result<Vec<RuleDecl<L>>, RuleParseError> {
+        let mut decls = vec![];
+        for (i, line) in text.lines().enumerate() {
+            let line_no = i + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let decl = parse_line(line).map_err(|message| RuleParseError {
+                line: line_no,
+                message,
+            })?;
+            decls.push(RuleDecl {
+                line: line_no,
+                ..decl
+            });
+        }
+        Ok(decls)
+    }
+
+    /// Turns rule declarations into [`Rewrite`]s, resolving conditions.
+    pub fn to_rewrites(&self, decls: &[RuleDecl<L>]) -> Result<Vec<Rewrite<L, N>>, RuleParseError> {
+        let mut rewrites = vec![];
+        for decl in decls {
+            let error = |message: String| RuleParseError {
+                line: decl.line,
+                message,
+            };
+            let conditions = decl
+                .conditions
+                .iter()
+                .map(|(name, args)| match self.conditions.get(name) {
+                    Some(make) => make(args).map_err(|e| format!("condition {}: {}", name, e)),
+                    None => Err(format!("unknown condition {}", name)),
+                })
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(error)?;
+
+            let mut add = |name: Symbol, lhs: &Pattern<L>, rhs: &Pattern<L>| {
+                let rewrite = if conditions.is_empty() {
+                    Rewrite::new(name, lhs.clone(), rhs.clone())
+                } else {
+                    let condition = RuleConditions {
+                        conditions: conditions.clone(),
+                        args: decl
+                            .conditions
+                            .iter()
+                            .flat_map(|(_, args)| args.clone())
+                            .collect(),
+                    };
+                    let applier = ConditionalApplier {
+                        condition,
+                        applier: rhs.clone(),
+                    };
+                    Rewrite::new(name, lhs.clone(), applier)
+                };
+                rewrite.map(|rw| rewrites.push(rw))
+            };
+            add(decl.name, &decl.lhs, &decl.rhs).map_err(error)?;
+            if decl.bidirectional {
+                let name = format!("{}-rev", decl.name);
+                add(name.into(), &decl.rhs, &decl.lhs).map_err(error)?;
+            }
+        }
+        Ok(rewrites)
+    }
+}
+
+fn parse_line<L: FromOp>(line: &str) -> Result<RuleDecl<L>, String> {
+    let (name, rest) = match line.find(':') {
+        Some(i) => (line[..i].trim(), &line[i + 1..]),
+        None => return Err("expected `name: lhs => rhs`".into()),
+    };
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        return Err(format!("bad rule name {:?}", name));
+    }
+
+    let tokens = tokenize(rest)?;
+    let (lhs, arrow, rhs, conditions) = match tokens.as_slice() {
+        [lhs, arrow, rhs, rest @ ..] if *arrow == "=>" || *arrow == "<=>" => {
+            (lhs, arrow, rhs, rest)
+        }
+        _ => return Err("expected `lhs => rhs` or `lhs <=> rhs`".into()),
+    };
+    let conditions = match conditions {
+        [] => vec![],
+        [kw, conds @ ..] if *kw == "if" && !conds.is_empty() => conds
+            .iter()
+            .map(|c| parse_condition(c))
+            .collect::<Result<_, _>>()?,
+        _ => return Err("expected `if (condition ...)` after the right-hand side".into()),
+    };
+
+    let pattern = |s: &str| {
+        s.parse::<Pattern<L>>()
+            .map_err(|e| format!("bad pattern {}: {:?}", s, e))
+    };
+    Ok(RuleDecl {
+        name: name.into(),
+        lhs: pattern(lhs)?,
+        rhs: pattern(rhs)?,
+        bidirectional: *arrow == "<=>",
+        conditions,
+        line: 0,
+    })
+}
+
+fn parse_condition(s: &str) -> Result<(Symbol, Vec<Var>), String> {
+    let inner = match s.strip_prefix('(').and_then(|s| s.strip_suffix(')')) {
+        Some(inner) => inner,
+        None => return Err(format!("expected a condition like (name ?x), found {}", s)),
+    };
+    let mut words = inner.split_whitespace();
+    let name = match words.next() {
+        Some(name) if !name.contains('(') => name,
+        _ => return Err(format!("bad condition {}", s)),
+    };
+    let args = words
+        .map(|w| {
+            w.parse::<Var>()
+                .map_err(|e| format!("bad condition argument {}: {}", w, e))
+        })
+        .collect::<Result<_, _>>()?;
+    Ok((name.into(), args))
+}
+
+// split on whitespace, keeping parenthesized s-expressions together
+fn tokenize(s: &str) -> Result<Vec<&str>, String> {
+    let mut tokens = vec![];
+    let mut depth = 0usize;
+    let mut start = None;
+    for (i, c) in s.char_indices() {
+        match c {
+            '(' => {
+                start.get_or_insert(i);
+                depth += 1;
+            }
+            ')' => {
+                depth = depth.checked_sub(1).ok_or("unbalanced `)`")?;
+            }
+            c if c.is_whitespace() && depth == 0 => {
+                if let Some(st) = start.take() {
+                    tokens.push(&s[st..i]);
+                }
+            }
+            _ => {
+                start.get_or_insert(i);
+            }
+        }
+    }
+    if depth != 0 {
+        return Err("unbalanced `(`".into());
+    }
+    if let Some(st) = start {
+        tokens.push(&s[st..]);
+    }
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{SymbolLang as S, *};
+
+    fn parser() -> RuleParser<S, ()> {
+        RuleParser::new().with_condition("is_leaf", |args| match args {
+            [v] => {
+                let v = *v;
+                Ok(move |egraph: &mut EGraph<S, ()>, _, subst: &Subst| {
+                    egraph[subst[v]].nodes.iter().any(|n| n.is_leaf())
+                })
+            }
+            _ => Err(format!("expected 1 argument, got {}", args.len())),
+        })
+    }
+
+    #[test]
+    fn parse_and_print_round_trip() {
+        let text = "\
+# a comment
+
+comm: (+ ?a ?b) => (+ ?b ?a)
+assoc: (+ ?a (+ ?b ?c)) <=> (+ (+ ?a ?b) ?c)
+double: (+ ?a ?a) => (* 2 ?a) if (is_leaf ?a) (is_leaf ?a)
+";
+        let decls = RuleParser::<S, ()>::parse_decls(text).unwrap();
+        assert_eq!(decls.iter().map(|d| d.line).collect::<Vec<_>>(), [3, 4, 5]);
+        assert!(decls[1].bidirectional);
+        assert_eq!(decls[2].conditions.len(), 2);
+
+        let printed = print_rules(&decls);
+        assert_eq!(
+            printed,
+            "comm: (+ ?a ?b) => (+ ?b ?a)\n\
+             assoc: (+ ?a (+ ?b ?c)) <=> (+ (+ ?a ?b) ?c)\n\
+             double: (+ ?a ?a) => (* 2 ?a) if (is_leaf ?a) (is_leaf ?a)\n"
+        );
+        let reparsed = RuleParser::<S, ()>::parse_decls(&printed).unwrap();
+        for (a, b) in decls.iter().zip(&reparsed) {
+            assert_eq!(
+                RuleDecl {
+                    line: 0,
+                    ..a.clone()
+                },
+                RuleDecl {
+                    line: 0,
+                    ..b.clone()
+                }
+            );
+        }
+
+        let rules = parser().to_rewrites(&decls).unwrap();
+        let names: Vec<_> = rules.iter().map(|rw| rw.name.as_str()).collect();
+        assert_eq!(names, ["comm", "assoc", "assoc-rev", "double"]);
+    }
+
+    #[test]
+    fn conditions_are_checked() {
+        let rules = parser()
+            .parse("double: (+ ?a ?a) => (* 2 ?a) if (is_leaf ?a)")
+            .unwrap();
+        let runner = Runner::default()
+            .with_expr(&"(+ x x)".parse().unwrap())
+            .with_expr(&"(+ (f x) (f x))".parse().unwrap())
+            .run(&rules);
+        let two_x = runner.egraph.lookup_expr(&"(* 2 x)".parse().unwrap());
+        assert_eq!(two_x, Some(runner.egraph.find(runner.roots[0])));
+        assert!(runner
+            .egraph
+            .lookup_expr(&"(* 2 (f x))".parse().unwrap())
+            .is_none());
+    }
+
+    #[test]
+    fn errors_have_line_numbers() {
+        let cases = [
+            ("\n\nno colon here", 3, "expected `name: lhs => rhs`"),
+            ("a: (+ ?a ?b) -> ?a", 1, "expected `lhs => rhs`"),
+            ("a: (+ ?a ?b => ?a", 1, "unbalanced"),
+            ("a: ?a => ?b", 1, "unbound var ?b"),
+            ("a: ?a => ?a if (nope ?a)", 1, "unknown condition nope"),
+            ("a: ?a => ?a if (is_leaf)", 1, "expected 1 argument"),
+            ("a: ?a => ?a if", 1, "expected `if (condition ...)`"),
+            ("\nd: (/ ?a ?a) => 1 if (is_leaf ?b)", 2, "unbound var ?b"),
+            ("a: (+ ?a ?b) <=> ?a if (is_leaf ?b)", 1, "a-rev refers to unbound var ?b"),
+        ];
+        for (text, line, message) in cases {
+            let err = parser().parse(text).unwrap_err();
+            assert_eq!(err.line, line, "{}", err);
+            assert!(err.to_string().contains(message), "{}", err);
+        }
+    }
+}
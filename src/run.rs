@@ -1,7 +1,14 @@
 use std::fmt::{self, Debug, Formatter};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
 
 use log::*;
+#[cfg(feature = "reports")]
+use serde_json::json;
 
+use crate::pattern_set::SetProgram;
 use crate::*;
 
 /** Faciliates running rewrites over an [`EGraph`].
@@ -55,6 +62,20 @@ Here are some of the things [`Runner`] does for you:
   If this limit is hit, it stops with
   [`StopReason::TimeLimit`].
 
+- Memory limit
+
+  You can set an upper limit on the estimated heap size of the
+  [`EGraph`] (see [`EGraph::estimated_heap_size`]).
+  If this limit is hit, it stops with
+  [`StopReason::MemoryLimit`].
+
+- Cancellation
+
+  You can give the runner a shared flag with
+  [`with_cancellation`](Runner::with_cancellation()).
+  Setting it (e.g. from another thread) stops the runner with
+  [`StopReason::Cancelled`].
+
 - Rule scheduling
 
   Some rules enable themselves, blowing up the [`EGraph`] and
@@ -64,6 +85,20 @@ Here are some of the things [`Runner`] does for you:
 
   [`BackoffScheduler`] is the default scheduler.
 
+- Profiling
+
+  With the `reports` feature, [`with_profiler`](Runner::with_profiler())
+  records a [`Profiler`] trace of each iteration, hook, rule search,
+  rule application, and rebuild, which can be viewed in
+  `chrome://tracing` or Perfetto.
+
+- Phased schedules
+
+  You can register named rule sets with
+  [`with_rule_set`](Runner::with_rule_set()) and drive them with a
+  [`Schedule`] using [`run_schedule`](Runner::run_schedule()),
+  e.g. to simplify to saturation, then expand for a few iterations.
+
 [`Runner`] generates [`Iteration`]s that record some data about
 each iteration.
 You can add your own data to this by implementing the
@@ -151,13 +186,37 @@ pub struct Runner<L: Language, N: Analysis<L>, IterData = ()> {
     #[allow(clippy::type_complexity)]
     pub hooks: Vec<Box<dyn FnMut(&mut Self) -> Result<(), String>>>,
 
+    /// The named rule sets added by the
+    /// [`with_rule_set`](Runner::with_rule_set()) method, used by
+    /// [`run_schedule`](Runner::run_schedule()).
+    pub rule_sets: IndexMap<Symbol, Vec<Rewrite<L, N>>>,
+
+    /// The trace recorded during the run, if enabled with
+    /// [`with_profiler`](Runner::with_profiler()).
+    #[cfg(feature = "reports")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "reports")))]
+    pub profiler: Option<Profiler>,
+
     // limits
     iter_limit: usize,
     node_limit: usize,
     time_limit: Duration,
+    memory_limit: usize,
 
+    cancellation: Option<Arc<AtomicBool>>,
     start_time: Option<Instant>,
     scheduler: Box<dyn RewriteScheduler<L, N>>,
+    // the automata for searching rules together, by the names of the rules
+    pattern_sets: HashMap<Vec<Symbol>, CachedSetProgram<L>>,
+}
+
+/// A [`SetProgram`] kept by the [`Runner`] across iterations.
+#[derive(Debug)]
+struct CachedSetProgram<L> {
+    // the patterns and the number of eclasses this was compiled for
+    patterns: Vec<PatternAst<L>>,
+    n_classes: usize,
+    program: SetProgram<L>,
 }
 
 impl<L, N> Default for Runner<L, N, ()>
@@ -184,22 +243,33 @@ where
             roots,
             stop_reason,
             hooks,
+            rule_sets,
+            #[cfg(feature = "reports")]
+            profiler,
             iter_limit,
             node_limit,
             time_limit,
+            memory_limit,
+            cancellation,
             start_time,
             scheduler: _,
+            pattern_sets: _,
         } = self;
 
-        f.debug_struct("Runner")
-            .field("egraph", egraph)
+        let mut s = f.debug_struct("Runner");
+        s.field("egraph", egraph)
             .field("iterations", iterations)
             .field("roots", roots)
             .field("stop_reason", stop_reason)
             .field("hooks", &vec![format_args!("<dyn FnMut ..>"); hooks.len()])
-            .field("iter_limit", iter_limit)
+            .field("rule_sets", &rule_sets.keys().collect::<Vec<_>>());
+        #[cfg(feature = "reports")]
+        s.field("profiler", profiler);
+        s.field("iter_limit", iter_limit)
             .field("node_limit", node_limit)
             .field("time_limit", time_limit)
+            .field("memory_limit", memory_limit)
+            .field("cancellation", cancellation)
             .field("start_time", start_time)
             .field("scheduler", &format_args!("<dyn RewriteScheduler ..>"))
             .finish()
@@ -220,6 +290,15 @@ pub enum StopReason {
     NodeLimit(usize),
     /// The time limit was hit. The data is the time limit in seconds.
     TimeLimit(f64),
+    /// The memory limit was hit. The data is the estimated heap size
+    /// of the egraph in bytes.
+    MemoryLimit(usize),
+    /// The flag given to [`with_cancellation`](Runner::with_cancellation())
+    /// was set.
+    Cancelled,
+    /// Every phase of the [`Schedule`] given to
+    /// [`run_schedule`](Runner::run_schedule()) ran to completion.
+    ScheduleComplete,
     /// Some other reason to stop.
     Other(String),
 }
@@ -232,18 +311,92 @@ pub enum StopReason {
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "serde-1", derive(serde::Serialize))]
 #[non_exhaustive]
-#[allow(missing_docs)]
 pub struct Report {
     /// The number of iterations this runner performed.
     pub iterations: usize,
+    /// Why the runner stopped.
     pub stop_reason: StopReason,
+    /// The number of enodes in the egraph at the end of the run.
     pub egraph_nodes: usize,
+    /// The number of eclasses in the egraph at the end of the run.
     pub egraph_classes: usize,
+    /// The size of the egraph's memo at the end of the run.
     pub memo_size: usize,
+    /// The number of rebuild iterations, summed across iterations.
     pub rebuilds: usize,
+    /// Seconds spent in all iterations.
     pub total_time: f64,
+    /// Seconds spent searching, summed across iterations.
     pub search_time: f64,
+    /// Seconds spent searching rules together in a [`PatternSet`], summed
+    /// across iterations.
+    /// This is part of `search_time`.
+    pub pattern_set_search_time: f64,
+    /// Seconds spent applying rules, summed across iterations.
     pub apply_time: f64,
+    /// Seconds spent rebuilding, summed across iterations.
+    pub rebuild_time: f64,
+    /// A summary of each phase of a [`Schedule`], in order.
+    /// This is empty if the runner was not run with a [`Schedule`].
+    pub phases: Vec<PhaseReport>,
+    /// Per-rule statistics, summed across all iterations.
+    pub rules: IndexMap<Symbol, RuleReport>,
+}
+
+/// Statistics about a single rewrite rule.
+///
+/// These are recorded per iteration in [`Iteration::rule_reports`]
+/// and summed across iterations in [`Report::rules`].
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde-1", derive(serde::Serialize))]
+#[non_exhaustive]
+pub struct RuleReport {
+    /// Seconds spent searching for this rule.
+    /// For rules searched together in a [`PatternSet`], this is their share
+    /// of [`Iteration::pattern_set_search_time`], split by their number of
+    /// matches plus one.
+    pub search_time: f64,
+    /// The number of matches (substitutions) found by searching.
+    pub matches: usize,
+    /// The number of matches that were _newly_ applied.
+    pub applied: usize,
+    /// The number of enodes added to the egraph while applying this rule,
+    /// measured before rebuilding.
+    pub nodes_added: usize,
+}
+
+impl RuleReport {
+    fn add(&mut self, other: &Self) {
+        self.search_time += other.search_time;
+        self.matches += other.matches;
+        self.applied += other.applied;
+        self.nodes_added += other.nodes_added;
+    }
+}
+
+/// A summary of one phase of a [`Schedule`] run.
+///
+/// A phase is a maximal stretch of consecutive [`Iteration`]s that
+/// ran the same named rule set.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde-1", derive(serde::Serialize))]
+#[non_exhaustive]
+pub struct PhaseReport {
+    /// The name of the rule set run during this phase.
+    pub rule_set: Symbol,
+    /// The index of the first [`Iteration`] of this phase.
+    pub first_iteration: usize,
+    /// The number of iterations in this phase.
+    pub iterations: usize,
+    /// The number of enodes in the egraph at the start of this phase.
+    pub egraph_nodes: usize,
+    /// Seconds spent in the iterations of this phase.
+    pub total_time: f64,
+    /// Seconds spent searching in this phase.
+    pub search_time: f64,
+    /// Seconds spent applying rules in this phase.
+    pub apply_time: f64,
+    /// Seconds spent rebuilding in this phase.
     pub rebuild_time: f64,
 }
 
@@ -258,8 +411,28 @@ impl std::fmt::Display for Report {
         writeln!(f, "  Rebuilds: {}", self.rebuilds)?;
         writeln!(f, "  Total time: {}", self.total_time)?;
         writeln!(f, "    Search:  ({:.2}) {}", self.search_time / self.total_time, self.search_time)?;
+        if self.pattern_set_search_time > 0.0 {
+            writeln!(f, "      Pattern set: {}", self.pattern_set_search_time)?;
+        }
         writeln!(f, "    Apply:   ({:.2}) {}", self.apply_time / self.total_time, self.apply_time)?;
         writeln!(f, "    Rebuild: ({:.2}) {}", self.rebuild_time / self.total_time, self.rebuild_time)?;
+        if !self.phases.is_empty() {
+            writeln!(f, "  Phases:")?;
+            for p in &self.phases {
+                writeln!(f, "    {}: {} iterations starting at {}, {} nodes at start, {} total time",
+                         p.rule_set, p.iterations, p.first_iteration, p.egraph_nodes, p.total_time)?;
+            }
+        }
+        if !self.rules.is_empty() {
+            let mut rules: Vec<_> = self.rules.iter().collect();
+            rules.sort_by(|(_, a), (_, b)| b.search_time.partial_cmp(&a.search_time).unwrap());
+            let width = rules.iter().map(|(name, _)| name.as_str().len()).max().unwrap_or(0).max(4);
+            writeln!(f, "  Rules (sorted by search time):")?;
+            writeln!(f, "    {:<width$}  {:>12}  {:>10}  {:>10}  {:>11}", "Rule", "Search time", "Matches", "Applied", "Nodes added", width = width)?;
+            for (name, s) in rules {
+                writeln!(f, "    {:<width$}  {:>12.6}  {:>10}  {:>10}  {:>11}", name.as_str(), s.search_time, s.matches, s.applied, s.nodes_added, width = width)?;
+            }
+        }
         Ok(())
     }
 }
@@ -284,10 +457,20 @@ pub struct Iteration<IterData> {
     /// A map from rule name to number of times it was _newly_ applied
     /// in this iteration.
     pub applied: IndexMap<Symbol, usize>,
+    /// A map from rule name to search and apply statistics for that rule
+    /// in this iteration.
+    /// Unlike [`applied`](Iteration::applied), this has an entry for every
+    /// rule that was searched, even if it found nothing.
+    pub rule_reports: IndexMap<Symbol, RuleReport>,
     /// Seconds spent running hooks.
     pub hook_time: f64,
     /// Seconds spent searching in this iteration.
     pub search_time: f64,
+    /// Seconds spent searching rules together in a [`PatternSet`] in this
+    /// iteration, see [`RewriteScheduler::can_search_together`].
+    /// This is part of [`search_time`](Iteration::search_time), and is split
+    /// among the [`RuleReport::search_time`]s of the rules searched together.
+    pub pattern_set_search_time: f64,
     /// Seconds spent applying rules in this iteration.
     pub apply_time: f64,
     /// Seconds spent [`rebuild`](EGraph::rebuild())ing
@@ -301,10 +484,110 @@ pub struct Iteration<IterData> {
     pub n_rebuilds: usize,
     /// If the runner stopped on this iterations, this is the reason
     pub stop_reason: Option<StopReason>,
+    /// The named rule set this iteration ran, if it was run as part of a
+    /// [`Schedule`].
+    pub rule_set: Option<Symbol>,
 }
 
 type RunnerResult<T> = std::result::Result<T, StopReason>;
 
+/// Finds a small subset of `rules` that still proves `start` equal to `end`.
+///
+/// Each attempt runs a fresh runner from `make_runner`, with explanations
+/// enabled, on `start` and `end`, stopping as soon as they are equal.
+/// `make_runner` should return a runner with an empty egraph, but can set
+/// limits, the analysis, the scheduler, and so on.
+///
+/// First, the rules are restricted to those used in the explanation found
+/// with all of `rules`.
+/// Then rules are greedily removed one at a time, least used first,
+/// keeping each removal that still proves the equivalence and dropping
+/// any rule the new proof no longer uses.
+/// The result is minimal in the sense that removing any rule still in it
+/// failed to prove the equivalence when that rule was tried,
+/// but it is not necessarily the smallest such subset.
+///
+/// Returns `None` if the equivalence cannot be proven with all of `rules`.
+/// The returned rules are in the same order as `rules`.
+///
+/// # Example
+/// ```
+/// # use egg::*;
+/// let rules: &[Rewrite<SymbolLang, ()>] = &[
+///     rewrite!("commute-add"; "(+ ?a ?b)" => "(+ ?b ?a)"),
+///     rewrite!("commute-mul"; "(* ?a ?b)" => "(* ?b ?a)"),
+///     rewrite!("add-0"; "(+ ?a 0)" => "?a"),
+///     rewrite!("mul-1"; "(* ?a 1)" => "?a"),
+/// ];
+///
+/// let start = "(+ 0 (* x 1))".parse().unwrap();
+/// let end = "x".parse().unwrap();
+/// let subset = minimal_rule_subset(&start, &end, rules, Runner::default).unwrap();
+/// let names: Vec<_> = subset.iter().map(|rw| rw.name.as_str()).collect();
+/// assert_eq!(names, ["commute-add", "add-0", "mul-1"]);
+/// ```
+pub fn minimal_rule_subset<'a, L, N, IterData>(
+    start: &RecExpr<L>,
+    end: &RecExpr<L>,
+    rules: &'a [Rewrite<L, N>],
+    mut make_runner: impl FnMut() -> Runner<L, N, IterData>,
+) -> Option<Vec<&'a Rewrite<L, N>>>
+where
+    L: Language,
+    N: Analysis<L>,
+    IterData: IterationData<L, N>,
+{
+    let mut prove = |rules: &[&Rewrite<L, N>]| -> Option<IndexMap<Symbol, usize>> {
+        let mut runner = make_runner()
+            .with_explanations_enabled()
+            .with_expr(start)
+            .with_expr(end)
+            .with_hook(|runner| {
+                if runner.egraph.find(runner.roots[0]) == runner.egraph.find(runner.roots[1]) {
+                    Err("proved".into())
+                } else {
+                    Ok(())
+                }
+            })
+            .run(rules.iter().copied());
+        if runner.egraph.find(runner.roots[0]) == runner.egraph.find(runner.roots[1]) {
+            Some(runner.explain_equivalence(start, end).rule_counts())
+        } else {
+            None
+        }
+    };
+
+    let all: Vec<&Rewrite<L, N>> = rules.iter().collect();
+    let counts = prove(&all)?;
+    let mut subset: Vec<&Rewrite<L, N>> = all
+        .into_iter()
+        .filter(|rw| counts.contains_key(&rw.name))
+        .collect();
+    subset.sort_by_key(|rw| counts[&rw.name]);
+
+    let mut i = 0;
+    while i < subset.len() {
+        let mut without = subset.clone();
+        without.remove(i);
+        match prove(&without) {
+            Some(counts) => {
+                subset = without
+                    .into_iter()
+                    .filter(|rw| counts.contains_key(&rw.name))
+                    .collect()
+            }
+            None => i += 1,
+        }
+    }
+
+    Some(
+        rules
+            .iter()
+            .filter(|rw| subset.iter().any(|s| std::ptr::eq(*s, *rw)))
+            .collect(),
+    )
+}
+
 impl<L, N, IterData> Runner<L, N, IterData>
 where
     L: Language,
@@ -317,15 +600,21 @@ where
             iter_limit: 30,
             node_limit: 10_000,
             time_limit: Duration::from_secs(5),
+            memory_limit: usize::MAX,
+            cancellation: None,
 
             egraph: EGraph::new(analysis),
             roots: vec![],
             iterations: vec![],
             stop_reason: None,
             hooks: vec![],
+            rule_sets: Default::default(),
+            #[cfg(feature = "reports")]
+            profiler: None,
 
             start_time: None,
             scheduler: Box::new(BackoffScheduler::default()),
+            pattern_sets: Default::default(),
         }
     }
 
@@ -344,6 +633,72 @@ where
         Self { time_limit, ..self }
     }
 
+    /// Sets the limit on the estimated heap size of the egraph, in bytes.
+    /// Default: no limit
+    ///
+    /// The size is estimated with [`EGraph::estimated_heap_size`],
+    /// which walks every eclass, so setting this limit makes each limit
+    /// check a bit more expensive.
+    pub fn with_memory_limit(self, memory_limit: usize) -> Self {
+        Self {
+            memory_limit,
+            ..self
+        }
+    }
+
+    /// Sets a flag that can be used to cancel this runner, possibly from
+    /// another thread.
+    ///
+    /// Once the flag is set to `true`, the runner stops searching and
+    /// applying rules as soon as it can, rebuilds the egraph, and stops
+    /// with [`StopReason::Cancelled`].
+    /// The flag is checked with the other limits, and also between eclasses
+    /// during searches and applications (see [`EGraph::is_cancelled`]),
+    /// so a single long search doesn't hold up the cancellation.
+    ///
+    /// # Example
+    /// ```
+    /// # use egg::*;
+    /// use std::sync::{atomic::{AtomicBool, Ordering}, Arc};
+    ///
+    /// let rules: &[Rewrite<SymbolLang, ()>] = &[
+    ///     rewrite!("commute-add"; "(+ ?a ?b)" => "(+ ?b ?a)"),
+    ///     rewrite!("assoc-add"; "(+ ?a (+ ?b ?c))" => "(+ (+ ?a ?b) ?c)"),
+    /// ];
+    ///
+    /// let cancel = Arc::new(AtomicBool::new(false));
+    /// let handle = cancel.clone();
+    /// // e.g. the client disconnected
+    /// std::thread::spawn(move || handle.store(true, Ordering::Relaxed))
+    ///     .join()
+    ///     .unwrap();
+    ///
+    /// let runner = Runner::<SymbolLang, ()>::default()
+    ///     .with_expr(&"(+ 1 (+ 2 (+ 3 4)))".parse().unwrap())
+    ///     .with_cancellation(cancel)
+    ///     .run(rules);
+    /// assert!(matches!(runner.stop_reason, Some(StopReason::Cancelled)));
+    /// assert!(runner.egraph.clean);
+    /// ```
+    pub fn with_cancellation(self, flag: Arc<AtomicBool>) -> Self {
+        Self {
+            cancellation: Some(flag),
+            ..self
+        }
+    }
+
+    /// Records a [`Profiler`] trace of the run in
+    /// [`profiler`](Runner::profiler).
+    /// See [`Profiler`] for details.
+    #[cfg(feature = "reports")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "reports")))]
+    pub fn with_profiler(self) -> Self {
+        Self {
+            profiler: Some(Profiler::new()),
+            ..self
+        }
+    }
+
     /// Add a hook to instrument or modify the behavior of a [`Runner`].
     /// Each hook will run at the beginning of each iteration, i.e. before
     /// all the rewrites.
@@ -383,6 +738,27 @@ where
         Self { scheduler, ..self }
     }
 
+    /// Makes the next search with a [`PatternSet`] recompile its patterns
+    /// for the current egraph, see [`OpStats`].
+    ///
+    /// The runner compiles the rules it searches together once, and only
+    /// recompiles them when the number of eclasses has doubled or halved
+    /// since, see [`RewriteScheduler::can_search_together`].
+    /// Call this, for example from a hook, if the egraph changed in a way
+    /// that makes the old join order slow.
+    pub fn recompile_pattern_sets(&mut self) {
+        self.pattern_sets.clear();
+    }
+
+    /// Register a named rule set for use in a [`Schedule`].
+    ///
+    /// Registering a rule set under an existing name replaces it.
+    /// See [`run_schedule`](Runner::run_schedule()).
+    pub fn with_rule_set(mut self, name: impl Into<Symbol>, rules: Vec<Rewrite<L, N>>) -> Self {
+        self.rule_sets.insert(name.into(), rules);
+        self
+    }
+
     /// Add an expression to the egraph to be run.
     ///
     /// The eclass id of this addition will be recorded in the
@@ -412,6 +788,7 @@ where
         let rules: Vec<&Rewrite<L, N>> = rules.into_iter().collect();
         check_rules(&rules);
         self.egraph.rebuild();
+        self.egraph.cancellation = self.cancellation.clone();
         loop {
             let iter = self.run_one(&rules);
             self.iterations.push(iter);
@@ -423,12 +800,110 @@ where
                 break;
             }
         }
+        self.egraph.cancellation = None;
 
         assert!(!self.iterations.is_empty());
         assert!(self.stop_reason.is_some());
         self
     }
 
+    /// Run this `Runner` according to the given [`Schedule`] over the
+    /// rule sets registered with
+    /// [`with_rule_set`](Runner::with_rule_set()).
+    ///
+    /// Each step of the schedule runs one [`Iteration`] of a single
+    /// rule set; those iterations are tagged with
+    /// [`rule_set`](Iteration::rule_set) and summarized per phase in the
+    /// [`Report`].
+    /// The limits of the `Runner` apply to the whole schedule.
+    /// If the schedule finishes before any limit is hit, the runner stops
+    /// with [`StopReason::ScheduleComplete`].
+    ///
+    /// This panics if the schedule refers to a rule set that hasn't been
+    /// registered.
+    pub fn run_schedule(mut self, schedule: &Schedule<L, N>) -> Self {
+        schedule.for_each_rule_set(&mut |name| {
+            if !self.rule_sets.contains_key(&name) {
+                panic!("Schedule refers to unknown rule set {}", name);
+            }
+        });
+        for rules in self.rule_sets.values() {
+            check_rules(&rules.iter().collect::<Vec<_>>());
+        }
+
+        self.egraph.rebuild();
+        self.try_start();
+        self.egraph.cancellation = self.cancellation.clone();
+        let stop_reason = match self.run_schedule_step(schedule) {
+            Ok(_) => StopReason::ScheduleComplete,
+            Err(stop_reason) => stop_reason,
+        };
+        self.egraph.cancellation = None;
+
+        info!("Stopping: {:?}", stop_reason);
+        if let Some(iter) = self.iterations.last_mut() {
+            iter.stop_reason = Some(stop_reason.clone());
+        }
+        self.stop_reason = Some(stop_reason);
+        self
+    }
+
+    /// Runs one step of a schedule, returning whether it changed the egraph.
+    fn run_schedule_step(&mut self, schedule: &Schedule<L, N>) -> RunnerResult<bool> {
+        match schedule {
+            Schedule::Run(name) => {
+                // rewrites are cheap to clone, and this leaves the rule sets
+                // in place for hooks and the scheduler
+                let rule_set = self.rule_sets[name].clone();
+                let rules: Vec<&Rewrite<L, N>> = rule_set.iter().collect();
+                let mut iter = self.run_one(&rules);
+
+                iter.rule_set = Some(*name);
+                let result = match iter.stop_reason.take() {
+                    None => Ok(true),
+                    // a saturated rule set only ends this phase
+                    Some(StopReason::Saturated) => Ok(false),
+                    Some(stop_reason) => {
+                        iter.stop_reason = Some(stop_reason.clone());
+                        Err(stop_reason)
+                    }
+                };
+                self.iterations.push(iter);
+                let changed = result?;
+                self.check_limits()?;
+                Ok(changed)
+            }
+            Schedule::Seq(schedules) => {
+                let mut changed = false;
+                for s in schedules {
+                    changed |= self.run_schedule_step(s)?;
+                }
+                Ok(changed)
+            }
+            Schedule::Repeat(n, s) => {
+                let mut changed = false;
+                for _ in 0..*n {
+                    changed |= self.run_schedule_step(s)?;
+                }
+                Ok(changed)
+            }
+            Schedule::Saturate(s) => {
+                let mut changed = false;
+                while self.run_schedule_step(s)? {
+                    changed = true;
+                }
+                Ok(changed)
+            }
+            Schedule::Until(s, pred) => {
+                let mut changed = false;
+                while !pred(&self.egraph) && self.run_schedule_step(s)? {
+                    changed = true;
+                }
+                Ok(changed)
+            }
+        }
+    }
+
     /// Enable explanations for this runner's egraph.
     /// This allows the runner to explain why two expressions are
     /// equivalent with the [`explain_equivalence`](Runner::explain_equivalence) function.
@@ -462,6 +937,42 @@ where
         self.egraph.explain_equivalence(left, right)
     }
 
+    /// Reports which rules were used to prove the expression added as `root`
+    /// equal to `best`, typically the result of extracting from `root`.
+    ///
+    /// This explains the equivalence and counts the rule uses with
+    /// [`Explanation::rule_counts`].
+    /// It requires explanations to be enabled
+    /// (see [`with_explanations_enabled`](Runner::with_explanations_enabled())).
+    /// To find a small set of rules that still proves the equivalence,
+    /// see [`minimal_rule_subset`].
+    ///
+    /// # Example
+    /// ```
+    /// # use egg::*;
+    /// let rules: &[Rewrite<SymbolLang, ()>] = &[
+    ///     rewrite!("commute-add"; "(+ ?a ?b)" => "(+ ?b ?a)"),
+    ///     rewrite!("add-0"; "(+ ?a 0)" => "?a"),
+    ///     rewrite!("mul-1"; "(* ?a 1)" => "?a"),
+    /// ];
+    ///
+    /// let mut runner = Runner::default()
+    ///     .with_explanations_enabled()
+    ///     .with_expr(&"(+ 0 x)".parse().unwrap())
+    ///     .run(rules);
+    /// let root = runner.roots[0];
+    /// let (_, best) = Extractor::new(&runner.egraph, AstSize).find_best(root);
+    ///
+    /// let counts = runner.rule_contributions(root, &best);
+    /// assert_eq!(counts.get(&Symbol::from("commute-add")), Some(&1));
+    /// assert_eq!(counts.get(&Symbol::from("add-0")), Some(&1));
+    /// assert_eq!(counts.get(&Symbol::from("mul-1")), None);
+    /// ```
+    pub fn rule_contributions(&mut self, root: Id, best: &RecExpr<L>) -> IndexMap<Symbol, usize> {
+        let start = self.egraph.id_to_expr(root);
+        self.egraph.explain_equivalence(&start, best).rule_counts()
+    }
+
     /// Calls [`EGraph::explain_existance`](EGraph::explain_existance()).
     pub fn explain_existance(&mut self, expr: &RecExpr<L>) -> Explanation<L> {
         self.egraph.explain_existance(expr)
@@ -501,10 +1012,57 @@ where
             memo_size: self.egraph.total_size(),
             rebuilds: self.iterations.iter().map(|i| i.n_rebuilds).sum(),
             search_time: self.iterations.iter().map(|i| i.search_time).sum(),
+            pattern_set_search_time: self
+                .iterations
+                .iter()
+                .map(|i| i.pattern_set_search_time)
+                .sum(),
             apply_time: self.iterations.iter().map(|i| i.apply_time).sum(),
             rebuild_time: self.iterations.iter().map(|i| i.rebuild_time).sum(),
             total_time: self.iterations.iter().map(|i| i.total_time).sum(),
+            phases: self.phase_reports(),
+            rules: self.rule_summaries(),
+        }
+    }
+
+    fn rule_summaries(&self) -> IndexMap<Symbol, RuleReport> {
+        let mut rules: IndexMap<Symbol, RuleReport> = IndexMap::default();
+        for iter in &self.iterations {
+            for (name, stats) in &iter.rule_reports {
+                rules.entry(*name).or_default().add(stats);
+            }
+        }
+        rules
+    }
+
+    fn phase_reports(&self) -> Vec<PhaseReport> {
+        let mut phases: Vec<PhaseReport> = vec![];
+        for (i, iter) in self.iterations.iter().enumerate() {
+            let rule_set = match iter.rule_set {
+                Some(rule_set) => rule_set,
+                None => continue,
+            };
+            match phases.last_mut() {
+                Some(p) if p.rule_set == rule_set && p.first_iteration + p.iterations == i => {
+                    p.iterations += 1;
+                    p.total_time += iter.total_time;
+                    p.search_time += iter.search_time;
+                    p.apply_time += iter.apply_time;
+                    p.rebuild_time += iter.rebuild_time;
+                }
+                _ => phases.push(PhaseReport {
+                    rule_set,
+                    first_iteration: i,
+                    iterations: 1,
+                    egraph_nodes: iter.egraph_nodes,
+                    total_time: iter.total_time,
+                    search_time: iter.search_time,
+                    apply_time: iter.apply_time,
+                    rebuild_time: iter.rebuild_time,
+                }),
+            }
         }
+        phases
     }
 
     fn run_one(&mut self, rules: &[&Rewrite<L, N>]) -> Iteration<IterData> {
@@ -512,6 +1070,8 @@ where
 
         info!("\nIteration {}", self.iterations.len());
 
+        #[cfg(feature = "reports")]
+        let iteration_time = Instant::now();
         self.try_start();
         let mut result = self.check_limits();
 
@@ -521,9 +1081,14 @@ where
         let hook_time = Instant::now();
         let mut hooks = std::mem::take(&mut self.hooks);
         result = result.and_then(|_| {
-            hooks
-                .iter_mut()
-                .try_for_each(|hook| hook(self).map_err(StopReason::Other))
+            hooks.iter_mut().enumerate().try_for_each(|(_j, hook)| {
+                #[cfg(feature = "reports")]
+                let hook_time = Instant::now();
+                let result = hook(self).map_err(StopReason::Other);
+                #[cfg(feature = "reports")]
+                self.profile(format!("hook {}", _j), "hook", hook_time, json!({}));
+                result
+            })
         });
         self.hooks = hooks;
         let hook_time = hook_time.elapsed().as_secs_f64();
@@ -538,10 +1103,49 @@ where
 
         let mut matches = Vec::new();
         let mut applied = IndexMap::default();
+        let mut rule_reports: IndexMap<Symbol, RuleReport> = IndexMap::default();
+        let mut shared_matches = vec![];
+        let mut pattern_set_search_time = 0.0;
+        if result.is_ok() && self.scheduler.can_search_together(i) {
+            let search_time = Instant::now();
+            shared_matches = self.search_together(rules);
+            pattern_set_search_time = search_time.elapsed().as_secs_f64();
+        }
+        // rules searched together share the set's time by their matches,
+        // plus one so rules without matches get some of it too
+        let shared_weight: usize = shared_matches
+            .iter()
+            .flatten()
+            .map(|ms| ms.iter().map(|m| m.substs.len()).sum::<usize>() + 1)
+            .sum();
         result = result.and_then(|_| {
-            rules.iter().try_for_each(|rw| {
-                let ms = self.scheduler.search_rewrite(i, &self.egraph, rw);
+            rules.iter().enumerate().try_for_each(|(j, rw)| {
+                let search_time = Instant::now();
+                let stats = rule_reports.entry(rw.name).or_default();
+                let shared = shared_matches.get_mut(j).and_then(Option::take);
+                let is_shared = shared.is_some();
+                let ms = match shared {
+                    Some(ms) => ms,
+                    None => {
+                        let ms = self.scheduler.search_rewrite(i, &self.egraph, rw);
+                        stats.search_time += search_time.elapsed().as_secs_f64();
+                        ms
+                    }
+                };
+                let n_matches = ms.iter().map(|m| m.substs.len()).sum::<usize>();
+                if is_shared {
+                    let share = (n_matches + 1) as f64 / shared_weight as f64;
+                    stats.search_time += pattern_set_search_time * share;
+                }
+                stats.matches += n_matches;
                 matches.push(ms);
+                #[cfg(feature = "reports")]
+                self.profile(
+                    rw.name.as_str(),
+                    "search",
+                    search_time,
+                    json!({ "matches": n_matches }),
+                );
                 self.check_limits()
             })
         });
@@ -556,7 +1160,21 @@ where
                 let total_matches: usize = ms.iter().map(|m| m.substs.len()).sum();
                 debug!("Applying {} {} times", rw.name, total_matches);
 
+                #[cfg(feature = "reports")]
+                let rule_apply_time = Instant::now();
+                let nodes_before = self.egraph.total_size();
                 let actually_matched = self.scheduler.apply_rewrite(i, &mut self.egraph, rw, ms);
+                let stats = rule_reports.entry(rw.name).or_default();
+                stats.applied += actually_matched;
+                let nodes_added = self.egraph.total_size() - nodes_before;
+                stats.nodes_added += nodes_added;
+                #[cfg(feature = "reports")]
+                self.profile(
+                    rw.name.as_str(),
+                    "apply",
+                    rule_apply_time,
+                    json!({ "applied": actually_matched, "nodes_added": nodes_added }),
+                );
                 if actually_matched > 0 {
                     if let Some(count) = applied.get_mut(&rw.name) {
                         *count += actually_matched;
@@ -578,6 +1196,13 @@ where
             debug_assert!(self.egraph.check_each_explain(rules));
         }
 
+        #[cfg(feature = "reports")]
+        self.profile(
+            "rebuild",
+            "rebuild",
+            rebuild_time,
+            json!({ "n_rebuilds": n_rebuilds }),
+        );
         let rebuild_time = rebuild_time.elapsed().as_secs_f64();
         info!("Rebuild time: {}", rebuild_time);
         info!(
@@ -600,26 +1225,115 @@ where
             result = result.and(Err(StopReason::Saturated))
         }
 
-        Iteration {
+        let iteration = Iteration {
             applied,
+            rule_reports,
             egraph_nodes,
             egraph_classes,
             hook_time,
             search_time,
+            pattern_set_search_time,
             apply_time,
             rebuild_time,
             n_rebuilds,
             data: IterData::make(self),
             total_time: start_time.elapsed().as_secs_f64(),
             stop_reason: result.err(),
+            rule_set: None,
+        };
+        #[cfg(feature = "reports")]
+        self.profile(
+            format!("iteration {}", i),
+            "iteration",
+            iteration_time,
+            json!({ "egraph_nodes": egraph_nodes, "egraph_classes": egraph_classes }),
+        );
+        iteration
+    }
+
+    #[cfg(feature = "reports")]
+    fn profile(
+        &mut self,
+        name: impl Into<String>,
+        cat: &'static str,
+        start: Instant,
+        args: serde_json::Value,
+    ) {
+        if let Some(profiler) = &mut self.profiler {
+            profiler.record(name, cat, start, args);
         }
     }
 
+    /// Searches the rules with [`Pattern`] searchers at once,
+    /// returning `None` for the other rules.
+    fn search_together<'a>(
+        &mut self,
+        rules: &[&'a Rewrite<L, N>],
+    ) -> Vec<Option<Vec<SearchMatches<'a, L>>>> {
+        #[cfg(feature = "reports")]
+        let search_time = Instant::now();
+        let patterns: Vec<Option<&'a Pattern<L>>> =
+            rules.iter().map(|rw| rw.searcher.get_pattern()).collect();
+        let names: Vec<Symbol> = rules
+            .iter()
+            .zip(&patterns)
+            .filter(|(_, p)| p.is_some())
+            .map(|(rw, _)| rw.name)
+            .collect();
+        let set: Vec<&'a Pattern<L>> = patterns.iter().flatten().copied().collect();
+
+        // only reorder the joins once the egraph has changed a lot
+        let n_classes = self.egraph.number_of_classes();
+        let stale = match self.pattern_sets.get(&names) {
+            Some(cached) => {
+                n_classes > 2 * cached.n_classes
+                    || 2 * n_classes < cached.n_classes
+                    || !cached.patterns.iter().eq(set.iter().map(|p| &p.ast))
+            }
+            None => true,
+        };
+        if stale {
+            let stats = OpStats::new(&self.egraph);
+            let cached = CachedSetProgram {
+                patterns: set.iter().map(|p| p.ast.clone()).collect(),
+                n_classes,
+                program: SetProgram::new(&set, Some(&stats)),
+            };
+            self.pattern_sets.insert(names.clone(), cached);
+        }
+        let program = &self.pattern_sets[&names].program;
+        let mut found = program
+            .search_with_limit(&set, &self.egraph, usize::MAX)
+            .into_iter();
+        let shared: Vec<_> = patterns
+            .iter()
+            .map(|p| p.and_then(|_| found.next()))
+            .collect();
+
+        #[cfg(feature = "reports")]
+        {
+            let size = program.size();
+            self.profile(
+                "pattern set",
+                "search",
+                search_time,
+                json!({ "patterns": set.len(), "size": size, "recompiled": stale }),
+            );
+        }
+        shared
+    }
+
     fn try_start(&mut self) {
         self.start_time.get_or_insert_with(Instant::now);
     }
 
     fn check_limits(&self) -> RunnerResult<()> {
+        if let Some(flag) = &self.cancellation {
+            if flag.load(Ordering::Relaxed) {
+                return Err(StopReason::Cancelled);
+            }
+        }
+
         let elapsed = self.start_time.unwrap().elapsed();
         if elapsed > self.time_limit {
             return Err(StopReason::TimeLimit(elapsed.as_secs_f64()));
@@ -634,6 +1348,13 @@ where
             return Err(StopReason::IterationLimit(self.iterations.len()));
         }
 
+        if self.memory_limit != usize::MAX {
+            let heap_size = self.egraph.estimated_heap_size();
+            if heap_size > self.memory_limit {
+                return Err(StopReason::MemoryLimit(heap_size));
+            }
+        }
+
         Ok(())
     }
 }
@@ -656,6 +1377,121 @@ fn check_rules<L, N>(rules: &[&Rewrite<L, N>]) {
     }
 }
 
+/** A declarative plan for running named rule sets in phases.
+
+A [`Schedule`] refers to rule sets registered on a [`Runner`] with
+[`with_rule_set`](Runner::with_rule_set()), and is run with
+[`run_schedule`](Runner::run_schedule()).
+All phases share the same [`Runner`] and [`EGraph`].
+
+The basic step, [`Schedule::Run`], runs one iteration of a rule set.
+Steps are combined with [`Schedule::seq`], [`repeat`](Schedule::repeat()),
+[`saturate`](Schedule::saturate()), and [`until`](Schedule::until()).
+A step "saturates" when it didn't change the egraph, which ends any
+enclosing [`saturate`](Schedule::saturate()) or
+[`until`](Schedule::until()).
+
+# Example
+```
+use egg::{*, rewrite as rw};
+
+let simplify: Vec<Rewrite<SymbolLang, ()>> = vec![
+    rw!("add-0"; "(+ ?a 0)" => "?a"),
+    rw!("mul-1"; "(* ?a 1)" => "?a"),
+];
+let expand: Vec<Rewrite<SymbolLang, ()>> = vec![
+    rw!("commute-add"; "(+ ?a ?b)" => "(+ ?b ?a)"),
+];
+
+// simplify to saturation, then expand for 3 iterations, then simplify again
+let schedule = Schedule::seq(vec![
+    Schedule::run("simplify").saturate(),
+    Schedule::run("expand").repeat(3),
+    Schedule::run("simplify").saturate(),
+]);
+
+let runner = Runner::default()
+    .with_expr(&"(+ 0 (* 1 x))".parse().unwrap())
+    .with_rule_set("simplify", simplify)
+    .with_rule_set("expand", expand)
+    .run_schedule(&schedule);
+
+assert!(matches!(runner.stop_reason, Some(StopReason::ScheduleComplete)));
+let phases = runner.report().phases;
+let names: Vec<&str> = phases.iter().map(|p| p.rule_set.as_str()).collect();
+assert_eq!(names, vec!["simplify", "expand", "simplify"]);
+assert_eq!(phases[1].iterations, 3);
+```
+*/
+pub enum Schedule<L: Language, N: Analysis<L>> {
+    /// Run one iteration of the named rule set.
+    Run(Symbol),
+    /// Run each schedule in order.
+    Seq(Vec<Schedule<L, N>>),
+    /// Run a schedule the given number of times.
+    Repeat(usize, Box<Schedule<L, N>>),
+    /// Run a schedule until it stops changing the egraph.
+    Saturate(Box<Schedule<L, N>>),
+    /// Run a schedule until the predicate holds on the egraph
+    /// (checked before each run), or until it stops changing the egraph.
+    #[allow(clippy::type_complexity)]
+    Until(Box<Schedule<L, N>>, Box<dyn Fn(&EGraph<L, N>) -> bool>),
+}
+
+impl<L: Language, N: Analysis<L>> Schedule<L, N> {
+    /// Run one iteration of the named rule set.
+    pub fn run(rule_set: impl Into<Symbol>) -> Self {
+        Schedule::Run(rule_set.into())
+    }
+
+    /// Run the given schedules in order.
+    pub fn seq(schedules: Vec<Self>) -> Self {
+        Schedule::Seq(schedules)
+    }
+
+    /// Run this schedule `n` times.
+    pub fn repeat(self, n: usize) -> Self {
+        Schedule::Repeat(n, Box::new(self))
+    }
+
+    /// Run this schedule until it stops changing the egraph.
+    pub fn saturate(self) -> Self {
+        Schedule::Saturate(Box::new(self))
+    }
+
+    /// Run this schedule until `pred` holds on the egraph,
+    /// or until it stops changing the egraph.
+    pub fn until(self, pred: impl Fn(&EGraph<L, N>) -> bool + 'static) -> Self {
+        Schedule::Until(Box::new(self), Box::new(pred))
+    }
+
+    fn for_each_rule_set(&self, f: &mut impl FnMut(Symbol)) {
+        match self {
+            Schedule::Run(name) => f(*name),
+            Schedule::Seq(schedules) => schedules.iter().for_each(|s| s.for_each_rule_set(f)),
+            Schedule::Repeat(_, s) | Schedule::Saturate(s) | Schedule::Until(s, _) => {
+                s.for_each_rule_set(f)
+            }
+        }
+    }
+}
+
+impl<L: Language, N: Analysis<L>> Debug for Schedule<L, N> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Schedule::Run(name) => f.debug_tuple("Run").field(name).finish(),
+            Schedule::Seq(schedules) => f.debug_tuple("Seq").field(schedules).finish(),
+            Schedule::Repeat(n, s) => f.debug_tuple("Repeat").field(n).field(s).finish(),
+            Schedule::Saturate(s) => f.debug_tuple("Saturate").field(s).finish(),
+            Schedule::Until(s, _) => f
+                .debug_tuple("Until")
+                .field(s)
+                .field(&format_args!("<dyn Fn ..>"))
+                .finish(),
+        }
+    }
+}
+
 /** A way to customize how a [`Runner`] runs [`Rewrite`]s.
 
 This gives you a way to prevent certain [`Rewrite`]s from exploding
@@ -678,6 +1514,23 @@ where
         true
     }
 
+    /// Whether the [`Runner`] may search all the rules with [`Pattern`]
+    /// searchers at once with a [`PatternSet`] this iteration,
+    /// instead of calling
+    /// [`search_rewrite`](RewriteScheduler::search_rewrite()) for each.
+    /// The runner compiles those patterns once per rule set, see
+    /// [`Runner::recompile_pattern_sets`].
+    /// The shared search time is recorded in
+    /// [`Iteration::pattern_set_search_time`] and split among the
+    /// [`RuleReport`]s of those rules.
+    ///
+    /// Only return `true` if `search_rewrite` would just call
+    /// [`Rewrite::search`] for those rules.
+    /// Default implementation returns `false`.
+    fn can_search_together(&mut self, iteration: usize) -> bool {
+        false
+    }
+
     /// A hook allowing you to customize rewrite searching behavior.
     /// Useful to implement rule management.
     ///
@@ -714,7 +1567,8 @@ where
 ///
 /// Using this is basically turning off rule scheduling.
 /// It uses the default implementation for all [`RewriteScheduler`]
-/// methods.
+/// methods, except that it lets the [`Runner`] search all the rules
+/// with [`Pattern`] searchers at once with a [`PatternSet`].
 ///
 /// This is not the default scheduler; choose it with the
 /// [`with_scheduler`](Runner::with_scheduler())
@@ -728,6 +1582,9 @@ where
     L: Language,
     N: Analysis<L>,
 {
+    fn can_search_together(&mut self, _iteration: usize) -> bool {
+        true
+    }
 }
 
 /// A [`RewriteScheduler`] that implements exponentional rule backoff.
@@ -930,3 +1787,244 @@ where
 {
     fn make(_: &Runner<L, N, Self>) -> Self {}
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{SymbolLang as S, *};
+
+    #[test]
+    fn schedule_until_and_limits() {
+        crate::init_logger();
+        let grow: Vec<Rewrite<S, ()>> = vec![
+            rewrite!("comm"; "(+ ?x ?y)" => "(+ ?y ?x)"),
+            rewrite!("assoc"; "(+ ?x (+ ?y ?z))" => "(+ (+ ?x ?y) ?z)"),
+        ];
+        let expr: RecExpr<S> = "(+ 1 (+ 2 (+ 3 (+ 4 5))))".parse().unwrap();
+
+        // stop growing once the egraph is big enough
+        let runner = Runner::default()
+            .with_expr(&expr)
+            .with_rule_set("grow", grow.clone())
+            .run_schedule(&Schedule::run("grow").until(|eg| eg.total_size() >= 30));
+        assert!(matches!(
+            runner.stop_reason,
+            Some(StopReason::ScheduleComplete)
+        ));
+        assert!(runner.egraph.total_size() >= 30);
+        assert!(runner
+            .iterations
+            .iter()
+            .all(|i| i.rule_set == Some("grow".into())));
+
+        // the runner's limits still apply to the whole schedule
+        let runner = Runner::default()
+            .with_expr(&expr)
+            .with_iter_limit(3)
+            .with_rule_set("grow", grow)
+            .run_schedule(&Schedule::run("grow").saturate());
+        assert!(matches!(
+            runner.stop_reason,
+            Some(StopReason::IterationLimit(3))
+        ));
+        assert_eq!(runner.report().phases.len(), 1);
+    }
+
+    #[test]
+    fn memory_limit() {
+        crate::init_logger();
+        let rules: Vec<Rewrite<S, ()>> = vec![
+            rewrite!("comm"; "(+ ?x ?y)" => "(+ ?y ?x)"),
+            rewrite!("assoc"; "(+ ?x (+ ?y ?z))" => "(+ (+ ?x ?y) ?z)"),
+        ];
+        let expr = "(+ 1 (+ 2 (+ 3 (+ 4 (+ 5 6)))))".parse().unwrap();
+        let runner = Runner::default()
+            .with_expr(&expr)
+            .with_memory_limit(100_000)
+            .run(&rules);
+        match runner.stop_reason {
+            Some(StopReason::MemoryLimit(size)) => assert!(size > 100_000),
+            reason => panic!("unexpected stop reason {:?}", reason),
+        }
+    }
+
+    #[test]
+    fn rule_reports() {
+        crate::init_logger();
+        let rules: Vec<Rewrite<S, ()>> = vec![
+            rewrite!("comm"; "(+ ?x ?y)" => "(+ ?y ?x)"),
+            rewrite!("assoc"; "(+ ?x (+ ?y ?z))" => "(+ (+ ?x ?y) ?z)"),
+            rewrite!("never"; "(* ?x 0)" => "0"),
+        ];
+        let expr = "(+ 1 (+ 2 3))".parse().unwrap();
+        let runner = Runner::default().with_expr(&expr).run(&rules);
+        assert!(matches!(runner.stop_reason, Some(StopReason::Saturated)));
+
+        let first = &runner.iterations[0].rule_reports;
+        assert_eq!(first.len(), 3);
+        assert_eq!(first[&Symbol::from("comm")].matches, 2);
+        assert_eq!(first[&Symbol::from("comm")].applied, 2);
+        assert_eq!(first[&Symbol::from("assoc")].matches, 1);
+        assert!(first[&Symbol::from("assoc")].nodes_added > 0);
+        let never = &first[&Symbol::from("never")];
+        assert_eq!((never.matches, never.applied, never.nodes_added), (0, 0, 0));
+
+        let report = runner.report();
+        for (name, stats) in &report.rules {
+            let applied: usize = runner
+                .iterations
+                .iter()
+                .map(|i| i.applied.get(name).copied().unwrap_or(0))
+                .sum();
+            assert_eq!(stats.applied, applied);
+        }
+        assert_eq!(report.rules[&Symbol::from("never")].matches, 0);
+        let text = report.to_string();
+        assert!(text.contains("Rules (sorted by search time)"));
+        assert!(text.contains("never"));
+    }
+
+    #[test]
+    fn hooks_see_rule_sets() {
+        crate::init_logger();
+        let rules: Vec<Rewrite<S, ()>> = vec![rewrite!("comm"; "(+ ?x ?y)" => "(+ ?y ?x)")];
+        let expr = "(+ 1 2)".parse().unwrap();
+        let runner = Runner::default()
+            .with_expr(&expr)
+            .with_rule_set("comm", rules)
+            .with_hook(|runner| match runner.rule_sets.get(&Symbol::from("comm")) {
+                Some(rules) if rules.len() == 1 => Ok(()),
+                _ => Err("missing rule set".into()),
+            })
+            .run_schedule(&Schedule::run("comm").saturate());
+        assert!(matches!(
+            runner.stop_reason,
+            Some(StopReason::ScheduleComplete)
+        ));
+    }
+
+    #[test]
+    fn pattern_set_cache() {
+        crate::init_logger();
+        let expr = "(+ (* a 1) 0)".parse().unwrap();
+        let mut runner = Runner::<S, ()>::default()
+            .with_expr(&expr)
+            .with_scheduler(SimpleScheduler)
+            .run(&[rewrite!("r"; "(+ ?x 0)" => "?x")]);
+        assert_eq!(runner.pattern_sets.len(), 1);
+
+        // a rule with the same name but another pattern is recompiled
+        runner.stop_reason = None;
+        let runner = runner.run(&[rewrite!("r"; "(* ?x 1)" => "?x")]);
+        let a = runner.egraph.lookup(S::leaf("a")).unwrap();
+        assert_eq!(runner.egraph.find(runner.roots[0]), a);
+        assert_eq!(runner.pattern_sets.len(), 1);
+
+        let mut runner = runner;
+        runner.recompile_pattern_sets();
+        assert!(runner.pattern_sets.is_empty());
+    }
+
+    #[cfg(feature = "reports")]
+    #[test]
+    fn profiler() {
+        crate::init_logger();
+        let rules: Vec<Rewrite<S, ()>> = vec![
+            rewrite!("comm"; "(+ ?x ?y)" => "(+ ?y ?x)"),
+            rewrite!("zero"; "(+ ?x 0)" => "?x"),
+        ];
+        let expr = "(+ 0 (+ 1 2))".parse().unwrap();
+        let runner = Runner::default()
+            .with_expr(&expr)
+            .with_hook(|_| Ok(()))
+            .with_profiler()
+            .run(&rules);
+
+        let n_iters = runner.iterations.len();
+        let events = runner.profiler.as_ref().unwrap().events();
+        let count = |cat: &str| events.iter().filter(|e| e.cat == cat).count();
+        assert_eq!(count("iteration"), n_iters);
+        assert_eq!(count("hook"), n_iters);
+        assert_eq!(count("rebuild"), n_iters);
+        assert_eq!(count("search"), 2 * n_iters);
+        assert_eq!(count("apply"), 2 * n_iters);
+
+        // every span is nested inside its iteration
+        let iters: Vec<_> = events.iter().filter(|e| e.cat == "iteration").collect();
+        for e in events {
+            assert!(iters
+                .iter()
+                .any(|it| it.ts <= e.ts && e.ts + e.dur <= it.ts + it.dur + 1e-3));
+        }
+
+        let mut json = vec![];
+        runner
+            .profiler
+            .as_ref()
+            .unwrap()
+            .write_chrome_trace(&mut json)
+            .unwrap();
+        let value: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(value["traceEvents"].as_array().unwrap().len(), events.len());
+        assert_eq!(value["traceEvents"][0]["ph"], "X");
+    }
+
+    #[test]
+    fn minimal_rule_subset_drops_unneeded_rules() {
+        crate::init_logger();
+        let rules: Vec<Rewrite<S, ()>> = vec![
+            rewrite!("comm"; "(+ ?x ?y)" => "(+ ?y ?x)"),
+            rewrite!("assoc"; "(+ ?x (+ ?y ?z))" => "(+ (+ ?x ?y) ?z)"),
+            rewrite!("zero"; "(+ ?x 0)" => "?x"),
+            rewrite!("double"; "(+ ?x ?x)" => "(* 2 ?x)"),
+        ];
+        let start = "(+ a (+ b 0))".parse().unwrap();
+        let end = "(+ a b)".parse().unwrap();
+        let subset = minimal_rule_subset(&start, &end, &rules, Runner::default).unwrap();
+        let names: Vec<_> = subset.iter().map(|rw| rw.name.as_str()).collect();
+        assert_eq!(names, ["zero"]);
+
+        let unprovable = "(+ a c)".parse().unwrap();
+        assert!(minimal_rule_subset(&start, &unprovable, &rules, || {
+            Runner::default().with_iter_limit(3)
+        })
+        .is_none());
+    }
+
+    #[test]
+    fn cancellation() {
+        use std::sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        };
+
+        crate::init_logger();
+        let rules: Vec<Rewrite<S, ()>> = vec![
+            rewrite!("comm"; "(+ ?x ?y)" => "(+ ?y ?x)"),
+            rewrite!("assoc"; "(+ ?x (+ ?y ?z))" => "(+ (+ ?x ?y) ?z)"),
+        ];
+        let expr = "(+ 1 (+ 2 (+ 3 (+ 4 (+ 5 6)))))".parse().unwrap();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let handle = cancel.clone();
+        let runner = Runner::default()
+            .with_expr(&expr)
+            .with_cancellation(cancel)
+            .with_hook(move |runner| {
+                if runner.iterations.len() == 2 {
+                    handle.store(true, Ordering::Relaxed);
+                }
+                Ok(())
+            })
+            .run(&rules);
+
+        assert!(matches!(runner.stop_reason, Some(StopReason::Cancelled)));
+        assert_eq!(runner.iterations.len(), 3);
+        assert!(runner.egraph.clean);
+        assert!(!runner.egraph.is_cancelled());
+    }
+
+    #[test]
+    #[should_panic(expected = "unknown rule set")]
+    fn schedule_unknown_rule_set() {
+        Runner::<S, ()>::default().run_schedule(&Schedule::run("missing"));
+    }
+}
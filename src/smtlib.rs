@@ -0,0 +1,331 @@
+/*!
+Conversion between [`RecExpr`]s and terms in the
+[SMT-LIB2](https://smtlib.cs.uiowa.edu/language.shtml) syntax.
+*/
+use std::fmt::{Display, Write};
+
+use symbolic_expressions::Sexp;
+
+use crate::*;
+
+impl<L: FromOp> RecExpr<L> {
+    /** Parses an SMT-LIB2 term.
+
+    Besides plain applications like `(bvadd x y)`, this understands:
+    - `let` bindings, which become shared subterms of the [`RecExpr`],
+    - indexed identifiers like `(_ bv8 32)` and `((_ extract 7 0) x)`,
+      whose operator is given to [`FromOp`] as the text of the identifier,
+      like `"(_ extract 7 0)"`,
+    - quoted symbols like `|a b|`, given to [`FromOp`] without the bars,
+    - string literals like `"a"`, given to [`FromOp`] with their quotes,
+    - comments starting with `;`.
+
+    Numerals, decimals, `true`, `false` and bit-vector literals like
+    `#b0101` or `#x1f` are leaves with that text as their operator.
+
+    # Example
+    ```
+    use egg::*;
+    let expr = RecExpr::<SymbolLang>::from_smtlib(
+        "(let ((y (bvadd x #x01))) (bvmul y ((_ zero_extend 0) y)))",
+    ).unwrap();
+    // the usual s-expression syntax quotes the indexed identifier
+    assert_eq!(
+        expr.to_string(),
+        r#"(bvmul (bvadd x #x01) ("(_ zero_extend 0)" (bvadd x #x01)))"#
+    );
+    // the let-bound term is shared
+    assert_eq!(expr.as_ref().len(), 5);
+    assert_eq!(expr.to_smtlib(), "(let ((_let_0 (bvadd x #x01))) (bvmul _let_0 ((_ zero_extend 0) _let_0)))");
+    ```
+    **/
+    pub fn from_smtlib(s: &str) -> Result<Self, RecExprParseError<L::Error>> {
+        let sexp = parse_sexp(s).map_err(RecExprParseError::BadSmtLib)?;
+        let mut expr = RecExpr::default();
+        let mut env = vec![];
+        smtlib_into(&sexp, &mut expr, &mut env)?;
+        Ok(expr)
+    }
+}
+
+impl<L: Language + Display> RecExpr<L> {
+    /// Prints this expression as an SMT-LIB2 term, the inverse of
+    /// [`from_smtlib`](RecExpr::from_smtlib()).
+    ///
+    /// Subterms with children that are used more than once are bound with
+    /// `let`, named `_let_0`, `_let_1` and so on.
+    /// Operators that aren't valid SMT-LIB symbols are printed as quoted
+    /// symbols like `|a b|`.
+    pub fn to_smtlib(&self) -> String {
+        let nodes = self.as_ref();
+        if nodes.is_empty() {
+            return "()".into();
+        }
+
+        let uses = self.use_counts();
+        let mut names: Vec<Option<String>> = vec![None; nodes.len()];
+        let mut bindings = vec![];
+        for (i, node) in nodes.iter().enumerate() {
+            if uses[i] > 1 && !node.is_leaf() {
+                let term = smtlib_term(nodes, i, &names);
+                let name = format!("_let_{}", bindings.len());
+                bindings.push((name.clone(), term));
+                names[i] = Some(name);
+            }
+        }
+
+        let mut out = String::new();
+        for (name, term) in &bindings {
+            write!(out, "(let (({} {})) ", name, term).unwrap();
+        }
+        out.push_str(&smtlib_term(nodes, nodes.len() - 1, &names));
+        for _ in &bindings {
+            out.push(')');
+        }
+        out
+    }
+}
+
+fn smtlib_term<L: Language + Display>(nodes: &[L], i: usize, names: &[Option<String>]) -> String {
+    let mut out = String::new();
+    smtlib_write(nodes, i, names, &mut out, true);
+    out
+}
+
+fn smtlib_write<L: Language + Display>(
+    nodes: &[L],
+    i: usize,
+    names: &[Option<String>],
+    out: &mut String,
+    is_root: bool,
+) {
+    if let (false, Some(name)) = (is_root, &names[i]) {
+        out.push_str(name);
+        return;
+    }
+    let node = &nodes[i];
+    let op = smtlib_symbol(&node.to_string());
+    if node.is_leaf() {
+        out.push_str(&op);
+    } else {
+        out.push('(');
+        out.push_str(&op);
+        for &child in node.children() {
+            out.push(' ');
+            smtlib_write(nodes, usize::from(child), names, out, false);
+        }
+        out.push(')');
+    }
+}
+
+/// Quotes `op` with bars if it isn't already a valid SMT-LIB token.
+fn smtlib_symbol(op: &str) -> String {
+    let is_special = |c: char| c.is_whitespace() || "()|;\"".contains(c);
+    let is_indexed = op.starts_with("(_ ") && op.ends_with(')');
+    let is_string = op.len() >= 2 && op.starts_with('"') && op.ends_with('"');
+    if op.is_empty() || (!is_indexed && !is_string && op.chars().any(is_special)) {
+        format!("|{}|", op)
+    } else {
+        op.into()
+    }
+}
+
+fn smtlib_into<L: FromOp>(
+    sexp: &Sexp,
+    expr: &mut RecExpr<L>,
+    env: &mut Vec<(String, Id)>,
+) -> Result<Id, RecExprParseError<L::Error>> {
+    use RecExprParseError::*;
+    let bad = |msg: &str, sexp: &Sexp| BadSmtLib(format!("{} in {}", msg, sexp));
+    let list = match sexp {
+        Sexp::Empty => return Err(EmptySexp),
+        Sexp::String(s) => {
+            if let Some((_, id)) = env.iter().rev().find(|(name, _)| name == s) {
+                return Ok(*id);
+            }
+            let node = L::from_op(s, vec![]).map_err(BadOp)?;
+            return Ok(expr.add(node));
+        }
+        Sexp::List(list) if list.is_empty() => return Err(EmptySexp),
+        Sexp::List(list) => list,
+    };
+
+    let (op, args) = match &list[0] {
+        Sexp::String(head) if head == "let" => {
+            let (bindings, body) = match &list[1..] {
+                [Sexp::List(bindings), body] => (bindings, body),
+                _ => return Err(bad("expected (let ((name term) ...) body)", sexp)),
+            };
+            // let binds in parallel, so evaluate all terms before binding
+            let mut bound = vec![];
+            for binding in bindings {
+                match binding {
+                    Sexp::List(pair) => match &pair[..] {
+                        [Sexp::String(name), term] => {
+                            bound.push((name.clone(), smtlib_into(term, expr, env)?))
+                        }
+                        _ => return Err(bad("expected a (name term) binding", binding)),
+                    },
+                    _ => return Err(bad("expected a (name term) binding", binding)),
+                }
+            }
+            let len = env.len();
+            env.extend(bound);
+            let id = smtlib_into(body, expr, env);
+            env.truncate(len);
+            return id;
+        }
+        // an indexed identifier like (_ bv8 32)
+        Sexp::String(head) if head == "_" => (indexed(sexp)?, &[][..]),
+        Sexp::String(head) => (head.clone(), &list[1..]),
+        // an indexed function like ((_ extract 7 0) x)
+        head @ Sexp::List(l) if matches!(l.first(), Some(Sexp::String(s)) if s == "_") => {
+            (indexed(head)?, &list[1..])
+        }
+        head => return Err(HeadList(head.clone())),
+    };
+
+    let arg_ids = args
+        .iter()
+        .map(|arg| smtlib_into(arg, expr, env))
+        .collect::<Result<Vec<Id>, _>>()?;
+    let node = L::from_op(&op, arg_ids).map_err(BadOp)?;
+    Ok(expr.add(node))
+}
+
+/// Returns the text of an indexed identifier like `(_ extract 7 0)`.
+fn indexed<E>(sexp: &Sexp) -> Result<String, RecExprParseError<E>> {
+    match sexp {
+        Sexp::List(list) if list.len() > 2 => {
+            let mut parts = vec![];
+            for part in list {
+                match part {
+                    Sexp::String(s) => parts.push(s.as_str()),
+                    _ => break,
+                }
+            }
+            if parts.len() == list.len() {
+                return Ok(format!("({})", parts.join(" ")));
+            }
+        }
+        _ => (),
+    }
+    Err(RecExprParseError::BadSmtLib(format!(
+        "expected an indexed identifier like (_ bv8 32), found {}",
+        sexp
+    )))
+}
+
+/// Parses a single s-expression in SMT-LIB2 lexical syntax.
+fn parse_sexp(s: &str) -> Result<Sexp, String> {
+    let mut chars = s.chars().peekable();
+    let mut stack: Vec<Vec<Sexp>> = vec![vec![]];
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => (),
+            ';' => while matches!(chars.next(), Some(c) if c != '\n') {},
+            '(' => stack.push(vec![]),
+            ')' => {
+                let list = stack.pop().unwrap();
+                match stack.last_mut() {
+                    Some(parent) => parent.push(if list.is_empty() {
+                        Sexp::Empty
+                    } else {
+                        Sexp::List(list)
+                    }),
+                    None => return Err("unexpected )".into()),
+                }
+            }
+            '|' => {
+                let mut symbol = String::new();
+                loop {
+                    match chars.next() {
+                        Some('|') => break,
+                        Some(c) => symbol.push(c),
+                        None => return Err("unterminated quoted symbol".into()),
+                    }
+                }
+                stack.last_mut().unwrap().push(Sexp::String(symbol));
+            }
+            '"' => {
+                // strings keep their quotes, and "" stands for a quote
+                let mut string = String::from('"');
+                loop {
+                    match chars.next() {
+                        Some('"') if chars.peek() == Some(&'"') => {
+                            chars.next();
+                            string.push_str("\"\"");
+                        }
+                        Some('"') => break,
+                        Some(c) => string.push(c),
+                        None => return Err("unterminated string literal".into()),
+                    }
+                }
+                string.push('"');
+                stack.last_mut().unwrap().push(Sexp::String(string));
+            }
+            c => {
+                let mut token = String::from(c);
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || "()|\";".contains(c) {
+                        break;
+                    }
+                    token.push(c);
+                    chars.next();
+                }
+                stack.last_mut().unwrap().push(Sexp::String(token));
+            }
+        }
+    }
+
+    let mut top = stack.pop().unwrap();
+    if !stack.is_empty() {
+        return Err("unclosed (".into());
+    }
+    match top.len() {
+        0 => Ok(Sexp::Empty),
+        1 => Ok(top.pop().unwrap()),
+        _ => Err("expected a single term".into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{SymbolLang as S, *};
+
+    #[test]
+    fn smtlib_round_trip() {
+        crate::init_logger();
+        let terms = [
+            "x",
+            "#b0101",
+            "(_ bv5 8)",
+            "(bvadd x (_ bv1 8))",
+            "((_ extract 7 0) (bvor #x1f y))",
+            "(ite true 1.5 (- 2))",
+            "(str.++ \"a \"\"b\"\"\" |odd symbol|)",
+            "(let ((_let_0 (+ x 1))) (let ((_let_1 (* _let_0 _let_0))) (f _let_1 _let_1 _let_0)))",
+        ];
+        for term in terms {
+            let expr = RecExpr::<S>::from_smtlib(term).unwrap();
+            assert_eq!(expr.to_smtlib(), term);
+        }
+    }
+
+    #[test]
+    fn smtlib_let() {
+        crate::init_logger();
+        // bindings are parallel and shadow outer ones
+        let expr = RecExpr::<S>::from_smtlib(
+            "(let ((x (f y)) (y x)) ; comment
+               (let ((x (g x))) (h x y)))",
+        )
+        .unwrap();
+        assert_eq!(expr.to_string(), "(h (g (f y)) x)");
+
+        let bad = ["()", "(let (x) x)", "((f x) y)", "(f x", "x y", "(_ bv8)"];
+        for term in bad {
+            assert!(RecExpr::<S>::from_smtlib(term).is_err(), "{}", term);
+        }
+    }
+}
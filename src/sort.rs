@@ -0,0 +1,216 @@
+use thiserror::Error;
+
+use crate::*;
+
+/// An error from sort-checking a [`PatternAst`], see [`PatternSorts`].
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum SortError {
+    /// A child of an enode has a different sort than the enode expects.
+    #[error("{node} expects child {index} to have sort {expected}, but it has sort {found}")]
+    Child {
+        /// The enode, in its `Debug` form.
+        node: String,
+        /// The position of the child.
+        index: usize,
+        /// The sort the enode expects.
+        expected: Symbol,
+        /// The sort of the child.
+        found: Symbol,
+    },
+    /// A variable is used at two different sorts.
+    #[error("{var} is used with sort {first} and with sort {second}")]
+    Var {
+        /// The variable.
+        var: Var,
+        /// The sort of the variable's first use.
+        first: Symbol,
+        /// The sort of the conflicting use.
+        second: Symbol,
+    },
+}
+
+/** The sorts inferred for the variables of one or more patterns.
+
+Sorts come from [`Language::sort`] and [`Language::child_sort`]; enodes
+and variables without a sort are compatible with any sort.
+A [`PatternSorts`] can check several patterns that share variables, like
+both sides of a rewrite, and [`Rewrite::new`] does just that.
+
+# Example
+```
+use egg::*;
+define_language! {
+    enum Lang {
+        #[sort(i32 <- i32, i32)] "+" = Add([Id; 2]),
+        #[sort(bool <- i32, i32)] "<" = Lt([Id; 2]),
+        #[sort(bool <- bool)] "not" = Not(Id),
+        Num(i32),
+    }
+}
+
+let lhs: PatternAst<Lang> = "(not (< ?a ?b))".parse().unwrap();
+let mut sorts = PatternSorts::infer(&lhs).unwrap();
+assert_eq!(sorts.root(), Some("bool".into()));
+assert_eq!(sorts.var("?a".parse().unwrap()), Some("i32".into()));
+
+// the sorts of the variables carry over to the next pattern
+let rhs: PatternAst<Lang> = "(< ?b (+ ?a 1))".parse().unwrap();
+assert_eq!(sorts.check(&rhs), Ok(Some("bool".into())));
+let bad: PatternAst<Lang> = "(not ?a)".parse().unwrap();
+assert!(sorts.check(&bad).is_err());
+
+// sort-incorrect rules are rejected
+let rw: Result<Rewrite<Lang, ()>, String> = Rewrite::new(
+    "bad",
+    "(+ ?a ?b)".parse::<Pattern<Lang>>().unwrap(),
+    "(< ?a ?b)".parse::<Pattern<Lang>>().unwrap(),
+);
+assert!(rw.is_err());
+```
+**/
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PatternSorts {
+    vars: IndexMap<Var, Symbol>,
+    root: Option<Symbol>,
+}
+
+impl PatternSorts {
+    /// Infers the sorts of the variables in `pattern`.
+    pub fn infer<L: Language>(pattern: &PatternAst<L>) -> Result<Self, SortError> {
+        let mut sorts = Self::default();
+        sorts.check(pattern)?;
+        Ok(sorts)
+    }
+
+    /// Checks that `pattern` is well-sorted given the variable sorts inferred
+    /// so far, inferring sorts for its new variables.
+    /// Returns the sort of the root of `pattern`, which [`root`](PatternSorts::root())
+    /// returns from then on.
+    pub fn check<L: Language>(
+        &mut self,
+        pattern: &PatternAst<L>,
+    ) -> Result<Option<Symbol>, SortError> {
+        let nodes = pattern.as_ref();
+        // the sorts of the enodes; variables are looked up in `self.vars`
+        let mut sorts: Vec<Option<Symbol>> = Vec::with_capacity(nodes.len());
+        for node in nodes {
+            let sort = match node {
+                ENodeOrVar::Var(_) => None,
+                ENodeOrVar::ENode(node) => {
+                    for (index, &child) in node.children().iter().enumerate() {
+                        let expected = match node.child_sort(index) {
+                            Some(expected) => expected,
+                            None => continue,
+                        };
+                        match &nodes[usize::from(child)] {
+                            ENodeOrVar::Var(v) if v.is_sequence() => (),
+                            ENodeOrVar::Var(v) => self.constrain(*v, expected)?,
+                            ENodeOrVar::ENode(_) => match sorts[usize::from(child)] {
+                                Some(found) if found != expected => {
+                                    return Err(SortError::Child {
+                                        node: format!("{:?}", node),
+                                        index,
+                                        expected,
+                                        found,
+                                    })
+                                }
+                                _ => (),
+                            },
+                        }
+                    }
+                    node.sort()
+                }
+            };
+            sorts.push(sort);
+        }
+
+        self.root = match nodes.last() {
+            Some(ENodeOrVar::Var(v)) => self.var(*v),
+            Some(ENodeOrVar::ENode(_)) => *sorts.last().unwrap(),
+            None => None,
+        };
+        Ok(self.root)
+    }
+
+    /// Returns the sort inferred for `var`, if any.
+    pub fn var(&self, var: Var) -> Option<Symbol> {
+        self.vars.get(&var).copied()
+    }
+
+    /// Returns the sort of the root of the last pattern checked, if any.
+    pub fn root(&self) -> Option<Symbol> {
+        self.root
+    }
+
+    fn constrain(&mut self, var: Var, sort: Symbol) -> Result<(), SortError> {
+        match self.vars.get(&var) {
+            Some(&first) if first != sort => Err(SortError::Var {
+                var,
+                first,
+                second: sort,
+            }),
+            Some(_) => Ok(()),
+            None => {
+                self.vars.insert(var, sort);
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    define_language! {
+        enum Typed {
+            #[sort(i32 <- i32, i32)] #[ac] "+" = Add([Id; 2]),
+            #[ac] #[sort(i32 <- i32, i32)] "*" = Mul([Id; 2]),
+            #[sort(bool <- i32, i32)] "<" = Lt([Id; 2]),
+            #[sort(i32 <- bool, i32, i32)] "if" = If([Id; 3]),
+            #[sort(bool)] "true" = True,
+            #[sort(i32)] Num(i32),
+            Var(Symbol),
+        }
+    }
+
+    #[test]
+    fn sorted_language() {
+        crate::init_logger();
+        let nodes: RecExpr<Typed> = "(if (< x 1) (+ x 2) (* 3 x))".parse().unwrap();
+        let sorts: Vec<Option<Symbol>> = nodes.as_ref().iter().map(|n| n.sort()).collect();
+        let (int, boolean) = (Some("i32".into()), Some("bool".into()));
+        assert_eq!(
+            sorts,
+            vec![None, int, boolean, None, int, int, int, None, int, int]
+        );
+        assert!(nodes.as_ref()[5].is_ac() && nodes.as_ref()[8].is_ac());
+        assert_eq!(nodes.as_ref()[9].child_sort(0), boolean);
+        assert_eq!(nodes.as_ref()[9].child_sort(3), None);
+
+        let rw = |lhs: &str, rhs: &str| -> Result<Rewrite<Typed, ()>, String> {
+            let lhs: Pattern<Typed> = lhs.parse().unwrap();
+            let rhs: Pattern<Typed> = rhs.parse().unwrap();
+            Rewrite::new("rw", lhs, rhs)
+        };
+        assert!(rw("(if true ?a ?b)", "?a").is_ok());
+        assert!(rw("(< ?a ?a)", "true").is_ok());
+        // the condition must be a bool
+        assert!(rw("(if ?c ?a ?b)", "(if (+ ?c 1) ?a ?b)").is_err());
+        // the sides have different sorts
+        assert!(rw("(+ ?a 0)", "(< ?a 0)").is_err());
+        assert!(rw("(if ?c ?a ?b)", "?c").is_err());
+        // a mismatched child that isn't a variable
+        assert!(rw("(+ ?a true)", "?a").is_err());
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "Tried to union eclass")]
+    fn union_different_sorts() {
+        let mut egraph = EGraph::<Typed, ()>::default();
+        let one = egraph.add(Typed::Num(1));
+        let t = egraph.add(Typed::True);
+        egraph.union(one, t);
+    }
+}
@@ -1,5 +1,8 @@
+use std::any::Any;
 use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::str::FromStr;
+use std::sync::Arc;
 
 use crate::*;
 use fmt::{Debug, Display, Formatter};
@@ -34,6 +37,24 @@ impl FromStr for Var {
     }
 }
 
+impl Var {
+    /// Returns true if this is a sequence variable like `?args...`,
+    /// which stands for any number of consecutive children of an enode.
+    pub fn is_sequence(&self) -> bool {
+        let s = self.0.as_str();
+        s.len() > 4 && s.ends_with("...")
+    }
+
+    /// Splits an annotated variable like `?x:const` into `?x` and `const`.
+    pub(crate) fn split_predicate(self) -> (Var, Option<Symbol>) {
+        let s = self.0.as_str();
+        match s.find(':') {
+            Some(i) if i > 1 && i + 1 < s.len() => (Var(s[..i].into()), Some(s[i + 1..].into())),
+            _ => (self, None),
+        }
+    }
+}
+
 impl Display for Var {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         Display::fmt(&self.0, f)
@@ -48,9 +69,54 @@ impl Debug for Var {
 
 /// A substitition mapping [`Var`]s to eclass [`Id`]s.
 ///
-#[derive(Default, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// Sequence variables (see [`Var::is_sequence`]) are mapped to a list of
+/// eclass [`Id`]s instead; use [`insert_sequence`](Subst::insert_sequence())
+/// and [`get_sequence`](Subst::get_sequence()) for those.
+///
+/// A variable can also be bound to a [`Value`], like the number in a
+/// constant eclass, with [`insert_value`](Subst::insert_value()) and
+/// [`get_value`](Subst::get_value()).
+/// [`Pattern`]s bind the values of annotated variables while matching,
+/// see [`Analysis::bind_value`].
+/// Values are assumed to follow from the [`Id`] bindings, so they are
+/// ignored when comparing and hashing `Subst`s.
+#[derive(Default, Clone)]
 pub struct Subst {
     pub(crate) vec: smallvec::SmallVec<[(Var, Id); 3]>,
+    pub(crate) seqs: Vec<(Var, Vec<Id>)>,
+    pub(crate) values: Vec<(Var, Value)>,
+}
+
+/// A value of any type bound to a [`Var`] in a [`Subst`].
+///
+/// Values are cheap to clone, since they are reference counted.
+///
+/// # Example
+/// ```
+/// use egg::*;
+/// let value = Value::new(42i64);
+/// assert_eq!(value.downcast_ref::<i64>(), Some(&42));
+/// assert_eq!(value.downcast_ref::<i32>(), None);
+/// ```
+#[derive(Clone)]
+pub struct Value(Arc<dyn Any + Send + Sync>);
+
+impl Value {
+    /// Wraps a value.
+    pub fn new<T: Any + Send + Sync>(value: T) -> Self {
+        Value(Arc::new(value))
+    }
+
+    /// Returns the value if it is a `T`.
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.0.downcast_ref()
+    }
+}
+
+impl Debug for Value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "Value(..)")
+    }
 }
 
 impl Subst {
@@ -58,6 +124,8 @@ impl Subst {
     pub fn with_capacity(capacity: usize) -> Self {
         Self {
             vec: smallvec::SmallVec::with_capacity(capacity),
+            seqs: vec![],
+            values: vec![],
         }
     }
 
@@ -79,6 +147,86 @@ impl Subst {
             .iter()
             .find_map(|(v, id)| if *v == var { Some(id) } else { None })
     }
+
+    /// Insert a sequence variable, returning the old `Id`s if present.
+    pub fn insert_sequence(&mut self, var: Var, ids: Vec<Id>) -> Option<Vec<Id>> {
+        for pair in &mut self.seqs {
+            if pair.0 == var {
+                return Some(std::mem::replace(&mut pair.1, ids));
+            }
+        }
+        self.seqs.push((var, ids));
+        None
+    }
+
+    /// Retrieve a sequence variable, returning `None` if not present.
+    pub fn get_sequence(&self, var: Var) -> Option<&[Id]> {
+        self.seqs
+            .iter()
+            .find_map(|(v, ids)| if *v == var { Some(&ids[..]) } else { None })
+    }
+
+    /// Binds `var` to a value, returning the old value if present.
+    ///
+    /// A variable can be bound to both an eclass and a value.
+    /// When a [`Pattern`] is instantiated, variables that are only bound
+    /// to a value are turned into enodes with [`Analysis::value_node`].
+    pub fn insert_value<T: Any + Send + Sync>(&mut self, var: Var, value: T) -> Option<Value> {
+        self.insert_raw_value(var, Value::new(value))
+    }
+
+    /// Like [`insert_value`](Subst::insert_value()), but for an already
+    /// wrapped [`Value`].
+    pub fn insert_raw_value(&mut self, var: Var, value: Value) -> Option<Value> {
+        for pair in &mut self.values {
+            if pair.0 == var {
+                return Some(std::mem::replace(&mut pair.1, value));
+            }
+        }
+        self.values.push((var, value));
+        None
+    }
+
+    /// Retrieve the value of `var`, returning `None` if it is not bound to
+    /// a value or the value is not a `T`.
+    pub fn get_value<T: Any>(&self, var: Var) -> Option<&T> {
+        self.get_raw_value(var)?.downcast_ref()
+    }
+
+    /// Retrieve the [`Value`] of `var`, returning `None` if not present.
+    pub fn get_raw_value(&self, var: Var) -> Option<&Value> {
+        self.values
+            .iter()
+            .find_map(|(v, value)| if *v == var { Some(value) } else { None })
+    }
+}
+
+// values are ignored, see the docs on `Subst`
+impl PartialEq for Subst {
+    fn eq(&self, other: &Self) -> bool {
+        self.vec == other.vec && self.seqs == other.seqs
+    }
+}
+
+impl Eq for Subst {}
+
+impl PartialOrd for Subst {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Subst {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (&self.vec, &self.seqs).cmp(&(&other.vec, &other.seqs))
+    }
+}
+
+impl Hash for Subst {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.vec.hash(state);
+        self.seqs.hash(state);
+    }
 }
 
 impl std::ops::Index<Var> for Subst {
@@ -103,6 +251,18 @@ impl Debug for Subst {
                 write!(f, ", ")?;
             }
         }
+        for (i, (var, ids)) in self.seqs.iter().enumerate() {
+            if len > 0 || i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}: {:?}", var, ids)?;
+        }
+        for (i, (var, value)) in self.values.iter().enumerate() {
+            if len > 0 || !self.seqs.is_empty() || i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}: {:?}", var, value)?;
+        }
         write!(f, "}}")
     }
 }
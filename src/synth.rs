@@ -0,0 +1,420 @@
+use std::fmt::Display;
+use std::hash::Hash;
+
+use log::*;
+
+use crate::*;
+
+/** Synthesizes [`Rewrite`]s by enumerating terms, in the style of
+[Ruler](https://dl.acm.org/doi/10.1145/3485496).
+
+The synthesizer enumerates patterns up to a given size, built from the
+given operators, constant leaves, and pattern variables.
+It evaluates each pattern with an [`Interpreter`] under several random
+assignments of the variables, and groups patterns with the same
+results (their "fingerprint").
+Patterns in the same group are probably equivalent, so each is paired
+with the smallest pattern of its group to form a candidate rewrite.
+
+Finally, [`synthesize`](Synthesizer::synthesize()) keeps only the
+candidates that a [`Runner`] can't already derive from the given rules
+plus the candidates kept so far, so the result is small.
+Candidates are only as good as the fingerprints, so it's a good idea to
+check the result with a [`RuleFuzzer`].
+
+To check derivability, pattern variables are turned into leaves by
+parsing their names (like `?a`) with [`FromOp`],
+so the language should have a variant that accepts them,
+like a [`Symbol`] variant in [`define_language!`].
+
+# Example
+```
+use egg::*;
+
+define_language! {
+    enum Arith {
+        Num(i64),
+        "+" = Add([Id; 2]),
+        Symbol(Symbol),
+    }
+}
+
+struct Eval;
+impl Interpreter<Arith> for Eval {
+    type Value = i64;
+    fn eval(&self, enode: &Arith, args: &[i64]) -> Option<i64> {
+        match enode {
+            Arith::Num(n) => Some(*n),
+            Arith::Add(_) => args[0].checked_add(args[1]),
+            Arith::Symbol(_) => None,
+        }
+    }
+    fn arbitrary(&self, rng: &mut FuzzRng) -> RecExpr<Arith> {
+        let mut expr = RecExpr::default();
+        expr.add(Arith::Num(rng.below(100) as i64));
+        expr
+    }
+}
+
+let synth = Synthesizer::new(Eval, vec![Arith::Add([Id::from(0); 2])])
+    .with_leaves(vec![Arith::Num(0)]);
+let rules: Vec<Rewrite<Arith, ()>> = synth.synthesize(&[], Runner::default);
+let names: Vec<_> = rules.iter().map(|rw| rw.name.as_str()).collect();
+assert!(names.contains(&"(+ ?a 0) => ?a"));
+assert!(names.contains(&"(+ ?b ?a) => (+ ?a ?b)"));
+```
+*/
+#[derive(Debug, Clone)]
+pub struct Synthesizer<L, I> {
+    interpreter: I,
+    operators: Vec<L>,
+    leaves: Vec<L>,
+    vars: Vec<Var>,
+    max_size: usize,
+    samples: usize,
+    seed: u64,
+}
+
+impl<L, I> Synthesizer<L, I>
+where
+    L: Language + Display + FromOp,
+    I: Interpreter<L>,
+    I::Value: Eq + Hash,
+{
+    /// Creates a synthesizer that builds terms from the given operators.
+    ///
+    /// The children of each operator are ignored; only its arity matters.
+    /// Operators with no children are treated as leaves.
+    pub fn new(interpreter: I, operators: Vec<L>) -> Self {
+        Self {
+            interpreter,
+            operators,
+            leaves: vec![],
+            vars: vec!["?a".parse().unwrap(), "?b".parse().unwrap()],
+            max_size: 3,
+            samples: 16,
+            seed: 0,
+        }
+    }
+
+    /// Sets the constant leaves, like `0` and `1`. Default: none
+    pub fn with_leaves(self, leaves: Vec<L>) -> Self {
+        Self { leaves, ..self }
+    }
+
+    /// Sets the pattern variables. Default: `?a` and `?b`
+    pub fn with_vars(self, vars: Vec<Var>) -> Self {
+        Self { vars, ..self }
+    }
+
+    /// Sets the size (number of nodes) of the largest term to enumerate. Default: 3
+    pub fn with_max_size(self, max_size: usize) -> Self {
+        Self { max_size, ..self }
+    }
+
+    /// Sets how many random variable assignments make up a fingerprint. Default: 16
+    pub fn with_samples(self, samples: usize) -> Self {
+        Self { samples, ..self }
+    }
+
+    /// Sets the seed for the random number generator. Default: 0
+    pub fn with_seed(self, seed: u64) -> Self {
+        Self { seed, ..self }
+    }
+
+    /// Enumerates all patterns up to the maximum size, smallest first.
+    pub fn enumerate(&self) -> Vec<PatternAst<L>> {
+        let mut by_size: Vec<Vec<PatternAst<L>>> = vec![vec![]; self.max_size + 1];
+        if self.max_size == 0 {
+            return vec![];
+        }
+
+        for &v in &self.vars {
+            let mut ast = PatternAst::default();
+            ast.add(ENodeOrVar::Var(v));
+            by_size[1].push(ast);
+        }
+        for leaf in self
+            .leaves
+            .iter()
+            .chain(self.operators.iter().filter(|op| op.is_leaf()))
+        {
+            let mut ast = PatternAst::default();
+            ast.add(ENodeOrVar::ENode(leaf.clone()));
+            by_size[1].push(ast);
+        }
+
+        for size in 2..=self.max_size {
+            let mut asts = vec![];
+            for op in self.operators.iter().filter(|op| !op.is_leaf()) {
+                for sizes in compositions(size - 1, op.len()) {
+                    let mut combos: Vec<Vec<&PatternAst<L>>> = vec![vec![]];
+                    for &s in &sizes {
+                        combos = combos
+                            .iter()
+                            .flat_map(|combo| {
+                                by_size[s].iter().map(move |child| {
+                                    let mut combo = combo.clone();
+                                    combo.push(child);
+                                    combo
+                                })
+                            })
+                            .collect();
+                    }
+                    asts.extend(combos.iter().map(|children| build(op, children)));
+                }
+            }
+            by_size[size] = asts;
+        }
+
+        by_size.into_iter().flatten().collect()
+    }
+
+    /// Proposes candidate rewrites by grouping enumerated patterns
+    /// by fingerprint.
+    ///
+    /// Each pattern is rewritten to the smallest pattern in its group;
+    /// pairs whose right-hand side uses variables the left-hand side
+    /// doesn't are flipped, or dropped if that doesn't help either.
+    /// Patterns that are undefined under every sample are ignored.
+    /// Candidates with more variables come first, then smaller ones.
+    pub fn candidates(&self) -> Vec<(Pattern<L>, Pattern<L>)> {
+        let mut rng = FuzzRng::new(self.seed);
+        let samples: Vec<Vec<(Var, Option<I::Value>)>> = (0..self.samples)
+            .map(|_| {
+                self.vars
+                    .iter()
+                    .map(|&v| {
+                        let term = self.interpreter.arbitrary(&mut rng);
+                        (v, self.interpreter.eval_expr(&term))
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let mut groups: IndexMap<Vec<Option<I::Value>>, Vec<PatternAst<L>>> = Default::default();
+        for ast in self.enumerate() {
+            let fingerprint: Vec<Option<I::Value>> = samples
+                .iter()
+                .map(|env| self.eval_pattern(&ast, env))
+                .collect();
+            if fingerprint.iter().all(Option::is_none) {
+                continue;
+            }
+            groups.entry(fingerprint).or_default().push(ast);
+        }
+
+        let mut candidates = vec![];
+        for asts in groups.values() {
+            let smallest = Pattern::new(asts[0].clone());
+            for ast in &asts[1..] {
+                let other = Pattern::new(ast.clone());
+                let (small_vars, other_vars) = (smallest.vars(), other.vars());
+                if small_vars.iter().all(|v| other_vars.contains(v)) {
+                    candidates.push((other, smallest.clone()));
+                } else if other_vars.iter().all(|v| small_vars.contains(v)) {
+                    candidates.push((smallest.clone(), other));
+                }
+            }
+        }
+        // try the most general candidates first, they tend to make the
+        // more specific ones derivable
+        candidates.sort_by_key(|(lhs, rhs)| {
+            let size = lhs.ast.as_ref().len() + rhs.ast.as_ref().len();
+            (std::cmp::Reverse(lhs.vars().len()), size)
+        });
+        candidates
+    }
+
+    /// Synthesizes rewrites that can't be derived from `rules`.
+    ///
+    /// Candidates are tried in the order of
+    /// [`candidates`](Synthesizer::candidates()). A candidate is kept if
+    /// a runner from `make_runner`, using `rules` and the rewrites kept so
+    /// far, can't prove its two sides equal.
+    /// `make_runner` should return a runner with an empty egraph;
+    /// use it to set limits, the analysis, and so on.
+    ///
+    /// Each rewrite is named `lhs => rhs`.
+    pub fn synthesize<N, IterData>(
+        &self,
+        rules: &[Rewrite<L, N>],
+        mut make_runner: impl FnMut() -> Runner<L, N, IterData>,
+    ) -> Vec<Rewrite<L, N>>
+    where
+        L: Send + Sync + 'static,
+        N: Analysis<L>,
+        IterData: IterationData<L, N>,
+    {
+        let mut synthesized: Vec<Rewrite<L, N>> = vec![];
+        for (lhs, rhs) in self.candidates() {
+            let (lhs_expr, rhs_expr) = (self.opaque(&lhs.ast), self.opaque(&rhs.ast));
+            let runner = make_runner()
+                .with_expr(&lhs_expr)
+                .with_expr(&rhs_expr)
+                .with_hook(|runner| {
+                    if runner.egraph.find(runner.roots[0]) == runner.egraph.find(runner.roots[1]) {
+                        Err("derived".into())
+                    } else {
+                        Ok(())
+                    }
+                })
+                .run(rules.iter().chain(&synthesized));
+            if runner.egraph.find(runner.roots[0]) == runner.egraph.find(runner.roots[1]) {
+                continue;
+            }
+
+            let name = format!("{} => {}", lhs, rhs);
+            match Rewrite::new(name.clone(), lhs, rhs) {
+                Ok(rw) => {
+                    debug!("Synthesized {}", name);
+                    synthesized.push(rw);
+                }
+                Err(e) => warn!("Dropping candidate {}: {}", name, e),
+            }
+        }
+        synthesized
+    }
+
+    fn eval_pattern(
+        &self,
+        ast: &PatternAst<L>,
+        env: &[(Var, Option<I::Value>)],
+    ) -> Option<I::Value> {
+        let mut values: Vec<Option<I::Value>> = Vec::with_capacity(ast.as_ref().len());
+        for node in ast.as_ref() {
+            let value = match node {
+                ENodeOrVar::Var(v) => env.iter().find(|(var, _)| var == v).unwrap().1.clone(),
+                ENodeOrVar::ENode(n) => {
+                    let children: Option<Vec<I::Value>> = n
+                        .children()
+                        .iter()
+                        .map(|&c| values[usize::from(c)].clone())
+                        .collect();
+                    children.and_then(|cs| self.interpreter.eval(n, &cs))
+                }
+            };
+            values.push(value);
+        }
+        values.pop().flatten()
+    }
+
+    // turn the pattern variables into leaves, so both sides can be added
+    // to an egraph and compared
+    fn opaque(&self, ast: &PatternAst<L>) -> RecExpr<L> {
+        ast.as_ref()
+            .iter()
+            .map(|node| match node {
+                ENodeOrVar::ENode(n) => n.clone(),
+                ENodeOrVar::Var(v) => match L::from_op(&v.to_string(), vec![]) {
+                    Ok(leaf) => leaf,
+                    Err(e) => panic!("Couldn't make a leaf for variable {}: {:?}", v, e),
+                },
+            })
+            .collect::<Vec<L>>()
+            .into()
+    }
+}
+
+// all ways to write `total` as an ordered sum of `parts` positive numbers
+fn compositions(total: usize, parts: usize) -> Vec<Vec<usize>> {
+    if parts == 0 {
+        return if total == 0 { vec![vec![]] } else { vec![] };
+    }
+    let mut result = vec![];
+    for first in 1..=total {
+        for mut rest in compositions(total - first, parts - 1) {
+            rest.insert(0, first);
+            result.push(rest);
+        }
+    }
+    result
+}
+
+fn build<L: Language>(op: &L, children: &[&PatternAst<L>]) -> PatternAst<L> {
+    let mut ast = PatternAst::default();
+    let mut roots = Vec::with_capacity(children.len());
+    for child in children {
+        let offset = ast.as_ref().len();
+        for node in child.as_ref() {
+            ast.add(
+                node.clone()
+                    .map_children(|c| Id::from(usize::from(c) + offset)),
+            );
+        }
+        roots.push(Id::from(ast.as_ref().len() - 1));
+    }
+    let mut roots = roots.into_iter();
+    ast.add(ENodeOrVar::ENode(
+        op.clone().map_children(|_| roots.next().unwrap()),
+    ));
+    ast
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    define_language! {
+        enum Arith {
+            Num(i64),
+            "+" = Add([Id; 2]),
+            "*" = Mul([Id; 2]),
+            Symbol(Symbol),
+        }
+    }
+
+    struct Eval;
+
+    impl Interpreter<Arith> for Eval {
+        type Value = i64;
+
+        fn eval(&self, enode: &Arith, args: &[i64]) -> Option<i64> {
+            match enode {
+                Arith::Num(n) => Some(*n),
+                Arith::Add(_) => args[0].checked_add(args[1]),
+                Arith::Mul(_) => args[0].checked_mul(args[1]),
+                Arith::Symbol(_) => None,
+            }
+        }
+
+        fn arbitrary(&self, rng: &mut FuzzRng) -> RecExpr<Arith> {
+            let mut expr = RecExpr::default();
+            expr.add(Arith::Num(rng.below(100) as i64 - 50));
+            expr
+        }
+    }
+
+    fn synth() -> Synthesizer<Arith, Eval> {
+        let ops = vec![Arith::Add([Id::from(0); 2]), Arith::Mul([Id::from(0); 2])];
+        Synthesizer::new(Eval, ops).with_leaves(vec![Arith::Num(0), Arith::Num(1)])
+    }
+
+    #[test]
+    fn enumerate_counts() {
+        // size 1: ?a ?b 0 1, size 2: none, size 3: 2 ops * 4 * 4
+        assert_eq!(synth().enumerate().len(), 4 + 2 * 16);
+        assert_eq!(synth().with_max_size(1).enumerate().len(), 4);
+    }
+
+    #[test]
+    fn synthesizes_sound_small_rule_set() {
+        crate::init_logger();
+        let rules: Vec<Rewrite<Arith, ()>> = synth().synthesize(&[], Runner::default);
+        let names: Vec<_> = rules.iter().map(|rw| rw.name.as_str()).collect();
+        for expected in &[
+            "(+ ?a 0) => ?a",
+            "(* ?a 1) => ?a",
+            "(* ?a 0) => 0",
+            "(+ ?b ?a) => (+ ?a ?b)",
+            "(* ?b ?a) => (* ?a ?b)",
+        ] {
+            assert!(names.contains(expected), "{} not in {:?}", expected, names);
+        }
+        // everything else is derivable from these
+        assert_eq!(names.len(), 5, "{:?}", names);
+
+        assert!(RuleFuzzer::new(Eval).check_all(&rules).is_empty());
+        assert!(synth().synthesize(&rules, Runner::default).is_empty());
+    }
+}
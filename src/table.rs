@@ -0,0 +1,352 @@
+use std::any::Any;
+use std::fmt::{self, Debug};
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use crate::*;
+
+/// A type-erased table, see [`EGraph::add_table`].
+pub(crate) trait AnyTable: Send + Sync {
+    /// Canonicalizes the keys with `find`, merging the values of keys that
+    /// became equal.
+    fn rebuild(&mut self, find: &dyn Fn(Id) -> Id);
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    fn clone_box(&self) -> Box<dyn AnyTable>;
+}
+
+impl Clone for Box<dyn AnyTable> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+type MergeFn<T> = Arc<dyn Fn(&T, &T) -> T + Send + Sync>;
+
+pub(crate) struct Table<T> {
+    merge: MergeFn<T>,
+    pub(crate) entries: HashMap<Box<[Id]>, T>,
+}
+
+impl<T: Clone + PartialEq> Table<T> {
+    pub(crate) fn new(merge: impl Fn(&T, &T) -> T + Send + Sync + 'static) -> Self {
+        Table {
+            merge: Arc::new(merge),
+            entries: HashMap::default(),
+        }
+    }
+
+    /// Merges `value` into the entry for `key`, returning whether it changed.
+    pub(crate) fn set(&mut self, key: Box<[Id]>, value: T) -> bool {
+        match self.entries.get_mut(&key) {
+            Some(old) => {
+                let new = (self.merge)(old, &value);
+                if new == *old {
+                    false
+                } else {
+                    *old = new;
+                    true
+                }
+            }
+            None => {
+                self.entries.insert(key, value);
+                true
+            }
+        }
+    }
+}
+
+impl<T> AnyTable for Table<T>
+where
+    T: Clone + PartialEq + Send + Sync + 'static,
+{
+    fn rebuild(&mut self, find: &dyn Fn(Id) -> Id) {
+        let entries = std::mem::take(&mut self.entries);
+        for (key, value) in entries {
+            let key: Box<[Id]> = key.iter().map(|&id| find(id)).collect();
+            self.set(key, value);
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn AnyTable> {
+        Box::new(Table {
+            merge: self.merge.clone(),
+            entries: self.entries.clone(),
+        })
+    }
+}
+
+/** A [`Searcher`] that reads an entry of a table, see [`EGraph::add_table`].
+
+For each match of the wrapped searcher, this looks up the entry of the table
+`table` whose key is the eclasses bound to `keys`.
+Matches without an entry are dropped, and the others bind the entry's value
+(of type `T`) to the variable `value`, for
+[`Subst::get_value`] to read in the [`Applier`], or in a [`TableSet`].
+Lookups can be nested to read several entries.
+
+See [`TableSet`] for an example.
+**/
+pub struct TableLookup<S, T> {
+    searcher: S,
+    table: Symbol,
+    keys: Vec<Var>,
+    value: Var,
+    phantom: PhantomData<fn() -> T>,
+}
+
+impl<S, T> TableLookup<S, T> {
+    /// Creates a [`TableLookup`] that binds the entry of `table` at `keys`
+    /// to `value` in each match of `searcher`.
+    pub fn new(searcher: S, table: impl Into<Symbol>, keys: Vec<Var>, value: Var) -> Self {
+        TableLookup {
+            searcher,
+            table: table.into(),
+            keys,
+            value,
+            phantom: PhantomData,
+        }
+    }
+
+    fn lookup<'a, L, N>(
+        &self,
+        egraph: &EGraph<L, N>,
+        mut matches: SearchMatches<'a, L>,
+    ) -> Option<SearchMatches<'a, L>>
+    where
+        L: Language,
+        N: Analysis<L>,
+        T: Clone + PartialEq + Send + Sync + 'static,
+    {
+        let substs = std::mem::take(&mut matches.substs);
+        matches.substs = substs
+            .into_iter()
+            .filter_map(|mut subst| {
+                let key = self
+                    .keys
+                    .iter()
+                    .map(|v| subst.get(*v).copied())
+                    .collect::<Option<Vec<Id>>>()?;
+                let value = egraph.table_get::<T>(self.table, &key)?.clone();
+                subst.insert_value(self.value, value);
+                Some(subst)
+            })
+            .collect();
+        if matches.substs.is_empty() {
+            None
+        } else {
+            Some(matches)
+        }
+    }
+}
+
+impl<S: Debug, T> Debug for TableLookup<S, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TableLookup")
+            .field("searcher", &self.searcher)
+            .field("table", &self.table)
+            .field("keys", &self.keys)
+            .field("value", &self.value)
+            .finish()
+    }
+}
+
+impl<L, N, S, T> Searcher<L, N> for TableLookup<S, T>
+where
+    L: Language,
+    N: Analysis<L>,
+    S: Searcher<L, N>,
+    T: Clone + PartialEq + Send + Sync + 'static,
+{
+    fn search_eclass_with_limit(
+        &self,
+        egraph: &EGraph<L, N>,
+        eclass: Id,
+        limit: usize,
+    ) -> Option<SearchMatches<'_, L>> {
+        let matches = self
+            .searcher
+            .search_eclass_with_limit(egraph, eclass, limit)?;
+        self.lookup(egraph, matches)
+    }
+
+    fn search_with_limit(&self, egraph: &EGraph<L, N>, limit: usize) -> Vec<SearchMatches<'_, L>> {
+        self.searcher
+            .search_with_limit(egraph, limit)
+            .into_iter()
+            .filter_map(|matches| self.lookup(egraph, matches))
+            .collect()
+    }
+
+    fn vars(&self) -> Vec<Var> {
+        let mut vars = self.searcher.vars();
+        vars.push(self.value);
+        vars
+    }
+}
+
+/** An [`Applier`] that writes an entry of a table, see
+[`EGraph::add_table`].
+
+For each match, this calls `f` with the egraph and the [`Subst`], and if it
+returns a value, merges it into the entry of the table `table` whose key is
+the eclasses bound to `keys`.
+The rule counts as applied when the entry changes, so a [`Runner`] does not
+saturate while table entries are still improving.
+
+# Example
+```
+use egg::*;
+
+// the size of the smallest term in each eclass, merged by `min`
+let mut egraph = EGraph::<SymbolLang, ()>::default();
+egraph.add_table("size", |a: &i64, b: &i64| *a.min(b));
+let root = egraph.add_expr(&"(+ (* x 1) (* x 1))".parse().unwrap());
+
+type G = EGraph<SymbolLang, ()>;
+fn var(s: &str) -> Var { s.parse().unwrap() }
+fn pat(s: &str) -> MultiPattern<SymbolLang> { s.parse().unwrap() }
+
+// leaves have size 1
+let leaf = || TableSet::new("size", vec![var("?e")], |_: &G, _: &Subst| Some(1i64));
+// binary nodes are one bigger than their children
+let children = |p: &str| {
+    let a = TableLookup::<_, i64>::new(pat(p), "size", vec![var("?a")], var("?sa"));
+    TableLookup::<_, i64>::new(a, "size", vec![var("?b")], var("?sb"))
+};
+let sum = || {
+    TableSet::new("size", vec![var("?e")], |_: &G, subst: &Subst| {
+        Some(1 + subst.get_value::<i64>(var("?sa"))? + subst.get_value::<i64>(var("?sb"))?)
+    })
+};
+
+let rules: Vec<Rewrite<SymbolLang, ()>> = vec![
+    Rewrite::new("size-x", pat("?e = x"), leaf()).unwrap(),
+    Rewrite::new("size-1", pat("?e = 1"), leaf()).unwrap(),
+    Rewrite::new("size-mul", children("?e = (* ?a ?b)"), sum()).unwrap(),
+    Rewrite::new("size-add", children("?e = (+ ?a ?b)"), sum()).unwrap(),
+    rewrite!("mul-1"; "(* ?a 1)" => "?a"),
+];
+let runner = Runner::default().with_egraph(egraph).run(&rules);
+assert_eq!(runner.egraph.table_get::<i64>("size", &[root]), Some(&3));
+```
+**/
+pub struct TableSet<T, F> {
+    table: Symbol,
+    keys: Vec<Var>,
+    f: F,
+    phantom: PhantomData<fn() -> T>,
+}
+
+impl<T, F> TableSet<T, F> {
+    /// Creates a [`TableSet`] that merges the value returned by `f` into the
+    /// entry of `table` at `keys`.
+    pub fn new(table: impl Into<Symbol>, keys: Vec<Var>, f: F) -> Self {
+        TableSet {
+            table: table.into(),
+            keys,
+            f,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T, F> Debug for TableSet<T, F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TableSet")
+            .field("table", &self.table)
+            .field("keys", &self.keys)
+            .finish()
+    }
+}
+
+impl<L, N, T, F> Applier<L, N> for TableSet<T, F>
+where
+    L: Language,
+    N: Analysis<L>,
+    T: Clone + PartialEq + Send + Sync + 'static,
+    F: Fn(&EGraph<L, N>, &Subst) -> Option<T>,
+{
+    fn apply_one(
+        &self,
+        egraph: &mut EGraph<L, N>,
+        eclass: Id,
+        subst: &Subst,
+        _searcher_ast: Option<&PatternAst<L>>,
+        _rule_name: Symbol,
+    ) -> Vec<Id> {
+        let key: Vec<Id> = self.keys.iter().map(|v| subst[*v]).collect();
+        match (self.f)(egraph, subst) {
+            Some(value) => {
+                if egraph.table_set(self.table, &key, value) {
+                    vec![eclass]
+                } else {
+                    vec![]
+                }
+            }
+            None => vec![],
+        }
+    }
+
+    fn vars(&self) -> Vec<Var> {
+        self.keys.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{SymbolLang as S, *};
+
+    #[test]
+    fn merge_on_rebuild() {
+        crate::init_logger();
+        let mut egraph = EGraph::<S, ()>::default();
+        egraph.add_table("dist", |a: &u32, b: &u32| *a.min(b));
+        egraph.add_table("names", |a: &Vec<&str>, b: &Vec<&str>| {
+            let mut names = a.clone();
+            names.extend(b.iter().filter(|n| !a.contains(n)));
+            names.sort_unstable();
+            names
+        });
+        let a = egraph.add(S::leaf("a"));
+        let b = egraph.add(S::leaf("b"));
+        let c = egraph.add(S::leaf("c"));
+        egraph.table_set("dist", &[a, b], 4u32);
+        egraph.table_set("dist", &[a, c], 2u32);
+        egraph.table_set("dist", &[b, c], 1u32);
+        egraph.table_set("names", &[b], vec!["b"]);
+        egraph.table_set("names", &[c], vec!["c", "see"]);
+
+        egraph.union(b, c);
+        egraph.rebuild();
+        assert_eq!(egraph.table_get::<u32>("dist", &[a, b]), Some(&2));
+        assert_eq!(egraph.table_get::<u32>("dist", &[c, b]), Some(&1));
+        assert_eq!(egraph.table_entries::<u32>("dist").count(), 2);
+        assert_eq!(
+            egraph.table_get::<Vec<&str>>("names", &[c]),
+            Some(&vec!["b", "c", "see"])
+        );
+
+        // the merged value is already the best, so nothing changes
+        assert!(!egraph.table_set("dist", &[a, c], 3u32));
+        assert!(egraph.remove_table("names"));
+        assert!(!egraph.remove_table("names"));
+    }
+
+    #[test]
+    #[should_panic(expected = "different value type")]
+    fn wrong_value_type() {
+        let mut egraph = EGraph::<S, ()>::default();
+        egraph.add_table("dist", |a: &u32, b: &u32| *a.min(b));
+        let a = egraph.add(S::leaf("a"));
+        egraph.table_get::<i64>("dist", &[a]);
+    }
+}
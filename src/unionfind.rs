@@ -18,6 +18,10 @@ impl UnionFind {
         self.parents.len()
     }
 
+    pub fn estimated_heap_size(&self) -> usize {
+        crate::util::vec_heap_size(&self.parents)
+    }
+
     fn parent(&self, query: Id) -> Id {
         self.parents[usize::from(query)]
     }
@@ -50,6 +50,25 @@ pub(crate) type IndexSet<K> = indexmap::IndexSet<K, BuildHasher>;
 pub(crate) type Instant = instant::Instant;
 pub(crate) type Duration = instant::Duration;
 
+/// Estimates the heap memory, in bytes, used by a [`HashMap`]'s table.
+///
+/// This does not include any heap memory owned by the keys or values.
+pub(crate) fn hashmap_heap_size<K, V>(map: &HashMap<K, V>) -> usize {
+    map.capacity() * (std::mem::size_of::<(K, V)>() + 1)
+}
+
+/// Estimates the heap memory, in bytes, used by a [`HashSet`]'s table.
+pub(crate) fn hashset_heap_size<K>(set: &HashSet<K>) -> usize {
+    set.capacity() * (std::mem::size_of::<K>() + 1)
+}
+
+/// Estimates the heap memory, in bytes, used by a [`Vec`]'s buffer.
+///
+/// This does not include any heap memory owned by the elements.
+pub(crate) fn vec_heap_size<T>(vec: &Vec<T>) -> usize {
+    vec.capacity() * std::mem::size_of::<T>()
+}
+
 pub(crate) fn concat_vecs<T>(to: &mut Vec<T>, mut from: Vec<T>) {
     if to.len() < from.len() {
         std::mem::swap(to, &mut from)
@@ -72,3 +72,70 @@ fn path2() {
     runner.egraph.check_not("(pred (path 4 1))");
     runner.egraph.check_not("(pred (path 3 1))");
 }
+
+#[test]
+fn sequence_vars() {
+    let mut egraph = EGraph::<Lang, ()>::default();
+    egraph.assert("(fact 1), (fact 1 2 3), (edge 1 2)");
+    let rules = vec![
+        multi_rewrite!("known"; "?x = true = (fact ?args...)" => "?x = (known ?args...)"),
+        multi_rewrite!("first"; "?x = true = (fact ?a ?rest...)" => "?x = (first ?a)"),
+        multi_rewrite!("last"; "?x = true = (fact ?init... ?a)" => "?x = (pred (last ?a))"),
+    ];
+    let mut runner = Runner::default().with_egraph(egraph).run(&rules);
+    runner.egraph.check("(known 1), (known 1 2 3), (first 1)");
+    runner.egraph.check("(pred (last 1)), (pred (last 3))");
+    runner
+        .egraph
+        .check_not("(known 1 2), (known 2 3), (first 2)");
+}
+
+#[test]
+fn query_paths() {
+    let mut egraph = EGraph::<Lang, ()>::default();
+    // added out of order, so the eclass ids of the leaves aren't sorted
+    egraph.assert("(edge 3 4), (edge 2 3), (edge 1 2), (edge 1 4)");
+    let rules = vec![
+        multi_rewrite!("base-case"; "?x = true = (edge ?a ?b)" => "?x = (path ?a ?b)"),
+        multi_rewrite!("transitive"; "?x = true = (path ?a ?b) = (edge ?b ?c)" => "?x = (path ?a ?c)"),
+    ];
+    let runner = Runner::default().with_egraph(egraph).run(&rules);
+    let egraph = &runner.egraph;
+
+    // (path 1 4) holds along two routes, but is one row
+    let pattern: MultiPattern<Lang> = "?x = true = (path ?a ?b)".parse().unwrap();
+    let query =
+        Query::new(pattern, vec!["?a".parse().unwrap(), "?b".parse().unwrap()]).with_sorted(true);
+    let rows: Vec<Vec<String>> = query
+        .run_extract(egraph, AstSize)
+        .iter()
+        .map(|row| row.iter().map(|e| e.to_string()).collect())
+        .collect();
+    assert_eq!(
+        rows,
+        [
+            ["1", "2"],
+            ["1", "3"],
+            ["1", "4"],
+            ["2", "3"],
+            ["2", "4"],
+            ["3", "4"]
+        ]
+    );
+    let limited = query.clone().with_limit(2).run_extract(egraph, AstSize);
+    assert_eq!(limited.len(), 2);
+    assert_eq!(limited[1][1].to_string(), "3");
+
+    // run only sorts by eclass id, which is the order the leaves were added
+    let ids = query.run(egraph);
+    assert_eq!(ids.len(), 6);
+    assert!(ids.iter().flatten().all(|&id| egraph.find(id) == id));
+    let leaf = |id: Id| egraph[id].nodes[0].to_string();
+    assert_eq!((leaf(ids[0][0]), leaf(ids[0][1])), ("3".into(), "4".into()));
+    assert_eq!(query.clone().with_limit(2).run(egraph), ids[..2]);
+
+    // a plain pattern works too
+    let pattern: Pattern<Lang> = "(path 1 ?b)".parse().unwrap();
+    let query = Query::new(pattern, vec!["?b".parse().unwrap()]);
+    assert_eq!(query.with_limit(5).run(egraph).len(), 3);
+}
@@ -0,0 +1,151 @@
+#![cfg(feature = "derive")]
+use egg::*;
+
+define_language! {
+    enum Macro {
+        "pi" = Pi,
+        #[sort(num <- num, num)] #[ac] "+" = Add([Id; 2]),
+        #[sort(num <- num)] "-" = Neg(Id),
+        "list" = List(Box<[Id]>),
+        "load" = Load { addr: Id },
+        #[sort(num)] Num(i32),
+        #[sort(bool)] Const { value: bool },
+        Cast { width: u8, arg: [Id; 1] },
+        "ext" = Ext(u8, Id),
+        #[ac] "max" = Max(u8, [Id; 2]),
+        "store" = Store { width: u8, args: [Id; 2] },
+        Call(Symbol, Vec<Id>),
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Language, FromOp, Display)]
+enum Derived<N> {
+    /// Same as `Macro::Pi`.
+    #[op = "pi"]
+    Pi,
+    #[ac]
+    #[sort(num <- num, num)]
+    #[op = "+"]
+    Add([Id; 2]),
+    #[sort(num <- num)]
+    #[op = "-"]
+    Neg(Id),
+    #[op = "list"]
+    List(Box<[Id]>),
+    #[op = "load"]
+    Load {
+        addr: Id,
+    },
+    #[sort(num)]
+    Num(N),
+    #[sort(bool)]
+    Const {
+        value: bool,
+    },
+    Cast {
+        width: u8,
+        arg: [Id; 1],
+    },
+    #[op = "ext"]
+    Ext(u8, Id),
+    #[ac]
+    #[op = "max"]
+    Max(u8, [Id; 2]),
+    #[op = "store"]
+    Store {
+        width: u8,
+        args: [Id; 2],
+    },
+    Call(Symbol, Vec<Id>),
+}
+
+#[test]
+fn derive_matches_define_language() {
+    let exprs = [
+        "pi",
+        "(+ 1 2)",
+        "(- (+ pi 3))",
+        "(list)",
+        "(list 1 2 3)",
+        "(f)",
+        "(f 1 (g pi))",
+        "(- 1 2)",
+        "(load true)",
+        "(8 (load 1))",
+        "(store.8 (ext.16 1) (max.8 pi 2))",
+        "(store (ext 1) (max.x 1 2))",
+    ];
+    for s in exprs {
+        let m: RecExpr<Macro> = s.parse().unwrap();
+        let d: RecExpr<Derived<i32>> = s.parse().unwrap();
+        assert_eq!(m.to_string(), d.to_string());
+        for (m, d) in m.as_ref().iter().zip(d.as_ref()) {
+            assert_eq!(m.to_string(), d.to_string());
+            assert_eq!(m.children(), d.children());
+            assert_eq!(m.is_ac(), d.is_ac());
+            assert_eq!(m.sort(), d.sort());
+            for i in 0..3 {
+                assert_eq!(m.child_sort(i), d.child_sort(i));
+            }
+            let doubled: Vec<Id> = m.children().iter().chain(m.children()).copied().collect();
+            assert_eq!(
+                m.with_children(doubled.clone()).is_some(),
+                d.with_children(doubled).is_some()
+            );
+        }
+    }
+
+    let d: RecExpr<Derived<i32>> = "(8 (load true))".parse().unwrap();
+    assert_eq!(d.as_ref()[0], Derived::Const { value: true });
+    assert_eq!(d.as_ref()[1], Derived::Load { addr: 0.into() });
+    assert_eq!(
+        d.as_ref()[2],
+        Derived::Cast {
+            width: 8,
+            arg: [1.into()]
+        }
+    );
+
+    let d: RecExpr<Derived<i32>> = "(store.8 1 (ext.16 2))".parse().unwrap();
+    assert_eq!(d.as_ref()[2], Derived::Ext(16, 1.into()));
+    assert_eq!(
+        d.as_ref()[3],
+        Derived::Store {
+            width: 8,
+            args: [0.into(), 2.into()]
+        }
+    );
+    assert!(!d.as_ref()[3].matches(&Derived::Store {
+        width: 16,
+        args: [0.into(), 2.into()]
+    }));
+}
+
+#[test]
+fn derive_sorts() {
+    let expr: RecExpr<Derived<i64>> = "(- (+ 1 pi))".parse().unwrap();
+    let add = &expr.as_ref()[2];
+    assert_eq!(add.sort(), Some("num".into()));
+    assert_eq!(add.child_sort(1), Some("num".into()));
+    assert_eq!(add.child_sort(2), None);
+    assert_eq!(expr.as_ref()[1].sort(), None);
+
+    let lhs: Pattern<Derived<i64>> = "(- ?a)".parse().unwrap();
+    let rhs: Pattern<Derived<i64>> = "true".parse().unwrap();
+    let err = Rewrite::<_, ()>::new("bad", lhs, rhs).unwrap_err();
+    assert!(err.contains("sort num to sort bool"), "{}", err);
+}
+
+#[test]
+fn derive_rewrites() {
+    let rules: Vec<Rewrite<Derived<i64>, ()>> = vec![
+        rewrite!("zero-add"; "(+ ?a 0)" => "?a"),
+        rewrite!("neg-neg"; "(- (- ?a))" => "?a"),
+        rewrite!("call-list"; "(f ?args...)" => "(list ?args...)"),
+    ];
+    let expr: RecExpr<Derived<i64>> = "(+ 0 (- (- (f 1 2 pi))))".parse().unwrap();
+    let runner = Runner::default().with_expr(&expr).run(&rules);
+    let extractor = Extractor::new(&runner.egraph, AstSize);
+    let (_, best) = extractor.find_best(runner.roots[0]);
+    assert_eq!(best.to_string(), "(list 1 2 pi)");
+}